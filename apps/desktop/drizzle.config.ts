@@ -37,7 +37,7 @@ const databasePath =
 
 export default defineConfig({
   dialect: "sqlite",
-  schema: "../../packages/database/src/schema.ts",
+  schema: ["../../packages/database/src/schema.ts", "./src/database/syncSchema.ts"],
   out: "./src-tauri/migrations",
   verbose: true,
   strict: true,
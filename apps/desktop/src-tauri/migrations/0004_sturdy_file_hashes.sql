@@ -0,0 +1,25 @@
+ALTER TABLE `albums` ADD `thumbnail_sha256` text(64);--> statement-breakpoint
+ALTER TABLE `artists` ADD `thumbnail_sha256` text(64);--> statement-breakpoint
+ALTER TABLE `playlists` ADD `thumbnail_sha256` text(64);--> statement-breakpoint
+ALTER TABLE `songs` ADD `file_sha256` text(64);--> statement-breakpoint
+ALTER TABLE `songs` ADD `thumbnail_sha256` text(64);--> statement-breakpoint
+CREATE TRIGGER `songs_file_sha256_reset` AFTER UPDATE OF `file` ON `songs`
+WHEN NEW.`file` IS NOT OLD.`file` AND NEW.`file_sha256` IS OLD.`file_sha256` BEGIN
+  UPDATE `songs` SET `file_sha256` = NULL WHERE `id` = NEW.`id`;
+END;--> statement-breakpoint
+CREATE TRIGGER `songs_thumbnail_sha256_reset` AFTER UPDATE OF `thumbnail` ON `songs`
+WHEN NEW.`thumbnail` IS NOT OLD.`thumbnail` AND NEW.`thumbnail_sha256` IS OLD.`thumbnail_sha256` BEGIN
+  UPDATE `songs` SET `thumbnail_sha256` = NULL WHERE `id` = NEW.`id`;
+END;--> statement-breakpoint
+CREATE TRIGGER `albums_thumbnail_sha256_reset` AFTER UPDATE OF `thumbnail` ON `albums`
+WHEN NEW.`thumbnail` IS NOT OLD.`thumbnail` AND NEW.`thumbnail_sha256` IS OLD.`thumbnail_sha256` BEGIN
+  UPDATE `albums` SET `thumbnail_sha256` = NULL WHERE `id` = NEW.`id`;
+END;--> statement-breakpoint
+CREATE TRIGGER `artists_thumbnail_sha256_reset` AFTER UPDATE OF `thumbnail` ON `artists`
+WHEN NEW.`thumbnail` IS NOT OLD.`thumbnail` AND NEW.`thumbnail_sha256` IS OLD.`thumbnail_sha256` BEGIN
+  UPDATE `artists` SET `thumbnail_sha256` = NULL WHERE `id` = NEW.`id`;
+END;--> statement-breakpoint
+CREATE TRIGGER `playlists_thumbnail_sha256_reset` AFTER UPDATE OF `thumbnail` ON `playlists`
+WHEN NEW.`thumbnail` IS NOT OLD.`thumbnail` AND NEW.`thumbnail_sha256` IS OLD.`thumbnail_sha256` BEGIN
+  UPDATE `playlists` SET `thumbnail_sha256` = NULL WHERE `id` = NEW.`id`;
+END;
\ No newline at end of file
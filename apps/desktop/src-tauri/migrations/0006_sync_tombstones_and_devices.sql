@@ -0,0 +1,100 @@
+CREATE TABLE IF NOT EXISTS `sync_access_tokens` (
+	`id` text PRIMARY KEY NOT NULL,
+	`label` text NOT NULL,
+	`scopes` text NOT NULL,
+	`playlists` text NOT NULL,
+	`albums` text NOT NULL,
+	`created_at` integer DEFAULT (unixepoch()) NOT NULL,
+	`expires_at` integer NOT NULL,
+	`revoked_at` integer
+);
+--> statement-breakpoint
+CREATE TABLE IF NOT EXISTS `sync_devices` (
+	`id` text PRIMARY KEY NOT NULL,
+	`name` text NOT NULL,
+	`secret_hash` text NOT NULL,
+	`created_at` integer DEFAULT (unixepoch()) NOT NULL,
+	`last_seen_at` integer,
+	`revoked_at` integer
+);
+--> statement-breakpoint
+CREATE TABLE IF NOT EXISTS `sync_meta` (
+	`key` text PRIMARY KEY NOT NULL,
+	`value` text NOT NULL
+);
+--> statement-breakpoint
+CREATE TABLE IF NOT EXISTS `sync_tombstones` (
+	`id` integer PRIMARY KEY AUTOINCREMENT NOT NULL,
+	`entity_type` text NOT NULL,
+	`fingerprint` text NOT NULL,
+	`deleted_at` integer DEFAULT (unixepoch()) NOT NULL
+);
+--> statement-breakpoint
+CREATE INDEX IF NOT EXISTS `sync_tombstones_deleted_at_idx` ON `sync_tombstones` (`deleted_at`);--> statement-breakpoint
+CREATE INDEX IF NOT EXISTS `sync_tombstones_entity_idx` ON `sync_tombstones` (`entity_type`,`fingerprint`);--> statement-breakpoint
+CREATE TRIGGER IF NOT EXISTS sync_songs_tombstone AFTER DELETE ON songs
+WHEN OLD.fingerprint IS NOT NULL BEGIN
+  INSERT INTO sync_tombstones (entity_type, fingerprint) VALUES ('song', OLD.fingerprint);
+END;--> statement-breakpoint
+CREATE TRIGGER IF NOT EXISTS sync_albums_tombstone AFTER DELETE ON albums
+WHEN OLD.fingerprint IS NOT NULL BEGIN
+  INSERT INTO sync_tombstones (entity_type, fingerprint) VALUES ('album', OLD.fingerprint);
+END;--> statement-breakpoint
+CREATE TRIGGER IF NOT EXISTS sync_artists_tombstone AFTER DELETE ON artists
+WHEN OLD.fingerprint IS NOT NULL BEGIN
+  INSERT INTO sync_tombstones (entity_type, fingerprint) VALUES ('artist', OLD.fingerprint);
+END;--> statement-breakpoint
+CREATE TRIGGER IF NOT EXISTS sync_playlists_tombstone AFTER DELETE ON playlists
+WHEN OLD.fingerprint IS NOT NULL BEGIN
+  INSERT INTO sync_tombstones (entity_type, fingerprint) VALUES ('playlist', OLD.fingerprint);
+END;--> statement-breakpoint
+CREATE TRIGGER IF NOT EXISTS sync_playlist_songs_tombstone BEFORE DELETE ON playlist_songs BEGIN
+  INSERT INTO sync_tombstones (entity_type, fingerprint)
+  SELECT 'playlistSong', p.fingerprint || ':' || s.fingerprint
+  FROM playlists p, songs s
+  WHERE p.id = OLD.playlist_id AND s.id = OLD.song_id
+    AND p.fingerprint IS NOT NULL AND s.fingerprint IS NOT NULL;
+END;--> statement-breakpoint
+CREATE TRIGGER IF NOT EXISTS sync_songs_touch
+AFTER UPDATE OF name, thumbnail, file, duration, is_favorite, release_year, album_id, lyrics, fingerprint
+ON songs WHEN NEW.updated_at = OLD.updated_at BEGIN
+  UPDATE songs SET updated_at = unixepoch() WHERE id = NEW.id;
+END;--> statement-breakpoint
+CREATE TRIGGER IF NOT EXISTS sync_albums_touch
+AFTER UPDATE OF name, thumbnail, release_year, is_favorite, album_type, fingerprint
+ON albums WHEN NEW.updated_at = OLD.updated_at BEGIN
+  UPDATE albums SET updated_at = unixepoch() WHERE id = NEW.id;
+END;--> statement-breakpoint
+CREATE TRIGGER IF NOT EXISTS sync_artists_touch
+AFTER UPDATE OF name, thumbnail, is_favorite, fingerprint
+ON artists WHEN NEW.updated_at = OLD.updated_at BEGIN
+  UPDATE artists SET updated_at = unixepoch() WHERE id = NEW.id;
+END;--> statement-breakpoint
+CREATE TRIGGER IF NOT EXISTS sync_playlists_touch
+AFTER UPDATE OF name, thumbnail, is_favorite, fingerprint
+ON playlists WHEN NEW.updated_at = OLD.updated_at BEGIN
+  UPDATE playlists SET updated_at = unixepoch() WHERE id = NEW.id;
+END;--> statement-breakpoint
+CREATE TRIGGER IF NOT EXISTS sync_song_artists_insert_touch AFTER INSERT ON song_artists BEGIN
+  UPDATE songs SET updated_at = unixepoch() WHERE id = NEW.song_id;
+END;--> statement-breakpoint
+CREATE TRIGGER IF NOT EXISTS sync_song_artists_delete_touch AFTER DELETE ON song_artists BEGIN
+  UPDATE songs SET updated_at = unixepoch() WHERE id = OLD.song_id;
+END;--> statement-breakpoint
+CREATE TRIGGER IF NOT EXISTS sync_album_artists_insert_touch AFTER INSERT ON album_artists BEGIN
+  UPDATE albums SET updated_at = unixepoch() WHERE id = NEW.album_id;
+END;--> statement-breakpoint
+CREATE TRIGGER IF NOT EXISTS sync_album_artists_delete_touch AFTER DELETE ON album_artists BEGIN
+  UPDATE albums SET updated_at = unixepoch() WHERE id = OLD.album_id;
+END;--> statement-breakpoint
+CREATE TRIGGER IF NOT EXISTS sync_playlist_songs_insert_touch AFTER INSERT ON playlist_songs BEGIN
+  UPDATE playlists SET updated_at = unixepoch() WHERE id = NEW.playlist_id;
+END;--> statement-breakpoint
+CREATE TRIGGER IF NOT EXISTS sync_playlist_songs_delete_touch AFTER DELETE ON playlist_songs BEGIN
+  UPDATE playlists SET updated_at = unixepoch() WHERE id = OLD.playlist_id;
+END;--> statement-breakpoint
+CREATE TRIGGER IF NOT EXISTS sync_playlist_songs_position_touch
+AFTER UPDATE OF position ON playlist_songs
+WHEN NEW.position <> OLD.position BEGIN
+  UPDATE playlists SET updated_at = unixepoch() WHERE id = NEW.playlist_id;
+END;
\ No newline at end of file
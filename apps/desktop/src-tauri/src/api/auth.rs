@@ -1,41 +1,270 @@
+use std::net::SocketAddr;
+use std::path::PathBuf;
 use std::sync::Arc;
 
+use sha2::{Digest, Sha256};
+
+use subtle::ConstantTimeEq;
+
 use uuid::Uuid;
 
-use warp::http::StatusCode;
-use warp::{reject, Filter, Rejection};
+use warp::http::{header, StatusCode};
+use warp::{reject, Filter, Rejection, Reply};
+
+use super::compression::BodyRejection;
+use super::db;
+use super::guard::{AuthGuard, Throttled};
+use super::sync_state::SyncDevice;
+use super::tokens::{self, AccessClaims, Scope};
+use super::SyncStatus;
 
 pub fn generate_token() -> String {
     Uuid::new_v4().to_string()
 }
 
+pub fn generate_device_secret() -> String {
+    format!("{}{}", Uuid::new_v4().simple(), Uuid::new_v4().simple())
+}
+
+pub fn hash_secret(secret: &str) -> String {
+    format!("{:x}", Sha256::digest(secret.as_bytes()))
+}
+
+fn constant_time_eq(a: &str, b: &str) -> bool {
+    a.as_bytes().ct_eq(b.as_bytes()).into()
+}
+
+pub fn device_credential(device_id: &str, secret: &str) -> String {
+    format!("{}.{}", device_id, secret)
+}
+
 #[derive(Debug)]
 struct Unauthorized;
 
 impl reject::Reject for Unauthorized {}
 
-pub fn with_auth(token: Arc<String>) -> impl Filter<Extract = (), Error = Rejection> + Clone {
-    warp::header::optional::<String>("authorization")
-        .and_then(move |auth_header: Option<String>| {
-            let expected = token.clone();
-            async move {
-                match auth_header {
-                    Some(header) if header.starts_with("Bearer ") => {
-                        let provided = &header[7..];
-                        if provided == expected.as_str() {
-                            Ok(())
-                        } else {
-                            Err(reject::custom(Unauthorized))
+#[derive(Debug)]
+pub struct PendingApproval;
+
+impl reject::Reject for PendingApproval {}
+
+#[derive(Debug)]
+pub struct Forbidden(pub String);
+
+impl reject::Reject for Forbidden {}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Caller {
+    Session,
+    Device { id: String, name: String },
+    Token(AccessClaims),
+}
+
+impl Caller {
+    pub fn sync_device(&self) -> Option<SyncDevice> {
+        match self {
+            Caller::Device { id, name } => Some(SyncDevice {
+                id: id.clone(),
+                name: name.clone(),
+            }),
+            Caller::Session | Caller::Token(_) => None,
+        }
+    }
+
+    pub fn allowed_collections(&self) -> Option<&AccessClaims> {
+        match self {
+            Caller::Token(claims) if claims.is_restricted() => Some(claims),
+            _ => None,
+        }
+    }
+}
+
+pub struct Authenticator {
+    session_token: String,
+    db_path: Arc<PathBuf>,
+    sync_status: SyncStatus,
+    guard: Arc<AuthGuard>,
+}
+
+impl Authenticator {
+    pub fn new(
+        session_token: String,
+        db_path: Arc<PathBuf>,
+        sync_status: SyncStatus,
+        guard: Arc<AuthGuard>,
+    ) -> Self {
+        Self {
+            session_token,
+            db_path,
+            sync_status,
+            guard,
+        }
+    }
+
+    pub fn session_token(&self) -> &str {
+        &self.session_token
+    }
+
+    pub fn db_path(&self) -> &Arc<PathBuf> {
+        &self.db_path
+    }
+
+    fn authenticate_device(&self, device_id: &str, secret: &str) -> Result<Option<Caller>, String> {
+        let conn = db::open_readwrite(&self.db_path).map_err(|e| e.to_string())?;
+        let Some((device, secret_hash)) =
+            db::get_active_device_secret_hash(&conn, device_id).map_err(|e| e.to_string())?
+        else {
+            return Ok(None);
+        };
+
+        if !constant_time_eq(&hash_secret(secret), &secret_hash) {
+            return Ok(None);
+        }
+
+        if let Err(e) = db::touch_device(&conn, &device.id) {
+            log::warn!("Failed to update last seen for device {}: {}", device.id, e);
+        }
+
+        Ok(Some(Caller::Device {
+            id: device.id,
+            name: device.name,
+        }))
+    }
+
+    fn authenticate_access_token(&self, provided: &str) -> Result<Option<Caller>, String> {
+        let conn = db::open_readonly(&self.db_path).map_err(|e| e.to_string())?;
+        let Some(key) = db::get_access_token_key(&conn).map_err(|e| e.to_string())? else {
+            return Ok(None);
+        };
+
+        let Some(claims) = tokens::verify(&key, provided, chrono::Utc::now().timestamp()) else {
+            return Ok(None);
+        };
+        if !db::is_access_token_active(&conn, &claims.id).map_err(|e| e.to_string())? {
+            return Ok(None);
+        }
+
+        Ok(Some(Caller::Token(claims)))
+    }
+
+    fn authenticate(&self, provided: &str) -> Option<Caller> {
+        let caller = if constant_time_eq(provided, &self.session_token) {
+            Some(Caller::Session)
+        } else if tokens::is_access_token(provided) {
+            match self.authenticate_access_token(provided) {
+                Ok(caller) => caller,
+                Err(e) => {
+                    log::error!("Failed to verify access token: {}", e);
+                    None
+                }
+            }
+        } else {
+            let (device_id, secret) = provided.split_once('.')?;
+            match self.authenticate_device(device_id, secret) {
+                Ok(caller) => caller,
+                Err(e) => {
+                    log::error!("Failed to verify device credential: {}", e);
+                    None
+                }
+            }
+        };
+
+        if let Some(device) = caller.as_ref().and_then(Caller::sync_device) {
+            self.sync_status.set_device(device);
+        }
+
+        caller
+    }
+}
+
+fn session_only_pairs() -> Forbidden {
+    Forbidden("The session token can only be used to pair a device".to_string())
+}
+
+fn bearer_token(auth_header: Option<String>) -> Option<String> {
+    auth_header?.strip_prefix("Bearer ").map(str::to_string)
+}
+
+pub fn with_caller(
+    auth: Arc<Authenticator>,
+) -> impl Filter<Extract = (Caller,), Error = Rejection> + Clone {
+    warp::addr::remote()
+        .and(warp::header::optional::<String>("authorization"))
+        .and_then(
+            move |remote: Option<SocketAddr>, auth_header: Option<String>| {
+                let auth = auth.clone();
+                async move {
+                    let caller = match bearer_token(auth_header) {
+                        Some(provided) => {
+                            let auth = auth.clone();
+                            tokio::task::spawn_blocking(move || auth.authenticate(&provided))
+                                .await
+                                .ok()
+                                .flatten()
+                        }
+                        None => None,
+                    };
+
+                    if let Some(remote) = remote {
+                        match caller {
+                            Some(_) => auth.guard.record_success(remote.ip()),
+                            None => auth.guard.record_failure(remote.ip()),
                         }
                     }
-                    _ => Err(reject::custom(Unauthorized)),
+
+                    caller.ok_or_else(|| reject::custom(Unauthorized))
                 }
+            },
+        )
+}
+
+pub fn with_scoped_caller(
+    auth: Arc<Authenticator>,
+    scope: Scope,
+) -> impl Filter<Extract = (Caller,), Error = Rejection> + Clone {
+    with_caller(auth).and_then(move |caller: Caller| async move {
+        match &caller {
+            Caller::Device { .. } => Ok(caller),
+            Caller::Token(claims) if claims.has_scope(scope) => Ok(caller),
+            Caller::Token(_) => Err(reject::custom(Forbidden(format!(
+                "Token is missing the {} scope",
+                scope.as_str()
+            )))),
+            Caller::Session => Err(reject::custom(session_only_pairs())),
+        }
+    })
+}
+
+pub fn with_scope(
+    auth: Arc<Authenticator>,
+    scope: Scope,
+) -> impl Filter<Extract = (), Error = Rejection> + Clone {
+    with_scoped_caller(auth, scope)
+        .and_then(|caller: Caller| async move {
+            match caller.allowed_collections() {
+                None => Ok(()),
+                Some(_) => Err(reject::custom(Forbidden(
+                    "Token is limited to specific playlists or albums".to_string(),
+                ))),
+            }
+        })
+        .untuple_one()
+}
+
+pub fn with_session_token(
+    auth: Arc<Authenticator>,
+) -> impl Filter<Extract = (), Error = Rejection> + Clone {
+    with_caller(auth)
+        .and_then(|caller: Caller| async move {
+            match caller {
+                Caller::Session => Ok(()),
+                Caller::Device { .. } | Caller::Token(_) => Err(reject::custom(Unauthorized)),
             }
         })
         .untuple_one()
 }
 
-pub async fn handle_rejection(err: Rejection) -> Result<impl warp::Reply, Rejection> {
+pub async fn handle_rejection(err: Rejection) -> Result<warp::reply::Response, Rejection> {
     if err.find::<Unauthorized>().is_some() {
         Ok(warp::reply::with_status(
             warp::reply::json(&serde_json::json!({
@@ -43,7 +272,58 @@ pub async fn handle_rejection(err: Rejection) -> Result<impl warp::Reply, Reject
                 "message": "Invalid or missing Bearer token"
             })),
             StatusCode::UNAUTHORIZED,
-        ))
+        )
+        .into_response())
+    } else if err.find::<PendingApproval>().is_some() {
+        Ok(warp::reply::with_status(
+            warp::reply::json(&serde_json::json!({
+                "error": "PendingApproval",
+                "message": "Pairing is waiting for approval on the desktop"
+            })),
+            StatusCode::FORBIDDEN,
+        )
+        .into_response())
+    } else if let Some(Forbidden(message)) = err.find::<Forbidden>() {
+        Ok(warp::reply::with_status(
+            warp::reply::json(&serde_json::json!({
+                "error": "Forbidden",
+                "message": message
+            })),
+            StatusCode::FORBIDDEN,
+        )
+        .into_response())
+    } else if let Some(throttled) = err.find::<Throttled>() {
+        let (error, message) = if throttled.locked {
+            ("Locked", "Too many failed authentication attempts")
+        } else {
+            ("TooManyRequests", "Rate limit exceeded")
+        };
+        Ok(warp::reply::with_header(
+            warp::reply::with_status(
+                warp::reply::json(&serde_json::json!({
+                    "error": error,
+                    "message": message,
+                    "retryAfterSecs": throttled.retry_after_secs
+                })),
+                StatusCode::TOO_MANY_REQUESTS,
+            ),
+            header::RETRY_AFTER,
+            throttled.retry_after_secs.to_string(),
+        )
+        .into_response())
+    } else if let Some(rejection) = err.find::<BodyRejection>() {
+        let (status, message) = match rejection {
+            BodyRejection::UnsupportedEncoding(encoding) => (
+                StatusCode::UNSUPPORTED_MEDIA_TYPE,
+                format!("Unsupported Content-Encoding: {}", encoding),
+            ),
+            BodyRejection::Invalid(message) => (StatusCode::BAD_REQUEST, message.clone()),
+        };
+        Ok(warp::reply::with_status(
+            warp::reply::json(&serde_json::json!({ "error": message })),
+            status,
+        )
+        .into_response())
     } else {
         Err(err)
     }
@@ -0,0 +1,40 @@
+use base64::engine::general_purpose::STANDARD as BASE64;
+use base64::Engine;
+
+use sha2::{Digest, Sha256};
+
+use std::fs::File;
+use std::io::{self, Read};
+use std::path::Path;
+
+const HASH_BUFFER_SIZE: usize = 64 * 1024;
+
+pub fn sha256_reader<R: Read>(reader: &mut R) -> io::Result<(String, u64)> {
+    let mut hasher = Sha256::new();
+    let mut buffer = vec![0u8; HASH_BUFFER_SIZE];
+    let mut total = 0u64;
+
+    loop {
+        let read = reader.read(&mut buffer)?;
+        if read == 0 {
+            break;
+        }
+        hasher.update(&buffer[..read]);
+        total += read as u64;
+    }
+
+    Ok((format!("{:x}", hasher.finalize()), total))
+}
+
+pub fn sha256_file(path: &Path) -> io::Result<String> {
+    let mut file = File::open(path)?;
+    sha256_reader(&mut file).map(|(hash, _)| hash)
+}
+
+pub fn digest_header_value(sha256_hex: &str) -> Option<String> {
+    let bytes = (0..sha256_hex.len())
+        .step_by(2)
+        .map(|i| u8::from_str_radix(sha256_hex.get(i..i + 2)?, 16).ok())
+        .collect::<Option<Vec<u8>>>()?;
+    Some(format!("sha-256={}", BASE64.encode(bytes)))
+}
@@ -1,27 +1,126 @@
 use super::{
-    generate_qr_data as internal_generate_qr_data, get_server_info as internal_get_server_info,
-    get_sync_status as internal_get_sync_status, is_server_running as internal_is_server_running,
-    start_api_server, stop_api_server, ServerInfo,
+    config, discovery, generate_qr_data as internal_generate_qr_data,
+    get_server_info as internal_get_server_info, get_sync_status as internal_get_sync_status,
+    guard, is_server_running as internal_is_server_running,
+    list_blocked_clients as internal_list_blocked_clients,
+    list_pairing_requests as internal_list_pairing_requests, network, pairing, resolve_pairing,
+    start_api_server, stop_api_server, sync_state, EventSink, ServerInfo, SERVER_STATE_EVENT,
 };
 
 use super::db;
+use super::pairing_routes::normalize_device_name;
+use super::tokens;
 
 use tauri::command;
-use tauri::Manager;
+use tauri::{Emitter, Manager};
+
+use tauri_plugin_store::StoreExt;
+
+pub fn load_server_config(app: &tauri::AppHandle) -> Result<config::ServerConfig, String> {
+    let store = app
+        .store(config::SERVER_CONFIG_STORE)
+        .map_err(|e| format!("Failed to open server settings: {}", e))?;
+
+    match store.get(config::SERVER_CONFIG_KEY) {
+        Some(value) => {
+            serde_json::from_value(value).map_err(|e| format!("Invalid server settings: {}", e))
+        }
+        None => Ok(config::ServerConfig::default()),
+    }
+}
 
 #[command]
-pub async fn start_server(app: tauri::AppHandle) -> Result<ServerInfo, String> {
+pub async fn get_server_config(app: tauri::AppHandle) -> Result<config::ServerConfig, String> {
+    load_server_config(&app)
+}
+
+#[command]
+pub async fn set_server_config(
+    app: tauri::AppHandle,
+    mut server_config: config::ServerConfig,
+) -> Result<config::ServerConfig, String> {
+    server_config.validate()?;
+    server_config.device_name = server_config
+        .device_name
+        .as_deref()
+        .map(normalize_device_name)
+        .transpose()?;
+    network::AllowList::parse(&server_config.network.allowed_networks)?;
+
+    let value = serde_json::to_value(&server_config)
+        .map_err(|e| format!("Failed to encode server settings: {}", e))?;
+    let store = app
+        .store(config::SERVER_CONFIG_STORE)
+        .map_err(|e| format!("Failed to open server settings: {}", e))?;
+    store.set(config::SERVER_CONFIG_KEY, value);
+    store
+        .save()
+        .map_err(|e| format!("Failed to save server settings: {}", e))?;
+
+    Ok(server_config)
+}
+
+pub fn serves_in_background(app: &tauri::AppHandle) -> bool {
+    match load_server_config(app) {
+        Ok(server_config) => server_config.serve_in_background,
+        Err(e) => {
+            log::warn!("{}", e);
+            false
+        }
+    }
+}
+
+fn emit_server_state(app: &tauri::AppHandle, server_info: Option<&ServerInfo>) {
+    if let Err(e) = app.emit(
+        SERVER_STATE_EVENT,
+        serde_json::json!({
+            "running": server_info.is_some(),
+            "port": server_info.map(|info| info.port),
+            "requestedPort": server_info.map(|info| info.requested_port)
+        }),
+    ) {
+        log::warn!("Failed to emit {}: {}", SERVER_STATE_EVENT, e);
+    }
+}
+
+pub fn event_sink(app: &tauri::AppHandle) -> EventSink {
+    let emitter = app.clone();
+    std::sync::Arc::new(move |event: &str, payload| {
+        if let Err(e) = emitter.emit(event, payload) {
+            log::warn!("Failed to emit {}: {}", event, e);
+        }
+    })
+}
+
+pub async fn start_server_for_app(app: &tauri::AppHandle) -> Result<ServerInfo, String> {
+    let mut server_config = load_server_config(app)?;
+    if server_config.device_name.is_none() {
+        server_config.device_name = normalize_device_name(&tauri_plugin_os::hostname()).ok();
+    }
+
     let app_data_dir = app
         .path()
         .app_data_dir()
         .map_err(|e| format!("Failed to get app data directory: {}", e))?;
 
-    start_api_server(app_data_dir).await
+    let server_info = start_api_server(app_data_dir, event_sink(app), server_config).await?;
+    emit_server_state(app, Some(&server_info));
+    Ok(server_info)
 }
 
-#[command]
-pub async fn stop_server() -> Result<(), String> {
+pub async fn stop_server_for_app(app: &tauri::AppHandle) {
     stop_api_server().await;
+    emit_server_state(app, None);
+}
+
+#[command]
+pub async fn start_server(app: tauri::AppHandle) -> Result<ServerInfo, String> {
+    start_server_for_app(&app).await
+}
+
+#[command]
+pub async fn stop_server(app: tauri::AppHandle) -> Result<(), String> {
+    stop_server_for_app(&app).await;
     Ok(())
 }
 
@@ -41,10 +140,36 @@ pub async fn get_qr_data() -> Option<String> {
 }
 
 #[command]
-pub async fn get_sync_status() -> String {
+pub async fn get_sync_status() -> sync_state::SyncSnapshot {
     internal_get_sync_status().await
 }
 
+#[command]
+pub async fn list_network_interfaces() -> Result<Vec<network::NetworkInterface>, String> {
+    tokio::task::spawn_blocking(network::list_interfaces)
+        .await
+        .map_err(|e| format!("Task failed: {}", e))?
+}
+
+#[command]
+pub async fn discover_sync_servers(
+    timeout_ms: Option<u64>,
+) -> Result<Vec<discovery::DiscoveredServer>, String> {
+    let timeout = std::time::Duration::from_millis(timeout_ms.unwrap_or(3000));
+    let own_service = internal_get_server_info()
+        .await
+        .and_then(|info| info.service_name);
+
+    tokio::task::spawn_blocking(move || discovery::browse(timeout, own_service.as_deref()))
+        .await
+        .map_err(|e| format!("Task failed: {}", e))?
+}
+
+#[command]
+pub async fn list_blocked_clients() -> Vec<guard::BlockedClient> {
+    internal_list_blocked_clients().await
+}
+
 #[command]
 pub async fn backfill_fingerprints(app: tauri::AppHandle) -> Result<u64, String> {
     let app_data_dir = app
@@ -61,3 +186,138 @@ pub async fn backfill_fingerprints(app: tauri::AppHandle) -> Result<u64, String>
     .await
     .map_err(|e| format!("Task failed: {}", e))?
 }
+
+fn resolve_sync_db_path(app: &tauri::AppHandle) -> Result<std::path::PathBuf, String> {
+    let app_data_dir = app
+        .path()
+        .app_data_dir()
+        .map_err(|e| format!("Failed to get app data directory: {}", e))?;
+
+    Ok(db::resolve_db_path(&app_data_dir))
+}
+
+#[command]
+pub async fn list_paired_devices(app: tauri::AppHandle) -> Result<Vec<db::PairedDevice>, String> {
+    let db_path = resolve_sync_db_path(&app)?;
+
+    tokio::task::spawn_blocking(move || {
+        let conn = db::open_readwrite(&db_path).map_err(|e| e.to_string())?;
+        db::list_devices(&conn).map_err(|e| format!("Failed to list devices: {}", e))
+    })
+    .await
+    .map_err(|e| format!("Task failed: {}", e))?
+}
+
+#[command]
+pub async fn rename_paired_device(
+    app: tauri::AppHandle,
+    device_id: String,
+    name: String,
+) -> Result<(), String> {
+    let db_path = resolve_sync_db_path(&app)?;
+    let name = normalize_device_name(&name)?;
+
+    tokio::task::spawn_blocking(move || {
+        let conn = db::open_readwrite(&db_path).map_err(|e| e.to_string())?;
+        match db::rename_device(&conn, &device_id, &name) {
+            Ok(true) => Ok(()),
+            Ok(false) => Err(format!("Device {} not found", device_id)),
+            Err(e) => Err(format!("Failed to rename device: {}", e)),
+        }
+    })
+    .await
+    .map_err(|e| format!("Task failed: {}", e))?
+}
+
+#[command]
+pub async fn revoke_paired_device(app: tauri::AppHandle, device_id: String) -> Result<(), String> {
+    let db_path = resolve_sync_db_path(&app)?;
+
+    tokio::task::spawn_blocking(move || {
+        let conn = db::open_readwrite(&db_path).map_err(|e| e.to_string())?;
+        match db::revoke_device(&conn, &device_id) {
+            Ok(true) => Ok(()),
+            Ok(false) => Err(format!("Device {} not found or already revoked", device_id)),
+            Err(e) => Err(format!("Failed to revoke device: {}", e)),
+        }
+    })
+    .await
+    .map_err(|e| format!("Task failed: {}", e))?
+}
+
+#[command]
+pub async fn list_pairing_requests() -> Vec<pairing::PairingRequest> {
+    internal_list_pairing_requests().await
+}
+
+async fn respond_to_pairing(
+    app: tauri::AppHandle,
+    pairing_id: String,
+    approve: bool,
+) -> Result<(), String> {
+    resolve_pairing(&pairing_id, approve).await?;
+
+    let _ = app.emit(
+        pairing::PAIRING_RESOLVED_EVENT,
+        serde_json::json!({ "pairingId": pairing_id, "approved": approve }),
+    );
+    Ok(())
+}
+
+#[command]
+pub async fn approve_pairing(app: tauri::AppHandle, pairing_id: String) -> Result<(), String> {
+    respond_to_pairing(app, pairing_id, true).await
+}
+
+#[command]
+pub async fn reject_pairing(app: tauri::AppHandle, pairing_id: String) -> Result<(), String> {
+    respond_to_pairing(app, pairing_id, false).await
+}
+
+#[command]
+pub async fn mint_access_token(
+    app: tauri::AppHandle,
+    request: tokens::AccessTokenRequest,
+) -> Result<tokens::MintedAccessToken, String> {
+    let db_path = resolve_sync_db_path(&app)?;
+
+    tokio::task::spawn_blocking(move || {
+        let conn = db::open_readwrite(&db_path).map_err(|e| e.to_string())?;
+        tokens::mint(&conn, request)
+    })
+    .await
+    .map_err(|e| format!("Task failed: {}", e))?
+}
+
+#[command]
+pub async fn list_access_tokens(
+    app: tauri::AppHandle,
+) -> Result<Vec<db::AccessTokenRecord>, String> {
+    let db_path = resolve_sync_db_path(&app)?;
+
+    tokio::task::spawn_blocking(move || {
+        let conn = db::open_readwrite(&db_path).map_err(|e| e.to_string())?;
+        db::list_access_tokens(&conn).map_err(|e| format!("Failed to list access tokens: {}", e))
+    })
+    .await
+    .map_err(|e| format!("Task failed: {}", e))?
+}
+
+#[command]
+pub async fn revoke_access_token(app: tauri::AppHandle, token_id: String) -> Result<(), String> {
+    let db_path = resolve_sync_db_path(&app)?;
+
+    tokio::task::spawn_blocking(move || {
+        let conn = db::open_readwrite(&db_path).map_err(|e| e.to_string())?;
+        match db::revoke_access_token(&conn, &token_id) {
+            Ok(true) => Ok(()),
+            Ok(false) => Err(format!(
+                "Access token {} not found or already revoked",
+                token_id
+            )),
+            Err(e) => Err(format!("Failed to revoke access token: {}", e)),
+        }
+    })
+    .await
+    .map_err(|e| format!("Task failed: {}", e))?
+}
@@ -0,0 +1,273 @@
+use std::io::Read;
+
+use async_compression::tokio::bufread::{BrotliEncoder, GzipEncoder};
+use async_compression::Level;
+
+use flate2::read::GzDecoder;
+
+use futures_util::TryStreamExt;
+
+use serde::de::DeserializeOwned;
+
+use tokio_util::io::{ReaderStream, StreamReader};
+
+use warp::http::{header, HeaderMap, HeaderValue, Response, StatusCode};
+use warp::hyper::body::{Body, Bytes};
+use warp::{reject, Filter, Rejection, Reply};
+
+const MIN_COMPRESS_BYTES: u64 = 1024;
+const MAX_DECODED_BODY_BYTES: u64 = 64 * 1024 * 1024;
+const BROTLI_QUALITY: i32 = 5;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum Encoding {
+    Brotli,
+    Gzip,
+}
+
+impl Encoding {
+    fn name(self) -> &'static str {
+        match self {
+            Encoding::Brotli => "br",
+            Encoding::Gzip => "gzip",
+        }
+    }
+}
+
+#[derive(Debug)]
+pub enum BodyRejection {
+    UnsupportedEncoding(String),
+    Invalid(String),
+}
+
+impl reject::Reject for BodyRejection {}
+
+fn quality(accept_encoding: &str, name: &str) -> f32 {
+    let mut wildcard = None;
+    for candidate in accept_encoding.split(',') {
+        let mut params = candidate.split(';').map(str::trim);
+        let coding = params.next().unwrap_or_default();
+        let q = params
+            .find_map(|param| param.strip_prefix("q="))
+            .map_or(Some(1.0), |q| q.parse::<f32>().ok())
+            .unwrap_or(0.0);
+
+        if coding.eq_ignore_ascii_case(name) {
+            return q;
+        }
+        if coding == "*" {
+            wildcard = Some(q);
+        }
+    }
+    wildcard.unwrap_or(0.0)
+}
+
+fn negotiate(accept_encoding: Option<&str>) -> Option<Encoding> {
+    let accept_encoding = accept_encoding?;
+    let mut best: Option<(Encoding, f32)> = None;
+    for encoding in [Encoding::Brotli, Encoding::Gzip] {
+        let q = quality(accept_encoding, encoding.name());
+        if q > 0.0 && best.is_none_or(|(_, best_q)| q > best_q) {
+            best = Some((encoding, q));
+        }
+    }
+    best.map(|(encoding, _)| encoding)
+}
+
+fn is_compressible(content_type: &str) -> bool {
+    let mime = content_type
+        .split(';')
+        .next()
+        .unwrap_or_default()
+        .trim()
+        .to_ascii_lowercase();
+
+    match mime.as_str() {
+        "application/json" | "application/x-ndjson" => true,
+        "audio/wav" | "audio/x-wav" => true,
+        _ => mime.starts_with("text/"),
+    }
+}
+
+fn should_compress(status: StatusCode, headers: &HeaderMap) -> bool {
+    if status != StatusCode::OK
+        || headers.contains_key(header::CONTENT_ENCODING)
+        || headers.contains_key(header::CONTENT_RANGE)
+    {
+        return false;
+    }
+
+    let compressible = headers
+        .get(header::CONTENT_TYPE)
+        .and_then(|value| value.to_str().ok())
+        .is_some_and(is_compressible);
+
+    let large_enough = headers
+        .get(header::CONTENT_LENGTH)
+        .and_then(|value| value.to_str().ok())
+        .and_then(|value| value.parse::<u64>().ok())
+        .is_none_or(|len| len >= MIN_COMPRESS_BYTES);
+
+    compressible && large_enough
+}
+
+fn encode_body(body: Body, encoding: Encoding) -> Body {
+    let reader = StreamReader::new(body.map_err(std::io::Error::other));
+    match encoding {
+        Encoding::Brotli => Body::wrap_stream(ReaderStream::new(BrotliEncoder::with_quality(
+            reader,
+            Level::Precise(BROTLI_QUALITY),
+        ))),
+        Encoding::Gzip => Body::wrap_stream(ReaderStream::new(GzipEncoder::new(reader))),
+    }
+}
+
+fn compress_response(accept_encoding: Option<String>, response: Response<Body>) -> Response<Body> {
+    let (mut parts, body) = response.into_parts();
+
+    let compressible = should_compress(parts.status, &parts.headers);
+    if compressible {
+        parts
+            .headers
+            .append(header::VARY, HeaderValue::from_static("accept-encoding"));
+    }
+
+    let encoding = match negotiate(accept_encoding.as_deref()) {
+        Some(encoding) if compressible => encoding,
+        _ => return Response::from_parts(parts, body),
+    };
+
+    parts.headers.remove(header::CONTENT_LENGTH);
+    parts.headers.remove(header::ACCEPT_RANGES);
+    parts.headers.remove("Digest");
+    parts.headers.insert(
+        header::CONTENT_ENCODING,
+        HeaderValue::from_static(encoding.name()),
+    );
+    if let Some(etag) = parts.headers.get(header::ETAG).cloned() {
+        if let Ok(etag) = etag.to_str() {
+            if !etag.starts_with("W/") {
+                if let Ok(weak) = HeaderValue::from_str(&format!("W/{}", etag)) {
+                    parts.headers.insert(header::ETAG, weak);
+                }
+            }
+        }
+    }
+
+    Response::from_parts(parts, encode_body(body, encoding))
+}
+
+pub fn negotiated<F, R>(
+    filter: F,
+) -> impl Filter<Extract = (Response<Body>,), Error = Rejection> + Clone
+where
+    F: Filter<Extract = (R,), Error = Rejection> + Clone + Send + Sync + 'static,
+    R: Reply,
+{
+    warp::header::optional::<String>("accept-encoding")
+        .and(filter)
+        .map(|accept_encoding: Option<String>, reply: R| {
+            compress_response(accept_encoding, reply.into_response())
+        })
+}
+
+fn decode_body(content_encoding: Option<&str>, body: Bytes) -> Result<Vec<u8>, BodyRejection> {
+    match content_encoding.map(str::trim) {
+        None | Some("") | Some("identity") => Ok(body.to_vec()),
+        Some(encoding) if encoding.eq_ignore_ascii_case("gzip") => {
+            let mut decoded = Vec::new();
+            GzDecoder::new(body.as_ref())
+                .take(MAX_DECODED_BODY_BYTES + 1)
+                .read_to_end(&mut decoded)
+                .map_err(|e| BodyRejection::Invalid(format!("Invalid gzip body: {}", e)))?;
+            if decoded.len() as u64 > MAX_DECODED_BODY_BYTES {
+                return Err(BodyRejection::Invalid(
+                    "Decoded body is too large".to_string(),
+                ));
+            }
+            Ok(decoded)
+        }
+        Some(encoding) => Err(BodyRejection::UnsupportedEncoding(encoding.to_string())),
+    }
+}
+
+pub fn json_body<T: DeserializeOwned + Send>(
+) -> impl Filter<Extract = (T,), Error = Rejection> + Clone {
+    warp::header::optional::<String>("content-encoding")
+        .and(warp::body::content_length_limit(MAX_DECODED_BODY_BYTES))
+        .and(warp::body::bytes())
+        .and_then(|content_encoding: Option<String>, body: Bytes| async move {
+            let decoded = decode_body(content_encoding.as_deref(), body).map_err(reject::custom)?;
+            serde_json::from_slice::<T>(&decoded).map_err(|e| {
+                reject::custom(BodyRejection::Invalid(format!("Invalid JSON body: {}", e)))
+            })
+        })
+}
+
+pub fn identity_body() -> impl Filter<Extract = (), Error = Rejection> + Clone {
+    warp::header::optional::<String>("content-encoding")
+        .and_then(|content_encoding: Option<String>| async move {
+            match content_encoding.as_deref().map(str::trim) {
+                None | Some("") | Some("identity") => Ok(()),
+                Some(encoding) => Err(reject::custom(BodyRejection::UnsupportedEncoding(
+                    encoding.to_string(),
+                ))),
+            }
+        })
+        .untuple_one()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn prefers_brotli_when_equally_acceptable() {
+        assert_eq!(negotiate(Some("gzip, deflate, br")), Some(Encoding::Brotli));
+        assert_eq!(negotiate(Some("*")), Some(Encoding::Brotli));
+    }
+
+    #[test]
+    fn honours_quality_values() {
+        assert_eq!(negotiate(Some("br;q=0.1, gzip")), Some(Encoding::Gzip));
+        assert_eq!(
+            negotiate(Some("gzip;q=0.5, br;q=0.8")),
+            Some(Encoding::Brotli)
+        );
+        assert_eq!(negotiate(Some("*;q=0.2, gzip;q=0.3")), Some(Encoding::Gzip));
+    }
+
+    #[test]
+    fn skips_refused_encodings() {
+        assert_eq!(negotiate(Some("br;q=0, gzip")), Some(Encoding::Gzip));
+        assert_eq!(negotiate(Some("*;q=0, gzip")), Some(Encoding::Gzip));
+        assert_eq!(negotiate(Some("br;q=0, gzip;q=0")), None);
+        assert_eq!(negotiate(Some("identity")), None);
+        assert_eq!(negotiate(None), None);
+    }
+
+    #[test]
+    fn rejects_oversized_gzip_body() {
+        use std::io::Write;
+
+        let mut encoder = flate2::write::GzEncoder::new(Vec::new(), flate2::Compression::fast());
+        encoder
+            .write_all(&vec![0u8; MAX_DECODED_BODY_BYTES as usize + 1])
+            .unwrap();
+        let body = Bytes::from(encoder.finish().unwrap());
+
+        assert!(matches!(
+            decode_body(Some("gzip"), body),
+            Err(BodyRejection::Invalid(_))
+        ));
+    }
+
+    #[test]
+    fn rejects_unknown_content_encoding() {
+        assert!(matches!(
+            decode_body(Some("br"), Bytes::from_static(b"{}")),
+            Err(BodyRejection::UnsupportedEncoding(_))
+        ));
+        assert_eq!(decode_body(None, Bytes::from_static(b"{}")).unwrap(), b"{}");
+    }
+}
@@ -0,0 +1,118 @@
+use std::time::Duration;
+
+use serde::{Deserialize, Serialize};
+
+use super::guard::RateLimits;
+use super::network::NetworkOptions;
+use super::pairing_routes::normalize_device_name;
+
+pub const SERVER_CONFIG_STORE: &str = ".sync-server.json";
+pub const SERVER_CONFIG_KEY: &str = "serverConfig";
+
+const MAX_PORT_RANGE: u16 = 1000;
+const MAX_TIMEOUT_SECS: u64 = 60 * 60;
+const MAX_UPLOAD_MB: u64 = 16 * 1024;
+
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub enum PortMode {
+    #[default]
+    Fixed,
+    Preferred,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase", default)]
+pub struct ServerConfig {
+    pub port: u16,
+    pub port_mode: PortMode,
+    pub port_range: u16,
+    pub plain_http: bool,
+    pub serve_in_background: bool,
+    pub advertise: bool,
+    pub device_name: Option<String>,
+    #[serde(flatten)]
+    pub network: NetworkOptions,
+    pub inactivity_timeout_secs: u64,
+    pub status_interval_secs: u64,
+    pub max_upload_mb: u64,
+    pub rate_limits: RateLimits,
+}
+
+impl Default for ServerConfig {
+    fn default() -> Self {
+        Self {
+            port: 3030,
+            port_mode: PortMode::Fixed,
+            port_range: 100,
+            plain_http: false,
+            serve_in_background: false,
+            advertise: true,
+            device_name: None,
+            network: NetworkOptions::default(),
+            inactivity_timeout_secs: 15,
+            status_interval_secs: 5,
+            max_upload_mb: 1024,
+            rate_limits: RateLimits::default(),
+        }
+    }
+}
+
+impl ServerConfig {
+    pub fn validate(&self) -> Result<(), String> {
+        if self.port == 0 {
+            return Err("Port must be between 1 and 65535".to_string());
+        }
+        if self.port_mode == PortMode::Preferred && !(1..=MAX_PORT_RANGE).contains(&self.port_range)
+        {
+            return Err(format!(
+                "Port range must be between 1 and {}",
+                MAX_PORT_RANGE
+            ));
+        }
+        if !(1..=MAX_TIMEOUT_SECS).contains(&self.inactivity_timeout_secs) {
+            return Err(format!(
+                "Inactivity timeout must be between 1 and {} seconds",
+                MAX_TIMEOUT_SECS
+            ));
+        }
+        if self.status_interval_secs == 0
+            || self.status_interval_secs > self.inactivity_timeout_secs
+        {
+            return Err(
+                "Status interval must be at least 1 second and no longer than the inactivity timeout"
+                    .to_string(),
+            );
+        }
+        if !(1..=MAX_UPLOAD_MB).contains(&self.max_upload_mb) {
+            return Err(format!(
+                "Upload limit must be between 1 and {} MB",
+                MAX_UPLOAD_MB
+            ));
+        }
+        if let Some(name) = &self.device_name {
+            normalize_device_name(name)?;
+        }
+        Ok(())
+    }
+
+    pub fn candidate_ports(&self) -> std::ops::RangeInclusive<u16> {
+        let last = match self.port_mode {
+            PortMode::Fixed => self.port,
+            PortMode::Preferred => self.port.saturating_add(self.port_range - 1),
+        };
+        self.port..=last
+    }
+
+    pub fn inactivity_timeout(&self) -> Duration {
+        Duration::from_secs(self.inactivity_timeout_secs)
+    }
+
+    pub fn status_interval(&self) -> Duration {
+        Duration::from_secs(self.status_interval_secs)
+    }
+
+    pub fn max_upload_bytes(&self) -> u64 {
+        self.max_upload_mb * 1024 * 1024
+    }
+}
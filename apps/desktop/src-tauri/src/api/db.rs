@@ -1,10 +1,10 @@
-use rusqlite::{params, Connection, OpenFlags, Result as SqliteResult};
+use rusqlite::{params, Connection, OpenFlags, OptionalExtension, Result as SqliteResult};
 
-use serde::Serialize;
+use serde::{Deserialize, Serialize};
 
 use sha2::{Digest, Sha256};
 
-use std::collections::HashMap;
+use std::collections::{HashMap, HashSet};
 use std::path::{Path, PathBuf};
 
 const DATABASE_FILENAME: &str = "database.db";
@@ -21,6 +21,265 @@ pub fn open_readwrite(db_path: &Path) -> SqliteResult<Connection> {
     Connection::open(db_path)
 }
 
+const DEVICE_LAST_SEEN_RESOLUTION_SECS: i64 = 60;
+
+#[derive(Debug, Clone, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct PairedDevice {
+    pub id: String,
+    pub name: String,
+    pub created_at: i64,
+    pub last_seen_at: Option<i64>,
+    pub revoked_at: Option<i64>,
+}
+
+fn map_paired_device(row: &rusqlite::Row) -> SqliteResult<PairedDevice> {
+    Ok(PairedDevice {
+        id: row.get(0)?,
+        name: row.get(1)?,
+        created_at: row.get(2)?,
+        last_seen_at: row.get(3)?,
+        revoked_at: row.get(4)?,
+    })
+}
+
+pub fn insert_device(
+    conn: &Connection,
+    id: &str,
+    name: &str,
+    secret_hash: &str,
+) -> SqliteResult<PairedDevice> {
+    conn.execute(
+        "INSERT INTO sync_devices (id, name, secret_hash) VALUES (?1, ?2, ?3)",
+        params![id, name, secret_hash],
+    )?;
+    conn.query_row(
+        "SELECT id, name, created_at, last_seen_at, revoked_at FROM sync_devices WHERE id = ?1",
+        params![id],
+        map_paired_device,
+    )
+}
+
+pub fn get_active_device_secret_hash(
+    conn: &Connection,
+    id: &str,
+) -> SqliteResult<Option<(PairedDevice, String)>> {
+    conn.query_row(
+        "SELECT id, name, created_at, last_seen_at, revoked_at, secret_hash \
+         FROM sync_devices WHERE id = ?1 AND revoked_at IS NULL",
+        params![id],
+        |row| Ok((map_paired_device(row)?, row.get(5)?)),
+    )
+    .optional()
+}
+
+pub fn touch_device(conn: &Connection, id: &str) -> SqliteResult<()> {
+    conn.execute(
+        "UPDATE sync_devices SET last_seen_at = unixepoch() \
+         WHERE id = ?1 AND (last_seen_at IS NULL OR last_seen_at < unixepoch() - ?2)",
+        params![id, DEVICE_LAST_SEEN_RESOLUTION_SECS],
+    )?;
+    Ok(())
+}
+
+pub fn list_devices(conn: &Connection) -> SqliteResult<Vec<PairedDevice>> {
+    let mut stmt = conn.prepare(
+        "SELECT id, name, created_at, last_seen_at, revoked_at \
+         FROM sync_devices ORDER BY created_at DESC",
+    )?;
+    let rows = stmt.query_map([], map_paired_device)?;
+    rows.collect()
+}
+
+pub fn rename_device(conn: &Connection, id: &str, name: &str) -> SqliteResult<bool> {
+    let updated = conn.execute(
+        "UPDATE sync_devices SET name = ?1 WHERE id = ?2",
+        params![name, id],
+    )?;
+    Ok(updated > 0)
+}
+
+pub fn revoke_device(conn: &Connection, id: &str) -> SqliteResult<bool> {
+    let updated = conn.execute(
+        "UPDATE sync_devices SET revoked_at = unixepoch() WHERE id = ?1 AND revoked_at IS NULL",
+        params![id],
+    )?;
+    Ok(updated > 0)
+}
+
+const ACCESS_TOKEN_KEY: &str = "access_token_key";
+
+#[derive(Debug, Clone, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct AccessTokenRecord {
+    pub id: String,
+    pub label: String,
+    pub scopes: Vec<String>,
+    pub playlists: Vec<String>,
+    pub albums: Vec<String>,
+    pub created_at: i64,
+    pub expires_at: i64,
+    pub revoked_at: Option<i64>,
+}
+
+fn json_list(value: String) -> Vec<String> {
+    serde_json::from_str(&value).unwrap_or_default()
+}
+
+fn map_access_token(row: &rusqlite::Row) -> SqliteResult<AccessTokenRecord> {
+    Ok(AccessTokenRecord {
+        id: row.get(0)?,
+        label: row.get(1)?,
+        scopes: json_list(row.get(2)?),
+        playlists: json_list(row.get(3)?),
+        albums: json_list(row.get(4)?),
+        created_at: row.get(5)?,
+        expires_at: row.get(6)?,
+        revoked_at: row.get(7)?,
+    })
+}
+
+pub fn get_or_create_access_token_key(conn: &Connection, candidate: &str) -> SqliteResult<String> {
+    conn.execute(
+        "INSERT OR IGNORE INTO sync_meta (key, value) VALUES (?1, ?2)",
+        params![ACCESS_TOKEN_KEY, candidate],
+    )?;
+    conn.query_row(
+        "SELECT value FROM sync_meta WHERE key = ?1",
+        params![ACCESS_TOKEN_KEY],
+        |row| row.get(0),
+    )
+}
+
+pub fn get_access_token_key(conn: &Connection) -> SqliteResult<Option<String>> {
+    conn.query_row(
+        "SELECT value FROM sync_meta WHERE key = ?1",
+        params![ACCESS_TOKEN_KEY],
+        |row| row.get(0),
+    )
+    .optional()
+}
+
+pub fn insert_access_token(
+    conn: &Connection,
+    id: &str,
+    label: &str,
+    scopes: &[String],
+    playlists: &[String],
+    albums: &[String],
+    expires_at: i64,
+) -> SqliteResult<AccessTokenRecord> {
+    let encode = |values: &[String]| serde_json::to_string(values).unwrap_or_else(|_| "[]".into());
+    conn.execute(
+        "INSERT INTO sync_access_tokens (id, label, scopes, playlists, albums, expires_at) \
+         VALUES (?1, ?2, ?3, ?4, ?5, ?6)",
+        params![
+            id,
+            label,
+            encode(scopes),
+            encode(playlists),
+            encode(albums),
+            expires_at
+        ],
+    )?;
+    conn.query_row(
+        "SELECT id, label, scopes, playlists, albums, created_at, expires_at, revoked_at \
+         FROM sync_access_tokens WHERE id = ?1",
+        params![id],
+        map_access_token,
+    )
+}
+
+pub fn is_access_token_active(conn: &Connection, id: &str) -> SqliteResult<bool> {
+    conn.query_row(
+        "SELECT EXISTS(SELECT 1 FROM sync_access_tokens \
+         WHERE id = ?1 AND revoked_at IS NULL AND expires_at > unixepoch())",
+        params![id],
+        |row| row.get(0),
+    )
+}
+
+pub fn list_access_tokens(conn: &Connection) -> SqliteResult<Vec<AccessTokenRecord>> {
+    let mut stmt = conn.prepare(
+        "SELECT id, label, scopes, playlists, albums, created_at, expires_at, revoked_at \
+         FROM sync_access_tokens ORDER BY created_at DESC",
+    )?;
+    let rows = stmt.query_map([], map_access_token)?;
+    rows.collect()
+}
+
+pub fn revoke_access_token(conn: &Connection, id: &str) -> SqliteResult<bool> {
+    let updated = conn.execute(
+        "UPDATE sync_access_tokens SET revoked_at = unixepoch() WHERE id = ?1 AND revoked_at IS NULL",
+        params![id],
+    )?;
+    Ok(updated > 0)
+}
+
+pub fn missing_fingerprints(
+    conn: &Connection,
+    table: &str,
+    fingerprints: &[String],
+) -> SqliteResult<Vec<String>> {
+    let sql = match table {
+        "playlists" => "SELECT 1 FROM playlists WHERE fingerprint = ?1",
+        "albums" => "SELECT 1 FROM albums WHERE fingerprint = ?1",
+        _ => return Ok(fingerprints.to_vec()),
+    };
+    let mut stmt = conn.prepare(sql)?;
+    let mut missing = Vec::new();
+    for fingerprint in fingerprints {
+        if !stmt.exists(params![fingerprint])? {
+            missing.push(fingerprint.clone());
+        }
+    }
+    Ok(missing)
+}
+
+const ALLOWED_SONGS_CTE: &str = "WITH allowed_songs AS ( \
+         SELECT s.id, s.album_id FROM songs s JOIN albums a ON a.id = s.album_id \
+         WHERE a.fingerprint IN (SELECT value FROM json_each(?1)) \
+         UNION \
+         SELECT s.id, s.album_id FROM songs s \
+         JOIN playlist_songs ps ON ps.song_id = s.id \
+         JOIN playlists p ON p.id = ps.playlist_id \
+         WHERE p.fingerprint IN (SELECT value FROM json_each(?2)) \
+     )";
+
+pub fn collection_allows(
+    conn: &Connection,
+    entity_type: &str,
+    fingerprint: &str,
+    playlists: &[String],
+    albums: &[String],
+) -> SqliteResult<bool> {
+    let condition = match entity_type {
+        "playlist" => return Ok(playlists.iter().any(|p| p == fingerprint)),
+        "song" => {
+            "EXISTS(SELECT 1 FROM songs s JOIN allowed_songs al ON al.id = s.id \
+             WHERE s.fingerprint = ?3)"
+        }
+        "album" => {
+            "EXISTS(SELECT 1 FROM albums a WHERE a.fingerprint = ?3 AND ( \
+             a.fingerprint IN (SELECT value FROM json_each(?1)) \
+             OR a.id IN (SELECT album_id FROM allowed_songs)))"
+        }
+        "artist" => {
+            "EXISTS(SELECT 1 FROM artists ar JOIN song_artists sa ON sa.artist_id = ar.id \
+             JOIN allowed_songs al ON al.id = sa.song_id WHERE ar.fingerprint = ?3)"
+        }
+        _ => return Ok(false),
+    };
+
+    let albums = serde_json::to_string(albums).unwrap_or_else(|_| "[]".into());
+    let playlists = serde_json::to_string(playlists).unwrap_or_else(|_| "[]".into());
+    conn.query_row(
+        &format!("{} SELECT {}", ALLOWED_SONGS_CTE, condition),
+        params![albums, playlists, fingerprint],
+        |row| row.get(0),
+    )
+}
+
 #[derive(Debug, Clone, Serialize)]
 #[serde(rename_all = "camelCase")]
 pub struct SongRow {
@@ -34,10 +293,13 @@ pub struct SongRow {
     pub has_thumbnail: bool,
     pub album_fingerprint: Option<String>,
     pub artist_fingerprints: Vec<ArtistOrder>,
-    pub playlist_fingerprints: Vec<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub playlist_fingerprints: Option<Vec<String>>,
+    pub updated_at: i64,
+    pub content_hash: String,
 }
 
-#[derive(Debug, Clone, Serialize)]
+#[derive(Debug, Clone, Serialize, Deserialize)]
 #[serde(rename_all = "camelCase")]
 pub struct ArtistOrder {
     pub fingerprint: String,
@@ -54,6 +316,8 @@ pub struct AlbumRow {
     pub is_favorite: bool,
     pub has_thumbnail: bool,
     pub artist_fingerprints: Vec<ArtistOrder>,
+    pub updated_at: i64,
+    pub content_hash: String,
 }
 
 #[derive(Debug, Clone, Serialize)]
@@ -63,6 +327,8 @@ pub struct ArtistRow {
     pub name: String,
     pub is_favorite: bool,
     pub has_thumbnail: bool,
+    pub updated_at: i64,
+    pub content_hash: String,
 }
 
 #[derive(Debug, Clone, Serialize)]
@@ -73,6 +339,17 @@ pub struct PlaylistRow {
     pub is_favorite: bool,
     pub has_thumbnail: bool,
     pub song_fingerprints: Vec<String>,
+    pub entries: Vec<PlaylistEntry>,
+    pub updated_at: i64,
+    pub content_hash: String,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct PlaylistEntry {
+    pub song_fingerprint: String,
+    pub position: i64,
+    pub added_at: i64,
 }
 
 pub fn get_all_fingerprints(conn: &Connection, table_name: &str) -> SqliteResult<Vec<String>> {
@@ -85,9 +362,160 @@ pub fn get_all_fingerprints(conn: &Connection, table_name: &str) -> SqliteResult
     rows.collect()
 }
 
+pub fn current_timestamp(conn: &Connection) -> SqliteResult<i64> {
+    conn.query_row("SELECT unixepoch()", [], |row| row.get(0))
+}
+
+pub fn get_changed_fingerprints(
+    conn: &Connection,
+    table_name: &str,
+    since: i64,
+) -> SqliteResult<Vec<String>> {
+    let sql = format!(
+        "SELECT fingerprint FROM {} \
+         WHERE fingerprint IS NOT NULL AND updated_at >= ?1 \
+         ORDER BY updated_at, id",
+        table_name
+    );
+    let mut stmt = conn.prepare(&sql)?;
+    let rows = stmt.query_map(params![since], |row| row.get::<_, String>(0))?;
+    rows.collect()
+}
+
+#[derive(Debug, Clone, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct Tombstone {
+    pub entity_type: String,
+    pub fingerprint: String,
+    pub deleted_at: i64,
+}
+
+pub fn get_tombstones_since(conn: &Connection, since: i64) -> SqliteResult<Vec<Tombstone>> {
+    let mut stmt = conn.prepare(
+        "SELECT t.entity_type, t.fingerprint, MAX(t.deleted_at) \
+         FROM sync_tombstones t \
+         WHERE t.deleted_at >= ?1 AND NOT ( \
+             (t.entity_type = 'song' AND EXISTS (SELECT 1 FROM songs WHERE fingerprint = t.fingerprint)) \
+             OR (t.entity_type = 'album' AND EXISTS (SELECT 1 FROM albums WHERE fingerprint = t.fingerprint)) \
+             OR (t.entity_type = 'artist' AND EXISTS (SELECT 1 FROM artists WHERE fingerprint = t.fingerprint)) \
+             OR (t.entity_type = 'playlist' AND EXISTS (SELECT 1 FROM playlists WHERE fingerprint = t.fingerprint)) \
+             OR (t.entity_type = 'playlistSong' AND EXISTS ( \
+                 SELECT 1 FROM playlist_songs ps \
+                 JOIN playlists p ON ps.playlist_id = p.id \
+                 JOIN songs s ON ps.song_id = s.id \
+                 WHERE p.fingerprint || ':' || s.fingerprint = t.fingerprint)) \
+         ) \
+         GROUP BY t.entity_type, t.fingerprint \
+         ORDER BY MAX(t.deleted_at)",
+    )?;
+    let rows = stmt.query_map(params![since], |row| {
+        Ok(Tombstone {
+            entity_type: row.get(0)?,
+            fingerprint: row.get(1)?,
+            deleted_at: row.get(2)?,
+        })
+    })?;
+    rows.collect()
+}
+
+pub const TOMBSTONE_RETENTION_SECS: i64 = 90 * 24 * 60 * 60;
+
+const TOMBSTONES_COMPACTED_THROUGH_KEY: &str = "tombstones_compacted_through";
+
+pub fn get_tombstones_compacted_through(conn: &Connection) -> SqliteResult<Option<i64>> {
+    let value: Option<String> = conn
+        .query_row(
+            "SELECT value FROM sync_meta WHERE key = ?1",
+            params![TOMBSTONES_COMPACTED_THROUGH_KEY],
+            |row| row.get(0),
+        )
+        .optional()?;
+    value
+        .map(|value| {
+            value.parse().map_err(|e| {
+                rusqlite::Error::FromSqlConversionFailure(
+                    0,
+                    rusqlite::types::Type::Text,
+                    Box::new(e),
+                )
+            })
+        })
+        .transpose()
+}
+
+pub fn compact_tombstones(conn: &mut Connection, retention_secs: i64) -> SqliteResult<usize> {
+    let tx = conn.transaction()?;
+
+    let horizon = current_timestamp(&tx)? - retention_secs;
+    let expired_through: Option<i64> = tx.query_row(
+        "SELECT MAX(deleted_at) FROM sync_tombstones WHERE deleted_at < ?1",
+        params![horizon],
+        |row| row.get(0),
+    )?;
+
+    let mut removed = tx.execute(
+        "DELETE FROM sync_tombstones WHERE deleted_at < ?1",
+        params![horizon],
+    )?;
+
+    if let Some(expired_through) = expired_through {
+        tx.execute(
+            "INSERT INTO sync_meta (key, value) VALUES (?1, ?2) \
+             ON CONFLICT(key) DO UPDATE SET value = MAX(CAST(value AS INTEGER), excluded.value)",
+            params![
+                TOMBSTONES_COMPACTED_THROUGH_KEY,
+                expired_through.to_string()
+            ],
+        )?;
+    }
+
+    removed += tx.execute(
+        "DELETE FROM sync_tombstones WHERE id NOT IN ( \
+             SELECT MAX(id) FROM sync_tombstones GROUP BY entity_type, fingerprint \
+         )",
+        [],
+    )?;
+
+    removed += tx.execute(
+        "DELETE FROM sync_tombstones WHERE \
+             (entity_type = 'song' AND fingerprint IN (SELECT fingerprint FROM songs WHERE fingerprint IS NOT NULL)) \
+             OR (entity_type = 'album' AND fingerprint IN (SELECT fingerprint FROM albums WHERE fingerprint IS NOT NULL)) \
+             OR (entity_type = 'artist' AND fingerprint IN (SELECT fingerprint FROM artists WHERE fingerprint IS NOT NULL)) \
+             OR (entity_type = 'playlist' AND fingerprint IN (SELECT fingerprint FROM playlists WHERE fingerprint IS NOT NULL))",
+        [],
+    )?;
+
+    tx.commit()?;
+    Ok(removed)
+}
+
+pub const MAX_SQL_VARIABLES: usize = 500;
+
+fn query_in_chunks<T, F>(fingerprints: &[String], mut query: F) -> SqliteResult<Vec<T>>
+where
+    F: FnMut(&[String]) -> SqliteResult<Vec<T>>,
+{
+    let mut rows = Vec::with_capacity(fingerprints.len());
+    for chunk in fingerprints.chunks(MAX_SQL_VARIABLES) {
+        rows.extend(query(chunk)?);
+    }
+    Ok(rows)
+}
+
 pub fn get_songs_by_fingerprints(
     conn: &Connection,
     fingerprints: &[String],
+    include_playlists: bool,
+) -> SqliteResult<Vec<SongRow>> {
+    query_in_chunks(fingerprints, |chunk| {
+        get_songs_chunk(conn, chunk, include_playlists)
+    })
+}
+
+fn get_songs_chunk(
+    conn: &Connection,
+    fingerprints: &[String],
+    include_playlists: bool,
 ) -> SqliteResult<Vec<SongRow>> {
     if fingerprints.is_empty() {
         return Ok(vec![]);
@@ -97,7 +525,7 @@ pub fn get_songs_by_fingerprints(
     let sql = format!(
         "SELECT s.id, s.fingerprint, s.name, s.duration, s.release_year, \
          s.is_favorite, s.lyrics, s.file, s.thumbnail, \
-         a.fingerprint as album_fingerprint \
+         a.fingerprint as album_fingerprint, s.updated_at \
          FROM songs s \
          LEFT JOIN albums a ON s.album_id = a.id \
          WHERE s.fingerprint IN ({})",
@@ -122,6 +550,7 @@ pub fn get_songs_by_fingerprints(
         file: String,
         thumbnail: Option<String>,
         album_fingerprint: Option<String>,
+        updated_at: i64,
     }
 
     let raw_rows: Vec<RawSong> = stmt
@@ -137,17 +566,23 @@ pub fn get_songs_by_fingerprints(
                 file: row.get(7)?,
                 thumbnail: row.get(8)?,
                 album_fingerprint: row.get(9)?,
+                updated_at: row.get(10)?,
             })
         })?
         .collect::<SqliteResult<Vec<_>>>()?;
 
     let song_ids: Vec<i64> = raw_rows.iter().map(|r| r.id).collect();
     let artist_map = get_song_artist_map(conn, &song_ids)?;
-    let playlist_map = get_song_playlist_map(conn, &song_ids)?;
+    let playlist_map = if include_playlists {
+        Some(get_song_playlist_map(conn, &song_ids)?)
+    } else {
+        None
+    };
 
     let songs = raw_rows
         .into_iter()
         .map(|r| SongRow {
+            content_hash: song_content_hash(r.release_year, r.is_favorite, r.lyrics.as_deref()),
             fingerprint: r.fingerprint,
             name: r.name,
             duration: r.duration,
@@ -158,7 +593,10 @@ pub fn get_songs_by_fingerprints(
             has_thumbnail: r.thumbnail.is_some(),
             album_fingerprint: r.album_fingerprint,
             artist_fingerprints: artist_map.get(&r.id).cloned().unwrap_or_default(),
-            playlist_fingerprints: playlist_map.get(&r.id).cloned().unwrap_or_default(),
+            playlist_fingerprints: playlist_map
+                .as_ref()
+                .map(|map| map.get(&r.id).cloned().unwrap_or_default()),
+            updated_at: r.updated_at,
         })
         .collect();
 
@@ -251,13 +689,18 @@ pub fn get_albums_by_fingerprints(
     conn: &Connection,
     fingerprints: &[String],
 ) -> SqliteResult<Vec<AlbumRow>> {
+    query_in_chunks(fingerprints, |chunk| get_albums_chunk(conn, chunk))
+}
+
+fn get_albums_chunk(conn: &Connection, fingerprints: &[String]) -> SqliteResult<Vec<AlbumRow>> {
     if fingerprints.is_empty() {
         return Ok(vec![]);
     }
 
     let placeholders = vec!["?"; fingerprints.len()].join(",");
     let sql = format!(
-        "SELECT id, fingerprint, name, album_type, release_year, is_favorite, thumbnail \
+        "SELECT id, fingerprint, name, album_type, release_year, is_favorite, thumbnail, \
+         updated_at \
          FROM albums \
          WHERE fingerprint IN ({})",
         placeholders
@@ -278,6 +721,7 @@ pub fn get_albums_by_fingerprints(
         release_year: Option<i64>,
         is_favorite: bool,
         thumbnail: Option<String>,
+        updated_at: i64,
     }
 
     let raw_rows: Vec<RawAlbum> = stmt
@@ -290,6 +734,7 @@ pub fn get_albums_by_fingerprints(
                 release_year: row.get(4)?,
                 is_favorite: row.get::<_, i64>(5).map(|v| v != 0)?,
                 thumbnail: row.get(6)?,
+                updated_at: row.get(7)?,
             })
         })?
         .collect::<SqliteResult<Vec<_>>>()?;
@@ -300,6 +745,7 @@ pub fn get_albums_by_fingerprints(
     let albums = raw_rows
         .into_iter()
         .map(|r| AlbumRow {
+            content_hash: album_content_hash(r.release_year, r.is_favorite),
             fingerprint: r.fingerprint,
             name: r.name,
             album_type: r.album_type,
@@ -307,6 +753,7 @@ pub fn get_albums_by_fingerprints(
             is_favorite: r.is_favorite,
             has_thumbnail: r.thumbnail.is_some(),
             artist_fingerprints: artist_map.get(&r.id).cloned().unwrap_or_default(),
+            updated_at: r.updated_at,
         })
         .collect();
 
@@ -362,13 +809,17 @@ pub fn get_artists_by_fingerprints(
     conn: &Connection,
     fingerprints: &[String],
 ) -> SqliteResult<Vec<ArtistRow>> {
+    query_in_chunks(fingerprints, |chunk| get_artists_chunk(conn, chunk))
+}
+
+fn get_artists_chunk(conn: &Connection, fingerprints: &[String]) -> SqliteResult<Vec<ArtistRow>> {
     if fingerprints.is_empty() {
         return Ok(vec![]);
     }
 
     let placeholders = vec!["?"; fingerprints.len()].join(",");
     let sql = format!(
-        "SELECT fingerprint, name, is_favorite, thumbnail \
+        "SELECT fingerprint, name, is_favorite, thumbnail, updated_at \
          FROM artists \
          WHERE fingerprint IN ({})",
         placeholders
@@ -383,11 +834,14 @@ pub fn get_artists_by_fingerprints(
     let artists = stmt
         .query_map(sql_params.as_slice(), |row| {
             let thumbnail: Option<String> = row.get(3)?;
+            let is_favorite = row.get::<_, i64>(2).map(|v| v != 0)?;
             Ok(ArtistRow {
                 fingerprint: row.get(0)?,
                 name: row.get(1)?,
-                is_favorite: row.get::<_, i64>(2).map(|v| v != 0)?,
+                is_favorite,
                 has_thumbnail: thumbnail.is_some(),
+                updated_at: row.get(4)?,
+                content_hash: artist_content_hash(is_favorite),
             })
         })?
         .collect::<SqliteResult<Vec<_>>>()?;
@@ -398,6 +852,13 @@ pub fn get_artists_by_fingerprints(
 pub fn get_playlists_by_fingerprints(
     conn: &Connection,
     fingerprints: &[String],
+) -> SqliteResult<Vec<PlaylistRow>> {
+    query_in_chunks(fingerprints, |chunk| get_playlists_chunk(conn, chunk))
+}
+
+fn get_playlists_chunk(
+    conn: &Connection,
+    fingerprints: &[String],
 ) -> SqliteResult<Vec<PlaylistRow>> {
     if fingerprints.is_empty() {
         return Ok(vec![]);
@@ -405,7 +866,7 @@ pub fn get_playlists_by_fingerprints(
 
     let placeholders = vec!["?"; fingerprints.len()].join(",");
     let sql = format!(
-        "SELECT id, fingerprint, name, is_favorite, thumbnail \
+        "SELECT id, fingerprint, name, is_favorite, thumbnail, updated_at \
          FROM playlists \
          WHERE fingerprint IN ({})",
         placeholders
@@ -417,7 +878,7 @@ pub fn get_playlists_by_fingerprints(
         .collect();
 
     let mut stmt = conn.prepare(&sql)?;
-    let raw_rows: Vec<(i64, String, String, bool, Option<String>)> = stmt
+    let raw_rows: Vec<(i64, String, String, bool, Option<String>, i64)> = stmt
         .query_map(sql_params.as_slice(), |row| {
             Ok((
                 row.get(0)?,
@@ -425,6 +886,7 @@ pub fn get_playlists_by_fingerprints(
                 row.get(2)?,
                 row.get::<_, i64>(3).map(|v| v != 0)?,
                 row.get(4)?,
+                row.get(5)?,
             ))
         })?
         .collect::<SqliteResult<Vec<_>>>()?;
@@ -435,12 +897,19 @@ pub fn get_playlists_by_fingerprints(
     let playlists = raw_rows
         .into_iter()
         .map(
-            |(id, fingerprint, name, is_favorite, thumbnail)| PlaylistRow {
-                fingerprint,
-                name,
-                is_favorite,
-                has_thumbnail: thumbnail.is_some(),
-                song_fingerprints: song_map.get(&id).cloned().unwrap_or_default(),
+            |(id, fingerprint, name, is_favorite, thumbnail, updated_at)| {
+                let entries = song_map.get(&id).cloned().unwrap_or_default();
+                let song_fingerprints = playlist_entry_fingerprints(&entries);
+                PlaylistRow {
+                    content_hash: playlist_content_hash(is_favorite, &song_fingerprints),
+                    fingerprint,
+                    name,
+                    is_favorite,
+                    has_thumbnail: thumbnail.is_some(),
+                    song_fingerprints,
+                    entries,
+                    updated_at,
+                }
             },
         )
         .collect();
@@ -448,21 +917,54 @@ pub fn get_playlists_by_fingerprints(
     Ok(playlists)
 }
 
+const PLAYLIST_ENTRIES_SQL: &str = "SELECT ps.playlist_id, s.fingerprint, ps.added_at \
+     FROM playlist_songs ps \
+     JOIN songs s ON ps.song_id = s.id \
+     WHERE s.fingerprint IS NOT NULL";
+
+const PLAYLIST_ENTRIES_ORDER: &str =
+    "ORDER BY ps.playlist_id, ps.position, ps.added_at, s.fingerprint";
+
+fn collect_playlist_entries(
+    stmt: &mut rusqlite::Statement,
+    sql_params: &[&dyn rusqlite::types::ToSql],
+) -> SqliteResult<HashMap<i64, Vec<PlaylistEntry>>> {
+    let mut map: HashMap<i64, Vec<PlaylistEntry>> = HashMap::new();
+
+    let rows = stmt
+        .query_map(sql_params, |row| {
+            Ok((
+                row.get::<_, i64>(0)?,
+                row.get::<_, String>(1)?,
+                row.get::<_, i64>(2)?,
+            ))
+        })?
+        .collect::<SqliteResult<Vec<_>>>()?;
+
+    for (playlist_id, song_fingerprint, added_at) in rows {
+        let entries = map.entry(playlist_id).or_default();
+        entries.push(PlaylistEntry {
+            song_fingerprint,
+            position: entries.len() as i64,
+            added_at,
+        });
+    }
+
+    Ok(map)
+}
+
 fn get_playlist_song_map(
     conn: &Connection,
     playlist_ids: &[i64],
-) -> SqliteResult<HashMap<i64, Vec<String>>> {
+) -> SqliteResult<HashMap<i64, Vec<PlaylistEntry>>> {
     if playlist_ids.is_empty() {
         return Ok(HashMap::new());
     }
 
     let placeholders = vec!["?"; playlist_ids.len()].join(",");
     let sql = format!(
-        "SELECT ps.playlist_id, s.fingerprint \
-         FROM playlist_songs ps \
-         JOIN songs s ON ps.song_id = s.id \
-         WHERE ps.playlist_id IN ({}) AND s.fingerprint IS NOT NULL",
-        placeholders
+        "{} AND ps.playlist_id IN ({}) {}",
+        PLAYLIST_ENTRIES_SQL, placeholders, PLAYLIST_ENTRIES_ORDER
     );
 
     let sql_params: Vec<&dyn rusqlite::types::ToSql> = playlist_ids
@@ -471,117 +973,1293 @@ fn get_playlist_song_map(
         .collect();
 
     let mut stmt = conn.prepare(&sql)?;
-    let mut map: HashMap<i64, Vec<String>> = HashMap::new();
+    collect_playlist_entries(&mut stmt, &sql_params)
+}
 
-    stmt.query_map(sql_params.as_slice(), |row| {
-        Ok((row.get::<_, i64>(0)?, row.get::<_, String>(1)?))
-    })?
-    .for_each(|result| {
-        if let Ok((playlist_id, fingerprint)) = result {
-            map.entry(playlist_id).or_default().push(fingerprint);
-        }
-    });
+fn playlist_entry_fingerprints(entries: &[PlaylistEntry]) -> Vec<String> {
+    entries
+        .iter()
+        .map(|entry| entry.song_fingerprint.clone())
+        .collect()
+}
 
-    Ok(map)
+#[derive(Debug, Clone)]
+pub struct StoredFile {
+    pub name: String,
+    pub sha256: Option<String>,
 }
 
-pub fn get_song_file_path(
-    conn: &Connection,
-    fingerprint: &str,
-    songs_dir: &Path,
-) -> SqliteResult<Option<PathBuf>> {
-    let mut stmt = conn.prepare("SELECT file FROM songs WHERE fingerprint = ?1")?;
-    let result: Option<String> = stmt.query_row(params![fingerprint], |row| row.get(0)).ok();
+fn thumbnail_table(entity_type: &str) -> Option<&'static str> {
+    match entity_type {
+        "song" => Some("songs"),
+        "album" => Some("albums"),
+        "artist" => Some("artists"),
+        "playlist" => Some("playlists"),
+        _ => None,
+    }
+}
 
-    Ok(result.map(|filename| songs_dir.join(filename)))
+pub fn get_song_file(conn: &Connection, fingerprint: &str) -> SqliteResult<Option<StoredFile>> {
+    conn.query_row(
+        "SELECT file, file_sha256 FROM songs WHERE fingerprint = ?1",
+        params![fingerprint],
+        |row| {
+            Ok(StoredFile {
+                name: row.get(0)?,
+                sha256: row.get(1)?,
+            })
+        },
+    )
+    .optional()
 }
 
-pub fn get_thumbnail_path(
+pub fn get_thumbnail_file(
     conn: &Connection,
     fingerprint: &str,
     entity_type: &str,
-    thumbnails_dir: &Path,
-) -> SqliteResult<Option<PathBuf>> {
-    let sql = match entity_type {
-        "song" => "SELECT thumbnail FROM songs WHERE fingerprint = ?1",
-        "album" => "SELECT thumbnail FROM albums WHERE fingerprint = ?1",
-        "artist" => "SELECT thumbnail FROM artists WHERE fingerprint = ?1",
-        "playlist" => "SELECT thumbnail FROM playlists WHERE fingerprint = ?1",
-        _ => return Ok(None),
+) -> SqliteResult<Option<StoredFile>> {
+    let Some(table) = thumbnail_table(entity_type) else {
+        return Ok(None);
     };
 
-    let mut stmt = conn.prepare(sql)?;
-    let result: Option<String> = stmt
-        .query_row(params![fingerprint], |row| row.get::<_, Option<String>>(0))
-        .ok()
-        .flatten();
+    let sql = format!(
+        "SELECT thumbnail, thumbnail_sha256 FROM {} WHERE fingerprint = ?1",
+        table
+    );
+    let result = conn
+        .query_row(&sql, params![fingerprint], |row| {
+            Ok((
+                row.get::<_, Option<String>>(0)?,
+                row.get::<_, Option<String>>(1)?,
+            ))
+        })
+        .optional()?;
 
-    Ok(result.map(|filename| thumbnails_dir.join(filename)))
+    Ok(result.and_then(|(name, sha256)| name.map(|name| StoredFile { name, sha256 })))
 }
 
-fn normalize_string(s: &str) -> String {
-    let trimmed = s.trim().to_lowercase();
-    let mut result = String::with_capacity(trimmed.len());
-    let mut prev_was_space = false;
+pub fn store_song_file_sha256(
+    conn: &Connection,
+    fingerprint: &str,
+    file: &str,
+    sha256: &str,
+) -> SqliteResult<()> {
+    conn.execute(
+        "UPDATE songs SET file_sha256 = ?3 WHERE fingerprint = ?1 AND file = ?2",
+        params![fingerprint, file, sha256],
+    )?;
+    Ok(())
+}
 
-    for ch in trimmed.chars() {
-        if ch.is_whitespace() {
-            if !prev_was_space {
-                result.push(' ');
-                prev_was_space = true;
-            }
-        } else {
-            result.push(ch);
-            prev_was_space = false;
-        }
-    }
+pub fn store_thumbnail_sha256(
+    conn: &Connection,
+    fingerprint: &str,
+    entity_type: &str,
+    thumbnail: &str,
+    sha256: &str,
+) -> SqliteResult<()> {
+    let Some(table) = thumbnail_table(entity_type) else {
+        return Ok(());
+    };
 
-    result
+    let sql = format!(
+        "UPDATE {} SET thumbnail_sha256 = ?3 WHERE fingerprint = ?1 AND thumbnail = ?2",
+        table
+    );
+    conn.execute(&sql, params![fingerprint, thumbnail, sha256])?;
+    Ok(())
 }
 
-fn hash_string(input: &str) -> String {
-    let mut hasher = Sha256::new();
-    hasher.update(input.as_bytes());
-    format!("{:x}", hasher.finalize())
+#[derive(Debug, Clone, Deserialize)]
+#[serde(tag = "kind", rename_all = "camelCase")]
+pub enum ExportSelection {
+    All,
+    Favorites,
+    Playlists { fingerprints: Vec<String> },
+    Albums { fingerprints: Vec<String> },
+    Artists { fingerprints: Vec<String> },
+    AddedSince { since: i64 },
 }
 
-pub fn generate_song_fingerprint(
-    name: &str,
-    duration: i64,
-    artist_names: &[String],
-    album_name: Option<&str>,
-) -> String {
-    let normalized_name = normalize_string(name);
-    let mut sorted_artists: Vec<String> =
-        artist_names.iter().map(|a| normalize_string(a)).collect();
-    sorted_artists.sort();
-    let artists_str = sorted_artists.join(",");
-    let normalized_album = album_name.map(normalize_string).unwrap_or_default();
+#[derive(Debug, Clone)]
+pub struct ExportArtist {
+    pub name: String,
+    pub thumbnail: Option<String>,
+}
 
-    let input = format!(
-        "song:{}:{}:{}:{}",
-        normalized_name, duration, artists_str, normalized_album
-    );
-    hash_string(&input)
+#[derive(Debug, Clone)]
+pub struct ExportAlbum {
+    pub name: String,
+    pub thumbnail: Option<String>,
+    pub release_year: Option<i64>,
+    pub album_type: String,
+    pub artists: Vec<ExportArtist>,
 }
 
-pub fn generate_album_fingerprint(name: &str, album_type: &str, artist_names: &[String]) -> String {
-    let normalized_name = normalize_string(name);
-    let normalized_type = normalize_string(album_type);
-    let mut sorted_artists: Vec<String> =
-        artist_names.iter().map(|a| normalize_string(a)).collect();
-    sorted_artists.sort();
-    let artists_str = sorted_artists.join(",");
+#[derive(Debug, Clone)]
+pub struct ExportSong {
+    pub id: i64,
+    pub name: String,
+    pub thumbnail: Option<String>,
+    pub file: String,
+    pub duration: i64,
+    pub release_year: Option<i64>,
+    pub lyrics: Option<String>,
+    pub album: Option<ExportAlbum>,
+    pub artists: Vec<ExportArtist>,
+}
 
-    let input = format!(
-        "album:{}:{}:{}",
-        normalized_name, normalized_type, artists_str
-    );
-    hash_string(&input)
+#[derive(Debug, Clone)]
+pub struct ExportPlaylist {
+    pub name: String,
+    pub song_ids: Vec<i64>,
 }
 
-pub fn generate_artist_fingerprint(name: &str) -> String {
-    let normalized_name = normalize_string(name);
+pub fn select_export_song_ids(
+    conn: &Connection,
+    selection: &ExportSelection,
+) -> SqliteResult<Vec<i64>> {
+    let (sql, filter) = match selection {
+        ExportSelection::All => ("SELECT id FROM songs ORDER BY id", None),
+        ExportSelection::Favorites => (
+            "SELECT id FROM songs WHERE is_favorite = 1 ORDER BY id",
+            None,
+        ),
+        ExportSelection::Playlists { fingerprints } => (
+            "SELECT DISTINCT s.id FROM songs s \
+             JOIN playlist_songs ps ON ps.song_id = s.id \
+             JOIN playlists p ON p.id = ps.playlist_id \
+             WHERE p.fingerprint IN (SELECT value FROM json_each(?1)) \
+             ORDER BY s.id",
+            Some(fingerprints),
+        ),
+        ExportSelection::Albums { fingerprints } => (
+            "SELECT s.id FROM songs s JOIN albums a ON a.id = s.album_id \
+             WHERE a.fingerprint IN (SELECT value FROM json_each(?1)) \
+             ORDER BY s.id",
+            Some(fingerprints),
+        ),
+        ExportSelection::Artists { fingerprints } => (
+            "SELECT s.id FROM songs s \
+             WHERE s.id IN ( \
+                 SELECT sa.song_id FROM song_artists sa JOIN artists ar ON ar.id = sa.artist_id \
+                 WHERE ar.fingerprint IN (SELECT value FROM json_each(?1))) \
+             OR s.album_id IN ( \
+                 SELECT aa.album_id FROM album_artists aa JOIN artists ar ON ar.id = aa.artist_id \
+                 WHERE ar.fingerprint IN (SELECT value FROM json_each(?1))) \
+             ORDER BY s.id",
+            Some(fingerprints),
+        ),
+        ExportSelection::AddedSince { since } => {
+            let mut stmt =
+                conn.prepare("SELECT id FROM songs WHERE created_at >= ?1 ORDER BY id")?;
+            let rows = stmt.query_map(params![since], |row| row.get::<_, i64>(0))?;
+            return rows.collect();
+        }
+    };
+
+    let mut stmt = conn.prepare(sql)?;
+    let rows = match filter {
+        Some(fingerprints) => {
+            let fingerprints = serde_json::to_string(fingerprints).unwrap_or_else(|_| "[]".into());
+            stmt.query_map(params![fingerprints], |row| row.get::<_, i64>(0))?
+                .collect::<SqliteResult<Vec<_>>>()?
+        }
+        None => stmt
+            .query_map([], |row| row.get::<_, i64>(0))?
+            .collect::<SqliteResult<Vec<_>>>()?,
+    };
+    Ok(rows)
+}
+
+pub fn get_export_songs(conn: &Connection, song_ids: &[i64]) -> SqliteResult<Vec<ExportSong>> {
+    let mut songs = Vec::with_capacity(song_ids.len());
+    for chunk in song_ids.chunks(MAX_SQL_VARIABLES) {
+        songs.extend(get_export_songs_chunk(conn, chunk)?);
+    }
+    Ok(songs)
+}
+
+fn get_export_songs_chunk(conn: &Connection, song_ids: &[i64]) -> SqliteResult<Vec<ExportSong>> {
+    if song_ids.is_empty() {
+        return Ok(vec![]);
+    }
+
+    let placeholders = vec!["?"; song_ids.len()].join(",");
+    let sql = format!(
+        "SELECT s.id, s.name, s.thumbnail, s.file, s.duration, s.release_year, s.lyrics, \
+         a.id, a.name, a.thumbnail, a.release_year, a.album_type \
+         FROM songs s \
+         LEFT JOIN albums a ON s.album_id = a.id \
+         WHERE s.id IN ({}) \
+         ORDER BY s.id",
+        placeholders
+    );
+
+    let sql_params: Vec<&dyn rusqlite::types::ToSql> = song_ids
+        .iter()
+        .map(|id| id as &dyn rusqlite::types::ToSql)
+        .collect();
+
+    let mut stmt = conn.prepare(&sql)?;
+
+    let raw_rows: Vec<(ExportSong, Option<i64>)> = stmt
+        .query_map(sql_params.as_slice(), |row| {
+            let album_id: Option<i64> = row.get(7)?;
+            let album = match album_id {
+                Some(_) => Some(ExportAlbum {
+                    name: row.get(8)?,
+                    thumbnail: row.get(9)?,
+                    release_year: row.get(10)?,
+                    album_type: row.get(11)?,
+                    artists: vec![],
+                }),
+                None => None,
+            };
+            Ok((
+                ExportSong {
+                    id: row.get(0)?,
+                    name: row.get(1)?,
+                    thumbnail: row.get(2)?,
+                    file: row.get(3)?,
+                    duration: row.get(4)?,
+                    release_year: row.get(5)?,
+                    lyrics: row.get(6)?,
+                    album,
+                    artists: vec![],
+                },
+                album_id,
+            ))
+        })?
+        .collect::<SqliteResult<Vec<_>>>()?;
+
+    let album_ids: Vec<i64> = raw_rows
+        .iter()
+        .filter_map(|(_, album_id)| *album_id)
+        .collect();
+    let mut song_artists = get_export_artist_map(
+        conn,
+        "SELECT sa.song_id, ar.name, ar.thumbnail FROM song_artists sa \
+         JOIN artists ar ON sa.artist_id = ar.id \
+         WHERE sa.song_id IN ({}) ORDER BY sa.artist_order",
+        song_ids,
+    )?;
+    let album_artists = get_export_artist_map(
+        conn,
+        "SELECT aa.album_id, ar.name, ar.thumbnail FROM album_artists aa \
+         JOIN artists ar ON aa.artist_id = ar.id \
+         WHERE aa.album_id IN ({}) ORDER BY aa.artist_order",
+        &album_ids,
+    )?;
+
+    let songs = raw_rows
+        .into_iter()
+        .map(|(mut song, album_id)| {
+            song.artists = song_artists.remove(&song.id).unwrap_or_default();
+            if let (Some(album), Some(album_id)) = (song.album.as_mut(), album_id) {
+                album.artists = album_artists.get(&album_id).cloned().unwrap_or_default();
+            }
+            song
+        })
+        .collect();
+
+    Ok(songs)
+}
+
+fn get_export_artist_map(
+    conn: &Connection,
+    sql: &str,
+    ids: &[i64],
+) -> SqliteResult<HashMap<i64, Vec<ExportArtist>>> {
+    if ids.is_empty() {
+        return Ok(HashMap::new());
+    }
+
+    let placeholders = vec!["?"; ids.len()].join(",");
+    let sql = sql.replace("{}", &placeholders);
+
+    let sql_params: Vec<&dyn rusqlite::types::ToSql> = ids
+        .iter()
+        .map(|id| id as &dyn rusqlite::types::ToSql)
+        .collect();
+
+    let mut stmt = conn.prepare(&sql)?;
+    let rows = stmt
+        .query_map(sql_params.as_slice(), |row| {
+            Ok((
+                row.get::<_, i64>(0)?,
+                ExportArtist {
+                    name: row.get(1)?,
+                    thumbnail: row.get(2)?,
+                },
+            ))
+        })?
+        .collect::<SqliteResult<Vec<_>>>()?;
+
+    let mut map: HashMap<i64, Vec<ExportArtist>> = HashMap::new();
+    for (id, artist) in rows {
+        map.entry(id).or_default().push(artist);
+    }
+
+    Ok(map)
+}
+
+pub fn get_export_playlists(
+    conn: &Connection,
+    selection: &ExportSelection,
+) -> SqliteResult<Vec<ExportPlaylist>> {
+    let fingerprints = match selection {
+        ExportSelection::Playlists { fingerprints } => {
+            Some(serde_json::to_string(fingerprints).unwrap_or_else(|_| "[]".into()))
+        }
+        _ => None,
+    };
+
+    let mut stmt = conn.prepare(
+        "SELECT p.id, p.name, ps.song_id \
+         FROM playlist_songs ps \
+         JOIN playlists p ON ps.playlist_id = p.id \
+         WHERE ?1 IS NULL OR p.fingerprint IN (SELECT value FROM json_each(?1)) \
+         ORDER BY p.id, ps.position, ps.added_at, ps.song_id",
+    )?;
+
+    let mut playlists: Vec<(i64, ExportPlaylist)> = Vec::new();
+    let rows = stmt.query_map(params![fingerprints], |row| {
+        Ok((
+            row.get::<_, i64>(0)?,
+            row.get::<_, String>(1)?,
+            row.get::<_, i64>(2)?,
+        ))
+    })?;
+    for row in rows {
+        let (playlist_id, name, song_id) = row?;
+        match playlists.last_mut() {
+            Some((id, playlist)) if *id == playlist_id => playlist.song_ids.push(song_id),
+            _ => playlists.push((
+                playlist_id,
+                ExportPlaylist {
+                    name,
+                    song_ids: vec![song_id],
+                },
+            )),
+        }
+    }
+
+    Ok(playlists
+        .into_iter()
+        .map(|(_, playlist)| playlist)
+        .collect())
+}
+
+#[derive(Debug, Clone, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct NewArtistRow {
+    pub fingerprint: Option<String>,
+    pub name: String,
+    #[serde(default)]
+    pub is_favorite: bool,
+}
+
+#[derive(Debug, Clone, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct NewAlbumRow {
+    pub fingerprint: Option<String>,
+    pub name: String,
+    pub album_type: String,
+    pub release_year: Option<i64>,
+    #[serde(default)]
+    pub is_favorite: bool,
+    #[serde(default)]
+    pub artist_fingerprints: Vec<ArtistOrder>,
+}
+
+#[derive(Debug, Clone, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct NewSongRow {
+    pub fingerprint: Option<String>,
+    pub name: String,
+    pub duration: i64,
+    pub release_year: Option<i64>,
+    #[serde(default)]
+    pub is_favorite: bool,
+    pub lyrics: Option<String>,
+    pub album_fingerprint: Option<String>,
+    #[serde(default)]
+    pub artist_fingerprints: Vec<ArtistOrder>,
+}
+
+#[derive(Debug, Clone, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct NewPlaylistRow {
+    pub fingerprint: Option<String>,
+    pub name: String,
+    #[serde(default)]
+    pub is_favorite: bool,
+    #[serde(default)]
+    pub song_fingerprints: Vec<String>,
+    #[serde(default)]
+    pub entries: Vec<PlaylistEntry>,
+}
+
+fn resolve_playlist_entries(
+    entries: &[PlaylistEntry],
+    song_fingerprints: &[String],
+    added_at: i64,
+) -> Vec<PlaylistEntry> {
+    if !entries.is_empty() {
+        return entries.to_vec();
+    }
+
+    song_fingerprints
+        .iter()
+        .enumerate()
+        .map(|(position, song_fingerprint)| PlaylistEntry {
+            song_fingerprint: song_fingerprint.clone(),
+            position: position as i64,
+            added_at,
+        })
+        .collect()
+}
+
+fn write_playlist_entries(
+    conn: &Connection,
+    playlist_id: i64,
+    entries: &[PlaylistEntry],
+    replace: bool,
+    keep_added_at: bool,
+) -> SqliteResult<()> {
+    if replace {
+        let song_fingerprints = playlist_entry_fingerprints(entries);
+        conn.execute(
+            "DELETE FROM playlist_songs WHERE playlist_id = ?1 AND song_id NOT IN ( \
+                 SELECT s.id FROM songs s, json_each(?2) j WHERE s.fingerprint = j.value \
+             )",
+            params![
+                playlist_id,
+                serde_json::to_string(&song_fingerprints).unwrap_or_default()
+            ],
+        )?;
+    }
+
+    let conflict = match (replace, keep_added_at) {
+        (true, true) => "DO UPDATE SET position = excluded.position",
+        (true, false) => "DO UPDATE SET position = excluded.position, added_at = excluded.added_at",
+        (false, _) => "DO NOTHING",
+    };
+
+    for entry in entries {
+        conn.execute(
+            &format!(
+                "INSERT INTO playlist_songs (playlist_id, song_id, added_at, position) \
+                 SELECT ?1, id, ?3, ?4 FROM songs WHERE fingerprint = ?2 \
+                 ON CONFLICT (playlist_id, song_id) {}",
+                conflict
+            ),
+            params![
+                playlist_id,
+                entry.song_fingerprint,
+                entry.added_at,
+                entry.position
+            ],
+        )?;
+    }
+
+    Ok(())
+}
+
+#[derive(Debug, Clone, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct PushOutcome {
+    pub fingerprint: String,
+    pub client_fingerprint: Option<String>,
+    pub created: bool,
+}
+
+impl PushOutcome {
+    fn new(fingerprint: String, client_fingerprint: Option<String>, created: bool) -> Self {
+        Self {
+            fingerprint,
+            client_fingerprint,
+            created,
+        }
+    }
+}
+
+fn get_id_by_fingerprint(
+    conn: &Connection,
+    table_name: &str,
+    fingerprint: &str,
+) -> SqliteResult<Option<i64>> {
+    let sql = format!("SELECT id FROM {} WHERE fingerprint = ?1", table_name);
+    let mut stmt = conn.prepare(&sql)?;
+    let result = stmt
+        .query_row(params![fingerprint], |row| row.get::<_, i64>(0))
+        .ok();
+    Ok(result)
+}
+
+fn resolve_artists(
+    conn: &Connection,
+    artists: &[ArtistOrder],
+) -> Result<Vec<(i64, String, i64)>, String> {
+    let mut stmt = conn
+        .prepare("SELECT id, name FROM artists WHERE fingerprint = ?1")
+        .map_err(|e| e.to_string())?;
+
+    let mut resolved = Vec::with_capacity(artists.len());
+    for artist in artists {
+        let (id, name) = stmt
+            .query_row(params![artist.fingerprint], |row| {
+                Ok((row.get::<_, i64>(0)?, row.get::<_, String>(1)?))
+            })
+            .map_err(|_| format!("Unknown artist fingerprint: {}", artist.fingerprint))?;
+        resolved.push((id, name, artist.artist_order));
+    }
+
+    resolved.sort_by_key(|(_, _, order)| *order);
+    Ok(resolved)
+}
+
+fn refresh_album_totals(conn: &Connection, album_id: i64) -> SqliteResult<()> {
+    conn.execute(
+        "UPDATE albums SET \
+         total_tracks = (SELECT COUNT(*) FROM songs WHERE album_id = ?1), \
+         total_duration = (SELECT COALESCE(SUM(duration), 0) FROM songs WHERE album_id = ?1) \
+         WHERE id = ?1",
+        params![album_id],
+    )?;
+    Ok(())
+}
+
+fn refresh_artist_totals(conn: &Connection, artist_id: i64) -> SqliteResult<()> {
+    conn.execute(
+        "UPDATE artists SET \
+         total_tracks = (SELECT COUNT(DISTINCT sa.song_id) FROM song_artists sa \
+         JOIN songs s ON sa.song_id = s.id WHERE sa.artist_id = ?1), \
+         total_duration = (SELECT COALESCE(SUM(s.duration), 0) FROM song_artists sa \
+         JOIN songs s ON sa.song_id = s.id WHERE sa.artist_id = ?1) \
+         WHERE id = ?1",
+        params![artist_id],
+    )?;
+    Ok(())
+}
+
+fn refresh_playlist_totals(conn: &Connection, playlist_id: i64) -> SqliteResult<()> {
+    conn.execute(
+        "UPDATE playlists SET \
+         total_tracks = (SELECT COUNT(*) FROM playlist_songs ps \
+         JOIN songs s ON ps.song_id = s.id WHERE ps.playlist_id = ?1), \
+         total_duration = (SELECT COALESCE(SUM(s.duration), 0) FROM playlist_songs ps \
+         JOIN songs s ON ps.song_id = s.id WHERE ps.playlist_id = ?1) \
+         WHERE id = ?1",
+        params![playlist_id],
+    )?;
+    Ok(())
+}
+
+pub fn insert_artist(
+    conn: &mut Connection,
+    artist: &NewArtistRow,
+    thumbnail: Option<&str>,
+) -> Result<PushOutcome, String> {
+    let fingerprint = generate_artist_fingerprint(&artist.name);
+    let client_fingerprint = artist.fingerprint.clone();
+
+    if get_id_by_fingerprint(conn, "artists", &fingerprint)
+        .map_err(|e| e.to_string())?
+        .is_some()
+    {
+        return Ok(PushOutcome::new(fingerprint, client_fingerprint, false));
+    }
+
+    conn.execute(
+        "INSERT INTO artists (uuid, name, thumbnail, is_favorite, fingerprint) \
+         VALUES (?1, ?2, ?3, ?4, ?5)",
+        params![
+            uuid::Uuid::new_v4().to_string(),
+            artist.name,
+            thumbnail,
+            artist.is_favorite,
+            fingerprint
+        ],
+    )
+    .map_err(|e| format!("Failed to insert artist {}: {}", artist.name, e))?;
+
+    Ok(PushOutcome::new(fingerprint, client_fingerprint, true))
+}
+
+pub fn insert_album(
+    conn: &mut Connection,
+    album: &NewAlbumRow,
+    thumbnail: Option<&str>,
+) -> Result<PushOutcome, String> {
+    let artists = resolve_artists(conn, &album.artist_fingerprints)?;
+    let artist_names: Vec<String> = artists.iter().map(|(_, name, _)| name.clone()).collect();
+    let fingerprint = generate_album_fingerprint(&album.name, &album.album_type, &artist_names);
+    let client_fingerprint = album.fingerprint.clone();
+
+    if get_id_by_fingerprint(conn, "albums", &fingerprint)
+        .map_err(|e| e.to_string())?
+        .is_some()
+    {
+        return Ok(PushOutcome::new(fingerprint, client_fingerprint, false));
+    }
+
+    let tx = conn.transaction().map_err(|e| e.to_string())?;
+
+    tx.execute(
+        "INSERT INTO albums (uuid, name, thumbnail, release_year, is_favorite, album_type, fingerprint) \
+         VALUES (?1, ?2, ?3, ?4, ?5, ?6, ?7)",
+        params![
+            uuid::Uuid::new_v4().to_string(),
+            album.name,
+            thumbnail,
+            album.release_year,
+            album.is_favorite,
+            album.album_type,
+            fingerprint
+        ],
+    )
+    .map_err(|e| format!("Failed to insert album {}: {}", album.name, e))?;
+    let album_id = tx.last_insert_rowid();
+
+    for (artist_id, _, artist_order) in &artists {
+        tx.execute(
+            "INSERT OR IGNORE INTO album_artists (album_id, artist_id, artist_order) \
+             VALUES (?1, ?2, ?3)",
+            params![album_id, artist_id, artist_order],
+        )
+        .map_err(|e| e.to_string())?;
+    }
+
+    tx.commit().map_err(|e| e.to_string())?;
+
+    Ok(PushOutcome::new(fingerprint, client_fingerprint, true))
+}
+
+pub fn insert_song(
+    conn: &mut Connection,
+    song: &NewSongRow,
+    file: &str,
+    thumbnail: Option<&str>,
+) -> Result<PushOutcome, String> {
+    let artists = resolve_artists(conn, &song.artist_fingerprints)?;
+    let artist_names: Vec<String> = artists.iter().map(|(_, name, _)| name.clone()).collect();
+
+    let album = match &song.album_fingerprint {
+        Some(album_fingerprint) => Some(
+            conn.query_row(
+                "SELECT id, name FROM albums WHERE fingerprint = ?1",
+                params![album_fingerprint],
+                |row| Ok((row.get::<_, i64>(0)?, row.get::<_, String>(1)?)),
+            )
+            .map_err(|_| format!("Unknown album fingerprint: {}", album_fingerprint))?,
+        ),
+        None => None,
+    };
+
+    let fingerprint = generate_song_fingerprint(
+        &song.name,
+        song.duration,
+        &artist_names,
+        album.as_ref().map(|(_, name)| name.as_str()),
+    );
+    let client_fingerprint = song.fingerprint.clone();
+
+    if get_id_by_fingerprint(conn, "songs", &fingerprint)
+        .map_err(|e| e.to_string())?
+        .is_some()
+    {
+        return Ok(PushOutcome::new(fingerprint, client_fingerprint, false));
+    }
+
+    let album_id = album.map(|(id, _)| id);
+    let tx = conn.transaction().map_err(|e| e.to_string())?;
+
+    tx.execute(
+        "INSERT INTO songs (uuid, name, thumbnail, file, duration, is_favorite, release_year, \
+         album_id, lyrics, fingerprint) \
+         VALUES (?1, ?2, ?3, ?4, ?5, ?6, ?7, ?8, COALESCE(?9, '[]'), ?10)",
+        params![
+            uuid::Uuid::new_v4().to_string(),
+            song.name,
+            thumbnail,
+            file,
+            song.duration,
+            song.is_favorite,
+            song.release_year,
+            album_id,
+            song.lyrics,
+            fingerprint
+        ],
+    )
+    .map_err(|e| format!("Failed to insert song {}: {}", song.name, e))?;
+    let song_id = tx.last_insert_rowid();
+
+    for (artist_id, _, artist_order) in &artists {
+        tx.execute(
+            "INSERT OR IGNORE INTO song_artists (song_id, artist_id, artist_order) \
+             VALUES (?1, ?2, ?3)",
+            params![song_id, artist_id, artist_order],
+        )
+        .map_err(|e| e.to_string())?;
+        refresh_artist_totals(&tx, *artist_id).map_err(|e| e.to_string())?;
+    }
+
+    if let Some(album_id) = album_id {
+        refresh_album_totals(&tx, album_id).map_err(|e| e.to_string())?;
+    }
+
+    tx.commit().map_err(|e| e.to_string())?;
+
+    Ok(PushOutcome::new(fingerprint, client_fingerprint, true))
+}
+
+pub fn insert_playlist(
+    conn: &mut Connection,
+    playlist: &NewPlaylistRow,
+    thumbnail: Option<&str>,
+) -> Result<PushOutcome, String> {
+    let fingerprint = generate_playlist_fingerprint(&playlist.name);
+    let client_fingerprint = playlist.fingerprint.clone();
+
+    let existing_id =
+        get_id_by_fingerprint(conn, "playlists", &fingerprint).map_err(|e| e.to_string())?;
+    let created = existing_id.is_none();
+
+    let tx = conn.transaction().map_err(|e| e.to_string())?;
+
+    let playlist_id = match existing_id {
+        Some(id) => id,
+        None => {
+            tx.execute(
+                "INSERT INTO playlists (uuid, name, thumbnail, is_favorite, fingerprint) \
+                 VALUES (?1, ?2, ?3, ?4, ?5)",
+                params![
+                    uuid::Uuid::new_v4().to_string(),
+                    playlist.name,
+                    thumbnail,
+                    playlist.is_favorite,
+                    fingerprint
+                ],
+            )
+            .map_err(|e| format!("Failed to insert playlist {}: {}", playlist.name, e))?;
+            tx.last_insert_rowid()
+        }
+    };
+
+    let now = current_timestamp(&tx).map_err(|e| e.to_string())?;
+    let entries = resolve_playlist_entries(&playlist.entries, &playlist.song_fingerprints, now);
+    write_playlist_entries(&tx, playlist_id, &entries, false, true).map_err(|e| e.to_string())?;
+
+    refresh_playlist_totals(&tx, playlist_id).map_err(|e| e.to_string())?;
+
+    tx.commit().map_err(|e| e.to_string())?;
+
+    Ok(PushOutcome::new(fingerprint, client_fingerprint, created))
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct EntityVersion {
+    pub fingerprint: String,
+    pub content_hash: String,
+    pub updated_at: i64,
+}
+
+pub fn get_song_versions(conn: &Connection) -> SqliteResult<Vec<EntityVersion>> {
+    let mut stmt = conn.prepare(
+        "SELECT fingerprint, release_year, is_favorite, lyrics, updated_at \
+         FROM songs WHERE fingerprint IS NOT NULL",
+    )?;
+    let rows = stmt.query_map([], |row| {
+        let lyrics: Option<String> = row.get(3)?;
+        Ok(EntityVersion {
+            fingerprint: row.get(0)?,
+            content_hash: song_content_hash(
+                row.get(1)?,
+                row.get::<_, i64>(2)? != 0,
+                lyrics.as_deref(),
+            ),
+            updated_at: row.get(4)?,
+        })
+    })?;
+    rows.collect()
+}
+
+pub fn get_album_versions(conn: &Connection) -> SqliteResult<Vec<EntityVersion>> {
+    let mut stmt = conn.prepare(
+        "SELECT fingerprint, release_year, is_favorite, updated_at \
+         FROM albums WHERE fingerprint IS NOT NULL",
+    )?;
+    let rows = stmt.query_map([], |row| {
+        Ok(EntityVersion {
+            fingerprint: row.get(0)?,
+            content_hash: album_content_hash(row.get(1)?, row.get::<_, i64>(2)? != 0),
+            updated_at: row.get(3)?,
+        })
+    })?;
+    rows.collect()
+}
+
+pub fn get_artist_versions(conn: &Connection) -> SqliteResult<Vec<EntityVersion>> {
+    let mut stmt = conn.prepare(
+        "SELECT fingerprint, is_favorite, updated_at \
+         FROM artists WHERE fingerprint IS NOT NULL",
+    )?;
+    let rows = stmt.query_map([], |row| {
+        Ok(EntityVersion {
+            fingerprint: row.get(0)?,
+            content_hash: artist_content_hash(row.get::<_, i64>(1)? != 0),
+            updated_at: row.get(2)?,
+        })
+    })?;
+    rows.collect()
+}
+
+pub fn get_playlist_versions(conn: &Connection) -> SqliteResult<Vec<EntityVersion>> {
+    let mut members_stmt = conn.prepare(&format!(
+        "{} {}",
+        PLAYLIST_ENTRIES_SQL, PLAYLIST_ENTRIES_ORDER
+    ))?;
+    let members: HashMap<i64, Vec<String>> = collect_playlist_entries(&mut members_stmt, &[])?
+        .into_iter()
+        .map(|(playlist_id, entries)| (playlist_id, playlist_entry_fingerprints(&entries)))
+        .collect();
+
+    let mut stmt = conn.prepare(
+        "SELECT id, fingerprint, is_favorite, updated_at \
+         FROM playlists WHERE fingerprint IS NOT NULL",
+    )?;
+    let rows = stmt.query_map([], |row| {
+        let id: i64 = row.get(0)?;
+        let song_fingerprints = members.get(&id).map(Vec::as_slice).unwrap_or_default();
+        Ok(EntityVersion {
+            fingerprint: row.get(1)?,
+            content_hash: playlist_content_hash(row.get::<_, i64>(2)? != 0, song_fingerprints),
+            updated_at: row.get(3)?,
+        })
+    })?;
+    rows.collect()
+}
+
+#[derive(Debug, Clone, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct SongUpdate {
+    pub fingerprint: String,
+    pub updated_at: i64,
+    pub release_year: Option<i64>,
+    pub is_favorite: bool,
+    pub lyrics: Option<String>,
+}
+
+#[derive(Debug, Clone, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct AlbumUpdate {
+    pub fingerprint: String,
+    pub updated_at: i64,
+    pub release_year: Option<i64>,
+    pub is_favorite: bool,
+}
+
+#[derive(Debug, Clone, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct ArtistUpdate {
+    pub fingerprint: String,
+    pub updated_at: i64,
+    pub is_favorite: bool,
+}
+
+#[derive(Debug, Clone, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct PlaylistUpdate {
+    pub fingerprint: String,
+    pub updated_at: i64,
+    pub is_favorite: bool,
+    pub song_fingerprints: Option<Vec<String>>,
+    pub entries: Option<Vec<PlaylistEntry>>,
+}
+
+#[derive(Debug, Clone, Default, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct UpdateOutcome {
+    pub applied: Vec<String>,
+    pub rejected: Vec<String>,
+}
+
+fn find_outdated_id(
+    conn: &Connection,
+    table_name: &str,
+    fingerprint: &str,
+    updated_at: i64,
+) -> SqliteResult<Option<i64>> {
+    let sql = format!(
+        "SELECT id FROM {} WHERE fingerprint = ?1 AND updated_at < ?2",
+        table_name
+    );
+    conn.query_row(&sql, params![fingerprint, updated_at], |row| row.get(0))
+        .optional()
+}
+
+pub fn apply_song_updates(
+    conn: &mut Connection,
+    updates: &[SongUpdate],
+    outcome: &mut UpdateOutcome,
+) -> SqliteResult<()> {
+    let tx = conn.transaction()?;
+    for update in updates {
+        let Some(id) = find_outdated_id(&tx, "songs", &update.fingerprint, update.updated_at)?
+        else {
+            outcome.rejected.push(update.fingerprint.clone());
+            continue;
+        };
+        tx.execute(
+            "UPDATE songs SET release_year = ?1, is_favorite = ?2, lyrics = COALESCE(?3, '[]'), \
+             updated_at = unixepoch() WHERE id = ?4",
+            params![update.release_year, update.is_favorite, update.lyrics, id],
+        )?;
+        outcome.applied.push(update.fingerprint.clone());
+    }
+    tx.commit()
+}
+
+pub fn apply_album_updates(
+    conn: &mut Connection,
+    updates: &[AlbumUpdate],
+    outcome: &mut UpdateOutcome,
+) -> SqliteResult<()> {
+    let tx = conn.transaction()?;
+    for update in updates {
+        let Some(id) = find_outdated_id(&tx, "albums", &update.fingerprint, update.updated_at)?
+        else {
+            outcome.rejected.push(update.fingerprint.clone());
+            continue;
+        };
+        tx.execute(
+            "UPDATE albums SET release_year = ?1, is_favorite = ?2, updated_at = unixepoch() \
+             WHERE id = ?3",
+            params![update.release_year, update.is_favorite, id],
+        )?;
+        outcome.applied.push(update.fingerprint.clone());
+    }
+    tx.commit()
+}
+
+pub fn apply_artist_updates(
+    conn: &mut Connection,
+    updates: &[ArtistUpdate],
+    outcome: &mut UpdateOutcome,
+) -> SqliteResult<()> {
+    let tx = conn.transaction()?;
+    for update in updates {
+        let Some(id) = find_outdated_id(&tx, "artists", &update.fingerprint, update.updated_at)?
+        else {
+            outcome.rejected.push(update.fingerprint.clone());
+            continue;
+        };
+        tx.execute(
+            "UPDATE artists SET is_favorite = ?1, updated_at = unixepoch() WHERE id = ?2",
+            params![update.is_favorite, id],
+        )?;
+        outcome.applied.push(update.fingerprint.clone());
+    }
+    tx.commit()
+}
+
+pub fn apply_playlist_updates(
+    conn: &mut Connection,
+    updates: &[PlaylistUpdate],
+    outcome: &mut UpdateOutcome,
+) -> SqliteResult<()> {
+    let tx = conn.transaction()?;
+    for update in updates {
+        let Some(id) = find_outdated_id(&tx, "playlists", &update.fingerprint, update.updated_at)?
+        else {
+            outcome.rejected.push(update.fingerprint.clone());
+            continue;
+        };
+
+        let entries = match (&update.entries, &update.song_fingerprints) {
+            (Some(entries), _) => Some((entries.clone(), false)),
+            (None, Some(song_fingerprints)) => Some((
+                resolve_playlist_entries(&[], song_fingerprints, update.updated_at),
+                true,
+            )),
+            (None, None) => None,
+        };
+
+        if let Some((entries, keep_added_at)) = entries {
+            write_playlist_entries(&tx, id, &entries, true, keep_added_at)?;
+            refresh_playlist_totals(&tx, id)?;
+        }
+
+        tx.execute(
+            "UPDATE playlists SET is_favorite = ?1, updated_at = unixepoch() WHERE id = ?2",
+            params![update.is_favorite, id],
+        )?;
+        outcome.applied.push(update.fingerprint.clone());
+    }
+    tx.commit()
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct PlayEvent {
+    pub song_fingerprint: String,
+    pub played_at: i64,
+    #[serde(default = "default_play_source")]
+    pub play_source: String,
+    #[serde(default)]
+    pub time_listened: i64,
+}
+
+fn default_play_source() -> String {
+    "unknown".to_string()
+}
+
+#[derive(Debug, Clone, Default, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct HistoryMergeOutcome {
+    pub inserted: usize,
+    pub duplicates: usize,
+    pub unknown_songs: Vec<String>,
+}
+
+pub fn get_play_history_after(
+    conn: &Connection,
+    after_id: i64,
+    limit: u32,
+) -> SqliteResult<Vec<(i64, PlayEvent)>> {
+    let mut stmt = conn.prepare(
+        "SELECT ph.id, s.fingerprint, ph.played_at, ph.play_source, ph.time_listened \
+         FROM play_history ph \
+         JOIN songs s ON ph.song_id = s.id \
+         WHERE ph.id > ?1 AND s.fingerprint IS NOT NULL \
+         ORDER BY ph.id \
+         LIMIT ?2",
+    )?;
+    let rows = stmt.query_map(params![after_id, limit], |row| {
+        Ok((
+            row.get(0)?,
+            PlayEvent {
+                song_fingerprint: row.get(1)?,
+                played_at: row.get(2)?,
+                play_source: row.get(3)?,
+                time_listened: row.get(4)?,
+            },
+        ))
+    })?;
+    rows.collect()
+}
+
+struct PlayAggregate {
+    table: &'static str,
+    stats_table: &'static str,
+    stats_key: &'static str,
+    owners: &'static str,
+    plays: &'static str,
+}
+
+const PLAY_AGGREGATES: [PlayAggregate; 4] = [
+    PlayAggregate {
+        table: "songs",
+        stats_table: "song_stats",
+        stats_key: "song_id",
+        owners: "SELECT value AS owner_id FROM json_each(?1)",
+        plays: "SELECT ph.song_id AS owner_id, ph.played_at, ph.time_listened \
+                FROM play_history ph",
+    },
+    PlayAggregate {
+        table: "albums",
+        stats_table: "album_stats",
+        stats_key: "album_id",
+        owners: "SELECT DISTINCT album_id AS owner_id FROM songs \
+                 WHERE album_id IS NOT NULL AND id IN (SELECT value FROM json_each(?1))",
+        plays: "SELECT s.album_id AS owner_id, ph.played_at, ph.time_listened \
+                FROM play_history ph JOIN songs s ON s.id = ph.song_id",
+    },
+    PlayAggregate {
+        table: "artists",
+        stats_table: "artist_stats",
+        stats_key: "artist_id",
+        owners: "SELECT DISTINCT artist_id AS owner_id FROM song_artists \
+                 WHERE song_id IN (SELECT value FROM json_each(?1))",
+        plays: "SELECT sa.artist_id AS owner_id, ph.played_at, ph.time_listened \
+                FROM play_history ph JOIN song_artists sa ON sa.song_id = ph.song_id",
+    },
+    PlayAggregate {
+        table: "playlists",
+        stats_table: "playlist_stats",
+        stats_key: "playlist_id",
+        owners: "SELECT DISTINCT playlist_id AS owner_id FROM playlist_songs \
+                 WHERE song_id IN (SELECT value FROM json_each(?1))",
+        plays: "SELECT ps.playlist_id AS owner_id, ph.played_at, ph.time_listened \
+                FROM play_history ph JOIN playlist_songs ps ON ps.song_id = ph.song_id \
+                WHERE ph.play_source = 'playlist' AND ph.played_at >= ps.added_at",
+    },
+];
+
+fn recompute_play_stats(conn: &Connection, song_ids: &[i64]) -> SqliteResult<()> {
+    if song_ids.is_empty() {
+        return Ok(());
+    }
+    let song_ids = serde_json::to_string(song_ids).unwrap_or_default();
+
+    for aggregate in &PLAY_AGGREGATES {
+        let sql = format!(
+            "UPDATE {table} SET \
+             play_count = (SELECT COUNT(*) FROM ({plays}) p \
+                 WHERE p.owner_id = {table}.id), \
+             last_played_at = (SELECT MAX(p.played_at) FROM ({plays}) p \
+                 WHERE p.owner_id = {table}.id) \
+             WHERE id IN ({owners})",
+            table = aggregate.table,
+            plays = aggregate.plays,
+            owners = aggregate.owners
+        );
+        conn.execute(&sql, params![song_ids])?;
+
+        let sql = format!(
+            "INSERT INTO {stats} ({key}, total_play_time, last_calculated_at) \
+             SELECT o.owner_id, \
+             (SELECT COALESCE(SUM(p.time_listened), 0) FROM ({plays}) p \
+                 WHERE p.owner_id = o.owner_id), \
+             unixepoch() \
+             FROM ({owners}) o WHERE true \
+             ON CONFLICT({key}) DO UPDATE SET \
+             total_play_time = excluded.total_play_time, \
+             last_calculated_at = excluded.last_calculated_at",
+            stats = aggregate.stats_table,
+            key = aggregate.stats_key,
+            plays = aggregate.plays,
+            owners = aggregate.owners
+        );
+        conn.execute(&sql, params![song_ids])?;
+    }
+
+    Ok(())
+}
+
+pub fn merge_play_history(
+    conn: &mut Connection,
+    events: &[PlayEvent],
+) -> SqliteResult<HistoryMergeOutcome> {
+    let tx = conn.transaction()?;
+    let mut outcome = HistoryMergeOutcome::default();
+    let mut affected_song_ids: HashSet<i64> = HashSet::new();
+
+    for event in events {
+        let song_id: Option<i64> = tx
+            .query_row(
+                "SELECT id FROM songs WHERE fingerprint = ?1",
+                params![event.song_fingerprint],
+                |row| row.get(0),
+            )
+            .optional()?;
+
+        let Some(song_id) = song_id else {
+            outcome.unknown_songs.push(event.song_fingerprint.clone());
+            continue;
+        };
+
+        let exists = tx
+            .query_row(
+                "SELECT 1 FROM play_history WHERE song_id = ?1 AND played_at = ?2",
+                params![song_id, event.played_at],
+                |_| Ok(()),
+            )
+            .optional()?
+            .is_some();
+        if exists {
+            outcome.duplicates += 1;
+            continue;
+        }
+
+        tx.execute(
+            "INSERT INTO play_history (song_id, played_at, play_source, time_listened) \
+             VALUES (?1, ?2, ?3, ?4)",
+            params![
+                song_id,
+                event.played_at,
+                event.play_source,
+                event.time_listened.max(0)
+            ],
+        )?;
+
+        affected_song_ids.insert(song_id);
+        outcome.inserted += 1;
+    }
+
+    let affected_song_ids: Vec<i64> = affected_song_ids.into_iter().collect();
+    recompute_play_stats(&tx, &affected_song_ids)?;
+
+    tx.commit()?;
+    Ok(outcome)
+}
+
+fn normalize_string(s: &str) -> String {
+    let trimmed = s.trim().to_lowercase();
+    let mut result = String::with_capacity(trimmed.len());
+    let mut prev_was_space = false;
+
+    for ch in trimmed.chars() {
+        if ch.is_whitespace() {
+            if !prev_was_space {
+                result.push(' ');
+                prev_was_space = true;
+            }
+        } else {
+            result.push(ch);
+            prev_was_space = false;
+        }
+    }
+
+    result
+}
+
+fn hash_string(input: &str) -> String {
+    let mut hasher = Sha256::new();
+    hasher.update(input.as_bytes());
+    format!("{:x}", hasher.finalize())
+}
+
+pub fn generate_song_fingerprint(
+    name: &str,
+    duration: i64,
+    artist_names: &[String],
+    album_name: Option<&str>,
+) -> String {
+    let normalized_name = normalize_string(name);
+    let mut sorted_artists: Vec<String> =
+        artist_names.iter().map(|a| normalize_string(a)).collect();
+    sorted_artists.sort();
+    let artists_str = sorted_artists.join(",");
+    let normalized_album = album_name.map(normalize_string).unwrap_or_default();
+
+    let input = format!(
+        "song:{}:{}:{}:{}",
+        normalized_name, duration, artists_str, normalized_album
+    );
+    hash_string(&input)
+}
+
+pub fn generate_album_fingerprint(name: &str, album_type: &str, artist_names: &[String]) -> String {
+    let normalized_name = normalize_string(name);
+    let normalized_type = normalize_string(album_type);
+    let mut sorted_artists: Vec<String> =
+        artist_names.iter().map(|a| normalize_string(a)).collect();
+    sorted_artists.sort();
+    let artists_str = sorted_artists.join(",");
+
+    let input = format!(
+        "album:{}:{}:{}",
+        normalized_name, normalized_type, artists_str
+    );
+    hash_string(&input)
+}
+
+pub fn generate_artist_fingerprint(name: &str) -> String {
+    let normalized_name = normalize_string(name);
     let input = format!("artist:{}", normalized_name);
     hash_string(&input)
 }
@@ -592,6 +2270,43 @@ pub fn generate_playlist_fingerprint(name: &str) -> String {
     hash_string(&input)
 }
 
+pub fn song_content_hash(
+    release_year: Option<i64>,
+    is_favorite: bool,
+    lyrics: Option<&str>,
+) -> String {
+    let input = format!(
+        "song:{}:{}:{}",
+        release_year.map(|y| y.to_string()).unwrap_or_default(),
+        is_favorite as u8,
+        lyrics.unwrap_or_default()
+    );
+    hash_string(&input)
+}
+
+pub fn album_content_hash(release_year: Option<i64>, is_favorite: bool) -> String {
+    let input = format!(
+        "album:{}:{}",
+        release_year.map(|y| y.to_string()).unwrap_or_default(),
+        is_favorite as u8
+    );
+    hash_string(&input)
+}
+
+pub fn artist_content_hash(is_favorite: bool) -> String {
+    let input = format!("artist:{}", is_favorite as u8);
+    hash_string(&input)
+}
+
+pub fn playlist_content_hash(is_favorite: bool, song_fingerprints: &[String]) -> String {
+    let input = format!(
+        "playlist:{}:{}",
+        is_favorite as u8,
+        song_fingerprints.join(",")
+    );
+    hash_string(&input)
+}
+
 pub fn backfill_all_fingerprints(conn: &Connection) -> Result<u64, String> {
     let mut total_updated: u64 = 0;
 
@@ -0,0 +1,213 @@
+use std::collections::HashMap;
+use std::net::IpAddr;
+use std::time::{Duration, Instant};
+
+use mdns_sd::{ServiceDaemon, ServiceEvent, ServiceInfo};
+
+use serde::Serialize;
+
+use super::network::is_link_local;
+
+pub const SERVICE_TYPE: &str = "_tunno._tcp.local.";
+
+const TXT_VERSION: &str = "version";
+const TXT_NAME: &str = "name";
+const TXT_FINGERPRINT: &str = "fingerprint";
+const TXT_SCHEME: &str = "scheme";
+
+const INSTANCE_ID_CHARS: usize = 8;
+pub const MAX_BROWSE_TIMEOUT: Duration = Duration::from_secs(30);
+
+pub struct ServiceAdvertisement {
+    pub device_name: String,
+    pub address: Option<IpAddr>,
+    pub port: u16,
+    pub cert_fingerprint: Option<String>,
+}
+
+pub struct Advertiser {
+    daemon: ServiceDaemon,
+    fullname: String,
+}
+
+impl Advertiser {
+    pub fn register(advertisement: ServiceAdvertisement) -> Result<Self, String> {
+        let daemon =
+            ServiceDaemon::new().map_err(|e| format!("Failed to start mDNS daemon: {}", e))?;
+
+        let instance_id: String = match &advertisement.cert_fingerprint {
+            Some(fingerprint) => fingerprint.chars().take(INSTANCE_ID_CHARS).collect(),
+            None => uuid::Uuid::new_v4()
+                .simple()
+                .to_string()
+                .chars()
+                .take(INSTANCE_ID_CHARS)
+                .collect(),
+        };
+        let instance_name = format!("{} [{}]", advertisement.device_name, instance_id);
+        let host_name = format!("tunno-{}.local.", instance_id);
+
+        let scheme = if advertisement.cert_fingerprint.is_some() {
+            "https"
+        } else {
+            "http"
+        };
+        let mut properties = vec![
+            (TXT_VERSION, super::PROTOCOL_VERSION),
+            (TXT_NAME, advertisement.device_name.as_str()),
+            (TXT_SCHEME, scheme),
+        ];
+        if let Some(fingerprint) = &advertisement.cert_fingerprint {
+            properties.push((TXT_FINGERPRINT, fingerprint.as_str()));
+        }
+
+        let addresses = advertisement
+            .address
+            .map(|address| address.to_string())
+            .unwrap_or_default();
+        let service = ServiceInfo::new(
+            SERVICE_TYPE,
+            &instance_name,
+            &host_name,
+            addresses.as_str(),
+            advertisement.port,
+            properties.as_slice(),
+        )
+        .map_err(|e| format!("Failed to describe mDNS service: {}", e))?;
+        let service = if advertisement.address.is_none() {
+            service.enable_addr_auto()
+        } else {
+            service
+        };
+
+        let fullname = service.get_fullname().to_string();
+        daemon
+            .register(service)
+            .map_err(|e| format!("Failed to advertise {}: {}", fullname, e))?;
+
+        log::info!("Advertising sync server as {}", fullname);
+        Ok(Self { daemon, fullname })
+    }
+
+    pub fn fullname(&self) -> &str {
+        &self.fullname
+    }
+}
+
+impl Drop for Advertiser {
+    fn drop(&mut self) {
+        if let Err(e) = self.daemon.unregister(&self.fullname) {
+            log::warn!("Failed to withdraw {}: {}", self.fullname, e);
+        }
+        if let Err(e) = self.daemon.shutdown() {
+            log::warn!("Failed to stop mDNS daemon: {}", e);
+        }
+    }
+}
+
+#[derive(Debug, Clone, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct DiscoveredServer {
+    pub service_name: String,
+    pub device_name: String,
+    pub host: String,
+    pub addresses: Vec<IpAddr>,
+    pub port: u16,
+    pub url: Option<String>,
+    pub protocol_version: Option<String>,
+    pub cert_fingerprint: Option<String>,
+}
+
+impl DiscoveredServer {
+    fn from_service(service: &ServiceInfo) -> Self {
+        let mut addresses: Vec<IpAddr> = service
+            .get_addresses()
+            .iter()
+            .filter(|address| !is_link_local(address))
+            .copied()
+            .collect();
+        addresses.sort_by_key(|address| (address.is_ipv6(), *address));
+
+        let cert_fingerprint = service
+            .get_property_val_str(TXT_FINGERPRINT)
+            .map(str::to_string);
+        let scheme =
+            service
+                .get_property_val_str(TXT_SCHEME)
+                .unwrap_or(if cert_fingerprint.is_some() {
+                    "https"
+                } else {
+                    "http"
+                });
+        let url = addresses.first().map(|address| {
+            format!(
+                "{}://{}",
+                scheme,
+                std::net::SocketAddr::new(*address, service.get_port())
+            )
+        });
+
+        let device_name = service
+            .get_property_val_str(TXT_NAME)
+            .map(str::to_string)
+            .unwrap_or_else(|| {
+                service
+                    .get_fullname()
+                    .trim_end_matches(SERVICE_TYPE)
+                    .trim_end_matches('.')
+                    .to_string()
+            });
+
+        Self {
+            service_name: service.get_fullname().to_string(),
+            device_name,
+            host: service.get_hostname().to_string(),
+            addresses,
+            port: service.get_port(),
+            url,
+            protocol_version: service
+                .get_property_val_str(TXT_VERSION)
+                .map(str::to_string),
+            cert_fingerprint,
+        }
+    }
+}
+
+pub fn browse(timeout: Duration, exclude: Option<&str>) -> Result<Vec<DiscoveredServer>, String> {
+    let daemon = ServiceDaemon::new().map_err(|e| format!("Failed to start mDNS daemon: {}", e))?;
+    let receiver = daemon
+        .browse(SERVICE_TYPE)
+        .map_err(|e| format!("Failed to browse for sync servers: {}", e))?;
+
+    let deadline = Instant::now() + timeout.min(MAX_BROWSE_TIMEOUT);
+    let mut found: HashMap<String, DiscoveredServer> = HashMap::new();
+
+    while let Ok(event) = receiver.recv_deadline(deadline) {
+        match event {
+            ServiceEvent::ServiceResolved(service) => {
+                if exclude == Some(service.get_fullname()) {
+                    continue;
+                }
+                found.insert(
+                    service.get_fullname().to_string(),
+                    DiscoveredServer::from_service(&service),
+                );
+            }
+            ServiceEvent::ServiceRemoved(_, fullname) => {
+                found.remove(&fullname);
+            }
+            _ => {}
+        }
+    }
+
+    let _ = daemon.stop_browse(SERVICE_TYPE);
+    let _ = daemon.shutdown();
+
+    let mut servers: Vec<DiscoveredServer> = found.into_values().collect();
+    servers.sort_by(|a, b| {
+        a.device_name
+            .cmp(&b.device_name)
+            .then(a.service_name.cmp(&b.service_name))
+    });
+    Ok(servers)
+}
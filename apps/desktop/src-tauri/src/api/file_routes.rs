@@ -1,14 +1,27 @@
-use std::path::PathBuf;
+use std::io::SeekFrom;
+use std::path::{Path, PathBuf};
 use std::sync::Arc;
 
-use warp::http::StatusCode;
+use futures_util::stream::{self, BoxStream, StreamExt, TryStreamExt};
+
+use tokio::io::{AsyncReadExt, AsyncSeekExt};
+use tokio_util::io::ReaderStream;
+
+use warp::http::{header, Response, StatusCode};
+use warp::hyper::body::{Body, Bytes};
 use warp::Filter;
 
-use super::auth::with_auth;
+use super::auth::{with_scoped_caller, Authenticator, Caller};
+use super::checksum;
 use super::db;
+use super::range::{self, ByteRange, RangeRequest};
+use super::tokens::Scope;
 use super::SyncStatus;
 
-fn content_type_for_extension(ext: &str) -> &'static str {
+const STREAM_CHUNK_SIZE: usize = 64 * 1024;
+const PROGRESS_REPORT_BYTES: u64 = 1024 * 1024;
+
+pub(super) fn content_type_for_extension(ext: &str) -> &'static str {
     match ext {
         "mp3" => "audio/mpeg",
         "m4a" | "aac" => "audio/mp4",
@@ -25,114 +38,381 @@ fn content_type_for_extension(ext: &str) -> &'static str {
     }
 }
 
+#[derive(Debug, Default)]
+struct FileConditions {
+    range: Option<String>,
+    if_range: Option<String>,
+    if_none_match: Option<String>,
+}
+
+fn file_conditions() -> impl Filter<Extract = (FileConditions,), Error = warp::Rejection> + Clone {
+    warp::header::optional::<String>("range")
+        .and(warp::header::optional::<String>("if-range"))
+        .and(warp::header::optional::<String>("if-none-match"))
+        .map(|range, if_range, if_none_match| FileConditions {
+            range,
+            if_range,
+            if_none_match,
+        })
+}
+
+fn empty_response(status: StatusCode) -> Response<Body> {
+    Response::builder()
+        .status(status)
+        .header(header::CONTENT_TYPE, "application/json")
+        .body(Body::empty())
+        .unwrap_or_default()
+}
+
+fn file_segment(path: PathBuf, start: u64, len: u64) -> BoxStream<'static, std::io::Result<Bytes>> {
+    stream::once(async move {
+        let mut file = tokio::fs::File::open(&path).await?;
+        file.seek(SeekFrom::Start(start)).await?;
+        Ok::<_, std::io::Error>(ReaderStream::with_capacity(
+            file.take(len),
+            STREAM_CHUNK_SIZE,
+        ))
+    })
+    .try_flatten()
+    .boxed()
+}
+
+fn static_chunk(data: String) -> BoxStream<'static, std::io::Result<Bytes>> {
+    stream::once(async move { Ok(Bytes::from(data)) }).boxed()
+}
+
+fn multipart_body(
+    path: PathBuf,
+    ranges: &[ByteRange],
+    total: u64,
+    content_type: &str,
+    boundary: &str,
+) -> (Body, u64) {
+    let mut parts = Vec::with_capacity(ranges.len() * 3 + 1);
+    let mut content_length = 0u64;
+
+    for range in ranges {
+        let part_header = format!(
+            "\r\n--{}\r\nContent-Type: {}\r\nContent-Range: {}\r\n\r\n",
+            boundary,
+            content_type,
+            range.content_range(total)
+        );
+        content_length += part_header.len() as u64 + range.byte_len();
+        parts.push(static_chunk(part_header));
+        parts.push(file_segment(path.clone(), range.start, range.byte_len()));
+    }
+
+    let closing = format!("\r\n--{}--\r\n", boundary);
+    content_length += closing.len() as u64;
+    parts.push(static_chunk(closing));
+
+    (
+        Body::wrap_stream(stream::iter(parts).flatten()),
+        content_length,
+    )
+}
+
+fn etag_matches(header: &str, etag: &str) -> bool {
+    header.split(',').map(str::trim).any(|candidate| {
+        candidate == "*" || candidate.strip_prefix("W/").unwrap_or(candidate) == etag
+    })
+}
+
+async fn serve_file(
+    file_path: PathBuf,
+    sha256: Option<String>,
+    conditions: FileConditions,
+) -> Response<Body> {
+    let metadata = match tokio::fs::metadata(&file_path).await {
+        Ok(metadata) if metadata.is_file() => metadata,
+        _ => return empty_response(StatusCode::NOT_FOUND),
+    };
+
+    let total = metadata.len();
+    let last_modified = metadata.modified().ok().map(range::http_date);
+    let etag = sha256.as_ref().map(|hash| format!("\"{}\"", hash));
+
+    let ext = file_path.extension().and_then(|e| e.to_str()).unwrap_or("");
+    let content_type = content_type_for_extension(ext);
+
+    let mut builder = Response::builder()
+        .header(header::ACCEPT_RANGES, "bytes")
+        .header(header::CACHE_CONTROL, "private, no-cache");
+    if let Some(modified) = &last_modified {
+        builder = builder.header(header::LAST_MODIFIED, modified);
+    }
+    if let Some(etag) = &etag {
+        builder = builder.header(header::ETAG, etag);
+    }
+    if let Some(hash) = &sha256 {
+        builder = builder.header("X-Content-SHA256", hash);
+        if let Some(digest) = checksum::digest_header_value(hash) {
+            builder = builder.header("Digest", digest);
+        }
+    }
+
+    if let (Some(header), Some(etag)) = (&conditions.if_none_match, &etag) {
+        if etag_matches(header, etag) {
+            return builder
+                .status(StatusCode::NOT_MODIFIED)
+                .body(Body::empty())
+                .unwrap_or_else(|_| empty_response(StatusCode::INTERNAL_SERVER_ERROR));
+        }
+    }
+
+    let range_applies = match conditions.if_range.as_deref().map(str::trim) {
+        None => true,
+        Some(validator) if validator.starts_with('"') => etag.as_deref() == Some(validator),
+        Some(validator) if validator.starts_with("W/") => false,
+        Some(validator) => last_modified.as_deref() == Some(validator),
+    };
+
+    let request = match conditions.range {
+        Some(header) if range_applies => range::parse_range_header(&header, total),
+        _ => RangeRequest::Full,
+    };
+
+    let response = match request {
+        RangeRequest::Full => builder
+            .status(StatusCode::OK)
+            .header(header::CONTENT_TYPE, content_type)
+            .header(header::CONTENT_LENGTH, total)
+            .body(Body::wrap_stream(file_segment(file_path, 0, total))),
+        RangeRequest::Partial(ranges) if ranges.len() == 1 => {
+            let range = ranges[0];
+            builder
+                .status(StatusCode::PARTIAL_CONTENT)
+                .header(header::CONTENT_TYPE, content_type)
+                .header(header::CONTENT_LENGTH, range.byte_len())
+                .header(header::CONTENT_RANGE, range.content_range(total))
+                .body(Body::wrap_stream(file_segment(
+                    file_path,
+                    range.start,
+                    range.byte_len(),
+                )))
+        }
+        RangeRequest::Partial(ranges) => {
+            let boundary = uuid::Uuid::new_v4().simple().to_string();
+            let (body, content_length) =
+                multipart_body(file_path, &ranges, total, content_type, &boundary);
+            builder
+                .status(StatusCode::PARTIAL_CONTENT)
+                .header(
+                    header::CONTENT_TYPE,
+                    format!("multipart/byteranges; boundary={}", boundary),
+                )
+                .header(header::CONTENT_LENGTH, content_length)
+                .body(body)
+        }
+        RangeRequest::Unsatisfiable => builder
+            .status(StatusCode::RANGE_NOT_SATISFIABLE)
+            .header(header::CONTENT_RANGE, format!("bytes */{}", total))
+            .body(Body::empty()),
+    };
+
+    response.unwrap_or_else(|_| empty_response(StatusCode::INTERNAL_SERVER_ERROR))
+}
+
+fn with_checksum<F>(
+    db_path: &Path,
+    path: PathBuf,
+    cached: Option<String>,
+    store: F,
+) -> (PathBuf, Option<String>)
+where
+    F: FnOnce(&rusqlite::Connection, &str) -> rusqlite::Result<()>,
+{
+    if !path.is_file() {
+        return (path, None);
+    }
+    if let Some(sha256) = cached {
+        return (path, Some(sha256));
+    }
+
+    match checksum::sha256_file(&path) {
+        Ok(sha256) => {
+            if let Err(e) = db::open_readwrite(db_path).and_then(|conn| store(&conn, &sha256)) {
+                log::warn!("Failed to cache checksum for {:?}: {}", path, e);
+            }
+            (path, Some(sha256))
+        }
+        Err(e) => {
+            log::warn!("Failed to compute checksum for {:?}: {}", path, e);
+            (path, None)
+        }
+    }
+}
+
+enum FileLookup {
+    Found(PathBuf, Option<String>),
+    Missing,
+    Forbidden,
+}
+
+fn caller_allows(
+    conn: &rusqlite::Connection,
+    caller: &Caller,
+    entity_type: &str,
+    fingerprint: &str,
+) -> Result<bool, String> {
+    match caller.allowed_collections() {
+        None => Ok(true),
+        Some(claims) => db::collection_allows(
+            conn,
+            entity_type,
+            fingerprint,
+            &claims.playlists,
+            &claims.albums,
+        )
+        .map_err(|e| e.to_string()),
+    }
+}
+
+async fn respond_with_lookup(
+    result: Result<FileLookup, String>,
+    conditions: FileConditions,
+) -> Response<Body> {
+    match result {
+        Ok(FileLookup::Found(file_path, sha256)) => serve_file(file_path, sha256, conditions).await,
+        Ok(FileLookup::Missing) => empty_response(StatusCode::NOT_FOUND),
+        Ok(FileLookup::Forbidden) => empty_response(StatusCode::FORBIDDEN),
+        Err(_) => empty_response(StatusCode::INTERNAL_SERVER_ERROR),
+    }
+}
+
+struct SentBytes {
+    sync_status: SyncStatus,
+    pending: u64,
+}
+
+impl SentBytes {
+    fn add(&mut self, bytes: u64) {
+        self.pending += bytes;
+        if self.pending >= PROGRESS_REPORT_BYTES {
+            self.flush();
+        }
+    }
+
+    fn flush(&mut self) {
+        self.sync_status
+            .record_bytes(std::mem::take(&mut self.pending));
+    }
+}
+
+impl Drop for SentBytes {
+    fn drop(&mut self) {
+        self.flush();
+    }
+}
+
+fn count_sent(response: Response<Body>, sync_status: SyncStatus) -> Response<Body> {
+    if !response.status().is_success() {
+        return response;
+    }
+
+    let (parts, body) = response.into_parts();
+    let mut sent = SentBytes {
+        sync_status,
+        pending: 0,
+    };
+    let body = body.map_ok(move |chunk| {
+        sent.add(chunk.len() as u64);
+        chunk
+    });
+    Response::from_parts(parts, Body::wrap_stream(body))
+}
+
 async fn handle_audio(
     fingerprint: String,
+    caller: Caller,
+    conditions: FileConditions,
     db_path: Arc<PathBuf>,
     app_data_dir: Arc<PathBuf>,
     sync_status: SyncStatus,
-) -> Result<impl warp::Reply, warp::Rejection> {
-    sync_status.lock().unwrap().last_activity = std::time::Instant::now();
+) -> Result<Response<Body>, warp::Rejection> {
+    sync_status.touch();
     let songs_dir = app_data_dir.join("songs");
 
     let result = tokio::task::spawn_blocking(move || {
         let conn = db::open_readonly(&db_path).map_err(|e| e.to_string())?;
-        db::get_song_file_path(&conn, &fingerprint, &songs_dir).map_err(|e| e.to_string())
+        if !caller_allows(&conn, &caller, "song", &fingerprint)? {
+            return Ok(FileLookup::Forbidden);
+        }
+        let stored = db::get_song_file(&conn, &fingerprint).map_err(|e| e.to_string())?;
+        drop(conn);
+        Ok::<_, String>(match stored {
+            Some(file) => {
+                let (path, sha256) = with_checksum(
+                    &db_path,
+                    songs_dir.join(&file.name),
+                    file.sha256,
+                    |conn, sha256| {
+                        db::store_song_file_sha256(conn, &fingerprint, &file.name, sha256)
+                    },
+                );
+                FileLookup::Found(path, sha256)
+            }
+            None => FileLookup::Missing,
+        })
     })
     .await
     .map_err(|_| warp::reject::reject())?;
 
-    match result {
-        Ok(Some(file_path)) if file_path.exists() => {
-            let data = tokio::fs::read(&file_path)
-                .await
-                .map_err(|_| warp::reject::reject())?;
-
-            let ext = file_path
-                .extension()
-                .and_then(|e| e.to_str())
-                .unwrap_or("");
-            let content_type = content_type_for_extension(ext);
-
-            Ok(warp::reply::with_status(
-                warp::reply::with_header(data, "Content-Type", content_type),
-                StatusCode::OK,
-            ))
-        }
-        Ok(_) => Ok(warp::reply::with_status(
-            warp::reply::with_header(
-                Vec::new(),
-                "Content-Type",
-                "application/json",
-            ),
-            StatusCode::NOT_FOUND,
-        )),
-        Err(_) => Ok(warp::reply::with_status(
-            warp::reply::with_header(
-                Vec::new(),
-                "Content-Type",
-                "application/json",
-            ),
-            StatusCode::INTERNAL_SERVER_ERROR,
-        )),
-    }
+    let response = respond_with_lookup(result, conditions).await;
+    Ok(count_sent(response, sync_status))
 }
 
 async fn handle_thumbnail(
     fingerprint: String,
     entity_type: String,
+    caller: Caller,
+    conditions: FileConditions,
     db_path: Arc<PathBuf>,
     app_data_dir: Arc<PathBuf>,
     sync_status: SyncStatus,
-) -> Result<impl warp::Reply, warp::Rejection> {
-    sync_status.lock().unwrap().last_activity = std::time::Instant::now();
+) -> Result<Response<Body>, warp::Rejection> {
+    sync_status.touch();
     let thumbnails_dir = app_data_dir.join("thumbnails");
 
     let result = tokio::task::spawn_blocking(move || {
         let conn = db::open_readonly(&db_path).map_err(|e| e.to_string())?;
-        db::get_thumbnail_path(&conn, &fingerprint, &entity_type, &thumbnails_dir)
-            .map_err(|e| e.to_string())
+        if !caller_allows(&conn, &caller, &entity_type, &fingerprint)? {
+            return Ok(FileLookup::Forbidden);
+        }
+        let stored =
+            db::get_thumbnail_file(&conn, &fingerprint, &entity_type).map_err(|e| e.to_string())?;
+        drop(conn);
+        Ok::<_, String>(match stored {
+            Some(file) => {
+                let (path, sha256) = with_checksum(
+                    &db_path,
+                    thumbnails_dir.join(&file.name),
+                    file.sha256,
+                    |conn, sha256| {
+                        db::store_thumbnail_sha256(
+                            conn,
+                            &fingerprint,
+                            &entity_type,
+                            &file.name,
+                            sha256,
+                        )
+                    },
+                );
+                FileLookup::Found(path, sha256)
+            }
+            None => FileLookup::Missing,
+        })
     })
     .await
     .map_err(|_| warp::reject::reject())?;
 
-    match result {
-        Ok(Some(file_path)) if file_path.exists() => {
-            let data = tokio::fs::read(&file_path)
-                .await
-                .map_err(|_| warp::reject::reject())?;
-
-            let ext = file_path
-                .extension()
-                .and_then(|e| e.to_str())
-                .unwrap_or("");
-            let content_type = content_type_for_extension(ext);
-
-            Ok(warp::reply::with_status(
-                warp::reply::with_header(data, "Content-Type", content_type),
-                StatusCode::OK,
-            ))
-        }
-        Ok(_) => Ok(warp::reply::with_status(
-            warp::reply::with_header(
-                Vec::new(),
-                "Content-Type",
-                "application/json",
-            ),
-            StatusCode::NOT_FOUND,
-        )),
-        Err(_) => Ok(warp::reply::with_status(
-            warp::reply::with_header(
-                Vec::new(),
-                "Content-Type",
-                "application/json",
-            ),
-            StatusCode::INTERNAL_SERVER_ERROR,
-        )),
-    }
+    let response = respond_with_lookup(result, conditions).await;
+    Ok(count_sent(response, sync_status))
 }
 
 pub fn file_routes(
-    token: Arc<String>,
+    auth: Arc<Authenticator>,
     db_path: Arc<PathBuf>,
     app_data_dir: Arc<PathBuf>,
     sync_status: SyncStatus,
@@ -146,7 +426,8 @@ pub fn file_routes(
 
     let audio = warp::path!("api" / "files" / "audio" / String)
         .and(warp::get())
-        .and(with_auth(token.clone()))
+        .and(with_scoped_caller(auth.clone(), Scope::FilesAudio))
+        .and(file_conditions())
         .and(warp::any().map(move || db_audio.clone()))
         .and(warp::any().map(move || app_audio.clone()))
         .and(warp::any().map(move || status_audio.clone()))
@@ -154,7 +435,8 @@ pub fn file_routes(
 
     let thumbnail = warp::path!("api" / "files" / "thumbnail" / String / String)
         .and(warp::get())
-        .and(with_auth(token))
+        .and(with_scoped_caller(auth, Scope::FilesThumbnail))
+        .and(file_conditions())
         .and(warp::any().map(move || db_thumb.clone()))
         .and(warp::any().map(move || app_thumb.clone()))
         .and(warp::any().map(move || status_thumb.clone()))
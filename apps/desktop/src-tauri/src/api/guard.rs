@@ -0,0 +1,376 @@
+use std::collections::HashMap;
+use std::net::{IpAddr, SocketAddr};
+use std::sync::{Arc, Mutex};
+use std::time::{Duration, Instant};
+
+use serde::{Deserialize, Serialize};
+
+use warp::{reject, Filter, Rejection};
+
+const RATE_WINDOW: Duration = Duration::from_secs(60);
+const FAILURES_BEFORE_LOCKOUT: u32 = 5;
+const BASE_LOCKOUT: Duration = Duration::from_secs(30);
+const MAX_LOCKOUT: Duration = Duration::from_secs(60 * 60);
+const FAILURE_MEMORY: Duration = Duration::from_secs(15 * 60);
+const MAX_TRACKED_CLIENTS: usize = 1024;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase", default)]
+pub struct RateLimits {
+    pub requests_per_minute: u32,
+    pub ping_per_minute: u32,
+    pub connection_per_minute: u32,
+}
+
+impl Default for RateLimits {
+    fn default() -> Self {
+        Self {
+            requests_per_minute: 6000,
+            ping_per_minute: 120,
+            connection_per_minute: 30,
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum Bucket {
+    Api,
+    Ping,
+    Connection,
+}
+
+impl Bucket {
+    fn for_path(path: &str) -> Self {
+        match path.trim_end_matches('/') {
+            "/ping" => Bucket::Ping,
+            "/connection" => Bucket::Connection,
+            _ => Bucket::Api,
+        }
+    }
+
+    fn index(self) -> usize {
+        match self {
+            Bucket::Api => 0,
+            Bucket::Ping => 1,
+            Bucket::Connection => 2,
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy)]
+struct Window {
+    started: Instant,
+    count: u32,
+}
+
+#[derive(Debug)]
+struct ClientState {
+    windows: [Window; 3],
+    failures: u32,
+    last_failure: Option<Instant>,
+    locked_until: Option<Instant>,
+    last_seen: Instant,
+}
+
+impl ClientState {
+    fn new(now: Instant) -> Self {
+        Self {
+            windows: [Window {
+                started: now,
+                count: 0,
+            }; 3],
+            failures: 0,
+            last_failure: None,
+            locked_until: None,
+            last_seen: now,
+        }
+    }
+
+    fn is_idle(&self, now: Instant) -> bool {
+        let locked = self.locked_until.is_some_and(|until| until > now);
+        !locked && now.duration_since(self.last_seen) > FAILURE_MEMORY
+    }
+}
+
+#[derive(Debug, Clone, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct BlockedClient {
+    pub ip: IpAddr,
+    pub failures: u32,
+    pub locked_for_secs: u64,
+    pub last_failure_secs_ago: Option<u64>,
+}
+
+#[derive(Debug)]
+pub struct Throttled {
+    pub retry_after_secs: u64,
+    pub locked: bool,
+}
+
+impl reject::Reject for Throttled {}
+
+fn lockout_duration(failures: u32) -> Duration {
+    let exponent = failures.saturating_sub(FAILURES_BEFORE_LOCKOUT).min(16);
+    BASE_LOCKOUT.saturating_mul(1 << exponent).min(MAX_LOCKOUT)
+}
+
+fn ceil_secs(duration: Duration) -> u64 {
+    duration.as_secs() + u64::from(duration.subsec_nanos() > 0)
+}
+
+pub struct AuthGuard {
+    limits: RateLimits,
+    clients: Mutex<HashMap<IpAddr, ClientState>>,
+}
+
+impl AuthGuard {
+    pub fn new(limits: RateLimits) -> Self {
+        Self {
+            limits,
+            clients: Mutex::new(HashMap::new()),
+        }
+    }
+
+    fn quota(&self, bucket: Bucket) -> u32 {
+        match bucket {
+            Bucket::Api => self.limits.requests_per_minute,
+            Bucket::Ping => self.limits.ping_per_minute,
+            Bucket::Connection => self.limits.connection_per_minute,
+        }
+    }
+
+    fn prune(clients: &mut HashMap<IpAddr, ClientState>, now: Instant) {
+        if clients.len() > MAX_TRACKED_CLIENTS {
+            clients.retain(|_, state| !state.is_idle(now));
+        }
+    }
+
+    fn admit(&self, ip: IpAddr, bucket: Bucket) -> Result<(), Throttled> {
+        let now = Instant::now();
+        let mut clients = self.clients.lock().unwrap();
+        Self::prune(&mut clients, now);
+
+        let state = clients.entry(ip).or_insert_with(|| ClientState::new(now));
+        state.last_seen = now;
+
+        if let Some(until) = state.locked_until {
+            if until > now {
+                return Err(Throttled {
+                    retry_after_secs: ceil_secs(until - now),
+                    locked: true,
+                });
+            }
+        }
+
+        let quota = self.quota(bucket);
+        if quota == 0 {
+            return Ok(());
+        }
+
+        let window = &mut state.windows[bucket.index()];
+        if now.duration_since(window.started) >= RATE_WINDOW {
+            *window = Window {
+                started: now,
+                count: 0,
+            };
+        }
+        if window.count >= quota {
+            let reset = RATE_WINDOW.saturating_sub(now.duration_since(window.started));
+            return Err(Throttled {
+                retry_after_secs: ceil_secs(reset).max(1),
+                locked: false,
+            });
+        }
+        window.count += 1;
+        Ok(())
+    }
+
+    pub fn record_failure(&self, ip: IpAddr) {
+        let now = Instant::now();
+        let mut clients = self.clients.lock().unwrap();
+        let state = clients.entry(ip).or_insert_with(|| ClientState::new(now));
+
+        if state
+            .last_failure
+            .is_some_and(|last| now.duration_since(last) > FAILURE_MEMORY)
+        {
+            state.failures = 0;
+        }
+        state.failures += 1;
+        state.last_failure = Some(now);
+        state.last_seen = now;
+
+        if state.failures >= FAILURES_BEFORE_LOCKOUT {
+            let duration = lockout_duration(state.failures);
+            state.locked_until = Some(now + duration);
+            log::warn!(
+                "Locking out {} for {}s after {} failed authentication attempts",
+                ip,
+                duration.as_secs(),
+                state.failures
+            );
+        }
+    }
+
+    pub fn record_success(&self, ip: IpAddr) {
+        let mut clients = self.clients.lock().unwrap();
+        if let Some(state) = clients.get_mut(&ip) {
+            state.failures = 0;
+            state.last_failure = None;
+            state.locked_until = None;
+        }
+    }
+
+    pub fn blocked_clients(&self) -> Vec<BlockedClient> {
+        let now = Instant::now();
+        let clients = self.clients.lock().unwrap();
+
+        let mut blocked: Vec<BlockedClient> = clients
+            .iter()
+            .filter(|(_, state)| state.failures > 0)
+            .map(|(ip, state)| BlockedClient {
+                ip: *ip,
+                failures: state.failures,
+                locked_for_secs: state
+                    .locked_until
+                    .map(|until| ceil_secs(until.saturating_duration_since(now)))
+                    .unwrap_or(0),
+                last_failure_secs_ago: state
+                    .last_failure
+                    .map(|last| now.duration_since(last).as_secs()),
+            })
+            .collect();
+        blocked.sort_by(|a, b| {
+            b.locked_for_secs
+                .cmp(&a.locked_for_secs)
+                .then(b.failures.cmp(&a.failures))
+        });
+        blocked
+    }
+}
+
+pub fn with_guard(guard: Arc<AuthGuard>) -> impl Filter<Extract = (), Error = Rejection> + Clone {
+    warp::addr::remote()
+        .and(warp::path::full())
+        .and_then(
+            move |remote: Option<SocketAddr>, path: warp::path::FullPath| {
+                let guard = guard.clone();
+                async move {
+                    let Some(remote) = remote else {
+                        return Ok(());
+                    };
+                    guard
+                        .admit(remote.ip(), Bucket::for_path(path.as_str()))
+                        .map_err(reject::custom)
+                }
+            },
+        )
+        .untuple_one()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    use std::net::Ipv4Addr;
+
+    const CLIENT: IpAddr = IpAddr::V4(Ipv4Addr::new(192, 168, 1, 20));
+
+    fn limits(requests_per_minute: u32) -> RateLimits {
+        RateLimits {
+            requests_per_minute,
+            ..RateLimits::default()
+        }
+    }
+
+    #[test]
+    fn lockout_starts_at_the_base_duration() {
+        assert_eq!(lockout_duration(FAILURES_BEFORE_LOCKOUT), BASE_LOCKOUT);
+    }
+
+    #[test]
+    fn lockout_doubles_with_each_further_failure() {
+        for extra in 1..=5 {
+            assert_eq!(
+                lockout_duration(FAILURES_BEFORE_LOCKOUT + extra),
+                lockout_duration(FAILURES_BEFORE_LOCKOUT + extra - 1) * 2
+            );
+        }
+        assert_eq!(
+            lockout_duration(FAILURES_BEFORE_LOCKOUT + 3),
+            Duration::from_secs(240)
+        );
+    }
+
+    #[test]
+    fn lockout_is_capped() {
+        assert_eq!(lockout_duration(FAILURES_BEFORE_LOCKOUT + 7), MAX_LOCKOUT);
+        assert_eq!(lockout_duration(FAILURES_BEFORE_LOCKOUT + 40), MAX_LOCKOUT);
+        assert_eq!(lockout_duration(u32::MAX), MAX_LOCKOUT);
+    }
+
+    #[test]
+    fn locks_out_after_repeated_failures() {
+        let guard = AuthGuard::new(limits(0));
+
+        for _ in 1..FAILURES_BEFORE_LOCKOUT {
+            guard.record_failure(CLIENT);
+        }
+        assert!(guard.admit(CLIENT, Bucket::Api).is_ok());
+
+        guard.record_failure(CLIENT);
+        let throttled = guard.admit(CLIENT, Bucket::Api).unwrap_err();
+        assert!(throttled.locked);
+        assert_eq!(throttled.retry_after_secs, BASE_LOCKOUT.as_secs());
+
+        let blocked = guard.blocked_clients();
+        assert_eq!(blocked.len(), 1);
+        assert_eq!(blocked[0].failures, FAILURES_BEFORE_LOCKOUT);
+        assert_eq!(blocked[0].locked_for_secs, BASE_LOCKOUT.as_secs());
+    }
+
+    #[test]
+    fn extends_the_lockout_on_further_failures() {
+        let guard = AuthGuard::new(limits(0));
+
+        for _ in 0..FAILURES_BEFORE_LOCKOUT + 2 {
+            guard.record_failure(CLIENT);
+        }
+        let throttled = guard.admit(CLIENT, Bucket::Ping).unwrap_err();
+        assert!(throttled.locked);
+        assert_eq!(throttled.retry_after_secs, BASE_LOCKOUT.as_secs() * 4);
+    }
+
+    #[test]
+    fn success_clears_the_lockout() {
+        let guard = AuthGuard::new(limits(0));
+
+        for _ in 0..FAILURES_BEFORE_LOCKOUT {
+            guard.record_failure(CLIENT);
+        }
+        guard.record_success(CLIENT);
+
+        assert!(guard.admit(CLIENT, Bucket::Api).is_ok());
+        assert!(guard.blocked_clients().is_empty());
+    }
+
+    #[test]
+    fn throttles_requests_over_the_bucket_quota() {
+        let guard = AuthGuard::new(limits(2));
+
+        assert!(guard.admit(CLIENT, Bucket::Api).is_ok());
+        assert!(guard.admit(CLIENT, Bucket::Api).is_ok());
+        let throttled = guard.admit(CLIENT, Bucket::Api).unwrap_err();
+        assert!(!throttled.locked);
+        assert!(throttled.retry_after_secs >= 1 && throttled.retry_after_secs <= 60);
+
+        assert!(guard.admit(CLIENT, Bucket::Ping).is_ok());
+    }
+
+    #[test]
+    fn routes_paths_to_buckets() {
+        assert_eq!(Bucket::for_path("/ping"), Bucket::Ping);
+        assert_eq!(Bucket::for_path("/connection/"), Bucket::Connection);
+        assert_eq!(Bucket::for_path("/api/sync/batch"), Bucket::Api);
+    }
+}
@@ -0,0 +1,132 @@
+use std::path::PathBuf;
+use std::sync::Arc;
+
+use serde::{Deserialize, Serialize};
+
+use warp::http::StatusCode;
+use warp::Filter;
+
+use super::auth::{with_scope, Authenticator};
+use super::compression;
+use super::db;
+use super::tokens::Scope;
+use super::SyncStatus;
+
+const DEFAULT_HISTORY_PAGE_SIZE: u32 = 1000;
+const MAX_HISTORY_PAGE_SIZE: u32 = 10_000;
+
+#[derive(Debug, Deserialize)]
+#[serde(rename_all = "camelCase")]
+struct HistoryRequest {
+    after_id: Option<i64>,
+    limit: Option<u32>,
+}
+
+#[derive(Debug, Serialize)]
+#[serde(rename_all = "camelCase")]
+struct HistoryResponse {
+    events: Vec<db::PlayEvent>,
+    next_after_id: Option<i64>,
+}
+
+#[derive(Debug, Deserialize)]
+#[serde(rename_all = "camelCase")]
+struct HistoryPushRequest {
+    events: Vec<db::PlayEvent>,
+}
+
+async fn handle_history(
+    body: HistoryRequest,
+    db_path: Arc<PathBuf>,
+    sync_status: SyncStatus,
+) -> Result<impl warp::Reply, warp::Rejection> {
+    sync_status.touch();
+    let result = tokio::task::spawn_blocking(move || {
+        let conn = db::open_readonly(&db_path).map_err(|e| e.to_string())?;
+
+        let limit = body
+            .limit
+            .unwrap_or(DEFAULT_HISTORY_PAGE_SIZE)
+            .clamp(1, MAX_HISTORY_PAGE_SIZE);
+        let rows = db::get_play_history_after(&conn, body.after_id.unwrap_or(0), limit)
+            .map_err(|e| e.to_string())?;
+
+        let next_after_id = if rows.len() as u32 == limit {
+            rows.last().map(|(id, _)| *id)
+        } else {
+            None
+        };
+
+        Ok::<HistoryResponse, String>(HistoryResponse {
+            events: rows.into_iter().map(|(_, event)| event).collect(),
+            next_after_id,
+        })
+    })
+    .await
+    .map_err(|_| warp::reject::reject())?;
+
+    match result {
+        Ok(response) => Ok(warp::reply::with_status(
+            warp::reply::json(&response),
+            StatusCode::OK,
+        )),
+        Err(err) => Ok(warp::reply::with_status(
+            warp::reply::json(&serde_json::json!({ "error": err })),
+            StatusCode::INTERNAL_SERVER_ERROR,
+        )),
+    }
+}
+
+async fn handle_history_push(
+    body: HistoryPushRequest,
+    db_path: Arc<PathBuf>,
+    sync_status: SyncStatus,
+) -> Result<impl warp::Reply, warp::Rejection> {
+    sync_status.touch();
+    let result = tokio::task::spawn_blocking(move || {
+        let mut conn = db::open_readwrite(&db_path).map_err(|e| e.to_string())?;
+        db::merge_play_history(&mut conn, &body.events).map_err(|e| e.to_string())
+    })
+    .await
+    .map_err(|_| warp::reject::reject())?;
+
+    match result {
+        Ok(response) => Ok(warp::reply::with_status(
+            warp::reply::json(&response),
+            StatusCode::OK,
+        )),
+        Err(err) => Ok(warp::reply::with_status(
+            warp::reply::json(&serde_json::json!({ "error": err })),
+            StatusCode::INTERNAL_SERVER_ERROR,
+        )),
+    }
+}
+
+pub fn history_routes(
+    auth: Arc<Authenticator>,
+    db_path: Arc<PathBuf>,
+    sync_status: SyncStatus,
+) -> impl Filter<Extract = (impl warp::Reply,), Error = warp::Rejection> + Clone {
+    let db_path_history = db_path.clone();
+    let db_path_push = db_path;
+    let status_history = sync_status.clone();
+    let status_push = sync_status;
+
+    let history = warp::path!("api" / "sync" / "history")
+        .and(warp::post())
+        .and(with_scope(auth.clone(), Scope::SyncRead))
+        .and(compression::json_body())
+        .and(warp::any().map(move || db_path_history.clone()))
+        .and(warp::any().map(move || status_history.clone()))
+        .and_then(handle_history);
+
+    let push = warp::path!("api" / "sync" / "history" / "push")
+        .and(warp::post())
+        .and(with_scope(auth, Scope::SyncWrite))
+        .and(compression::json_body())
+        .and(warp::any().map(move || db_path_push.clone()))
+        .and(warp::any().map(move || status_push.clone()))
+        .and_then(handle_history_push);
+
+    history.or(push)
+}
@@ -3,45 +3,68 @@ use warp::Filter;
 use serde::{Deserialize, Serialize};
 use serde_json::json;
 
-use local_ip_address::local_ip;
-
-use std::net::{SocketAddr, TcpListener};
+use std::net::{IpAddr, SocketAddr, TcpListener};
 use std::path::PathBuf;
 use std::sync::atomic::{AtomicBool, Ordering};
 use std::sync::Arc;
 use std::sync::LazyLock;
-use std::time::{Duration, Instant};
 
 use tokio::sync::{oneshot, Mutex};
 
-pub struct SyncStatusData {
-    pub status: String,
-    pub last_activity: Instant,
-}
+pub type SyncStatus = Arc<sync_state::SyncState>;
+
+pub const PROTOCOL_VERSION: &str = "2.0";
+pub const DEFAULT_DEVICE_NAME: &str = "Tunno Desktop";
 
-pub type SyncStatus = Arc<std::sync::Mutex<SyncStatusData>>;
+pub const SERVER_STATE_EVENT: &str = "sync-server-state";
+pub const SHOW_QR_EVENT: &str = "sync-show-qr";
+
+pub type EventSink = Arc<dyn Fn(&str, serde_json::Value) + Send + Sync>;
 
 pub mod auth;
+pub mod checksum;
 pub mod commands;
+pub mod compression;
+pub mod config;
 pub mod db;
+pub mod discovery;
 pub mod file_routes;
+pub mod guard;
+pub mod history_routes;
+pub mod network;
+pub mod pairing;
+pub mod pairing_routes;
+pub mod playlist_routes;
+pub mod push_routes;
+pub mod range;
 pub mod sync_routes;
+pub mod sync_state;
+pub mod tls;
+pub mod tokens;
 
 pub struct ApiServer {
     shutdown_tx: Option<oneshot::Sender<()>>,
     server_info: Option<ServerInfo>,
     token: Option<String>,
     sync_status: SyncStatus,
+    pairings: Option<Arc<pairing::PairingRegistry>>,
+    guard: Option<Arc<guard::AuthGuard>>,
+    advertiser: Option<discovery::Advertiser>,
     timeout_running: Option<Arc<AtomicBool>>,
 }
 
 #[derive(Clone, Debug, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
 pub struct ServerInfo {
-    pub ip: std::net::IpAddr,
+    pub ip: IpAddr,
+    pub bind_address: IpAddr,
     pub port: u16,
+    pub requested_port: u16,
     pub url: String,
     pub endpoints: Vec<String>,
     pub token: Option<String>,
+    pub cert_fingerprint: Option<String>,
+    pub service_name: Option<String>,
 }
 
 impl ApiServer {
@@ -50,78 +73,127 @@ impl ApiServer {
             shutdown_tx: None,
             server_info: None,
             token: None,
-            sync_status: Arc::new(std::sync::Mutex::new(SyncStatusData {
-                status: "waiting".to_string(),
-                last_activity: Instant::now(),
-            })),
+            sync_status: Arc::new(sync_state::SyncState::new()),
+            pairings: None,
+            guard: None,
+            advertiser: None,
             timeout_running: None,
         }
     }
 
-    fn find_available_port(start_port: u16) -> Result<u16, String> {
-        for port in start_port..start_port + 100 {
-            if TcpListener::bind(SocketAddr::from(([0, 0, 0, 0], port))).is_ok() {
+    fn find_available_port(
+        bind: IpAddr,
+        server_config: &config::ServerConfig,
+    ) -> Result<u16, String> {
+        let ports = server_config.candidate_ports();
+        for port in ports.clone() {
+            if TcpListener::bind(SocketAddr::new(bind, port)).is_ok() {
                 return Ok(port);
             }
         }
-        Err(format!(
-            "All ports {}-{} are in use",
-            start_port,
-            start_port + 99
-        ))
+
+        match server_config.port_mode {
+            config::PortMode::Fixed => Err(format!(
+                "Port {} is already in use on {}. Free it or choose another port in the server settings",
+                server_config.port, bind
+            )),
+            config::PortMode::Preferred => Err(format!(
+                "All ports {}-{} are in use on {}",
+                ports.start(),
+                ports.end(),
+                bind
+            )),
+        }
     }
 
     pub async fn start(
         &mut self,
         app_data_dir: PathBuf,
+        events: EventSink,
+        server_config: config::ServerConfig,
     ) -> Result<ServerInfo, String> {
-        let port = Self::find_available_port(3030)?;
+        server_config.validate()?;
 
-        let local_ip = local_ip().map_err(|e| {
-            format!("Failed to detect local network IP: {}", e)
-        })?;
+        let addresses = network::resolve_addresses(&server_config.network)?;
+        let allow_list = Arc::new(network::AllowList::parse(
+            &server_config.network.allowed_networks,
+        )?);
+
+        let port = Self::find_available_port(addresses.bind, &server_config)?;
 
         let db_path = db::resolve_db_path(&app_data_dir);
         if !db_path.exists() {
             return Err(format!("Database not found at {:?}", db_path));
         }
 
+        let mut conn =
+            db::open_readwrite(&db_path).map_err(|e| format!("Failed to open database: {}", e))?;
+        if let Err(e) = db::compact_tombstones(&mut conn, db::TOMBSTONE_RETENTION_SECS) {
+            log::warn!("Failed to compact sync tombstones: {}", e);
+        }
+        drop(conn);
+
+        let tls_identity = if !server_config.plain_http {
+            Some(tls::load_or_create_identity(&app_data_dir)?)
+        } else {
+            None
+        };
+
         let token = auth::generate_token();
-        let server_url = format!("http://{}:{}", local_ip, port);
+        let scheme = if tls_identity.is_some() {
+            "https"
+        } else {
+            "http"
+        };
+        let server_url = format!(
+            "{}://{}",
+            scheme,
+            SocketAddr::new(addresses.advertised, port)
+        );
 
         let endpoints = vec![
             "/ping".to_string(),
             "/connection".to_string(),
+            "/api/pair".to_string(),
+            "/api/pair/:pairingId/claim".to_string(),
             "/api/sync/compare".to_string(),
+            "/api/sync/delta".to_string(),
+            "/api/sync/tombstones".to_string(),
             "/api/sync/batch".to_string(),
+            "/api/sync/update".to_string(),
+            "/api/sync/complete".to_string(),
+            "/api/sync/abort".to_string(),
+            "/api/sync/history".to_string(),
+            "/api/sync/history/push".to_string(),
+            "/api/sync/push/:kind".to_string(),
             "/api/files/audio/:fingerprint".to_string(),
             "/api/files/thumbnail/:fingerprint/:type".to_string(),
+            "/api/playlists/:fingerprint".to_string(),
         ];
 
-        let server_info = ServerInfo {
-            ip: local_ip,
+        let mut server_info = ServerInfo {
+            ip: addresses.advertised,
+            bind_address: addresses.bind,
             port,
+            requested_port: server_config.port,
             url: server_url.clone(),
             endpoints: endpoints.clone(),
             token: Some(token.clone()),
+            cert_fingerprint: tls_identity
+                .as_ref()
+                .map(|identity| identity.fingerprint.clone()),
+            service_name: None,
         };
 
         let info_for_routes = server_info.clone();
 
         let sync_status = self.sync_status.clone();
-        {
-            let mut data = sync_status.lock().unwrap();
-            data.status = "waiting".to_string();
-            data.last_activity = Instant::now();
-        }
+        sync_status.attach(events.clone());
+        sync_status.reset();
 
         let ping_status = sync_status.clone();
         let ping = warp::path("ping").and(warp::get()).map(move || {
-            let mut data = ping_status.lock().unwrap();
-            if data.status == "waiting" {
-                data.status = "connected".to_string();
-            }
-            data.last_activity = Instant::now();
+            ping_status.mark_connected();
             warp::reply::json(&json!({
                 "message": "pong",
                 "status": "ok",
@@ -134,65 +206,149 @@ impl ApiServer {
                 "ip": info_for_routes.ip,
                 "port": info_for_routes.port,
                 "url": info_for_routes.url,
-                "endpoints": info_for_routes.endpoints
+                "endpoints": info_for_routes.endpoints,
+                "certFingerprint": info_for_routes.cert_fingerprint,
+                "maxBatchSize": sync_routes::MAX_BATCH_SIZE
             }))
         });
 
-        let token_arc = Arc::new(token.clone());
         let db_path_arc = Arc::new(db_path);
         let app_data_arc = Arc::new(app_data_dir);
-
-        let sync = sync_routes::sync_routes(token_arc.clone(), db_path_arc.clone(), sync_status.clone());
-        let files = file_routes::file_routes(token_arc, db_path_arc, app_data_arc, sync_status.clone());
+        let guard = Arc::new(guard::AuthGuard::new(server_config.rate_limits));
+        let auth_arc = Arc::new(auth::Authenticator::new(
+            token.clone(),
+            db_path_arc.clone(),
+            sync_status.clone(),
+            guard.clone(),
+        ));
+
+        let pairings = Arc::new(pairing::PairingRegistry::new());
+
+        let pairing = pairing_routes::pairing_routes(
+            auth_arc.clone(),
+            pairings.clone(),
+            events,
+            sync_status.clone(),
+        );
+        let sync =
+            sync_routes::sync_routes(auth_arc.clone(), db_path_arc.clone(), sync_status.clone());
+        let files = file_routes::file_routes(
+            auth_arc.clone(),
+            db_path_arc.clone(),
+            app_data_arc.clone(),
+            sync_status.clone(),
+        );
+        let history = history_routes::history_routes(
+            auth_arc.clone(),
+            db_path_arc.clone(),
+            sync_status.clone(),
+        );
+        let playlists = playlist_routes::playlist_routes(auth_arc.clone(), db_path_arc.clone());
+        let push = push_routes::push_routes(
+            auth_arc,
+            db_path_arc,
+            app_data_arc,
+            sync_status.clone(),
+            server_config.max_upload_bytes(),
+        );
 
         let cors = warp::cors()
             .allow_any_origin()
-            .allow_headers(vec!["content-type", "authorization"])
+            .allow_headers(vec!["content-type", "content-encoding", "authorization"])
             .allow_methods(vec!["GET", "POST"]);
 
-        let routes = ping
-            .or(connection_info)
-            .or(sync)
-            .or(files)
-            .recover(auth::handle_rejection)
-            .with(cors)
-            .with(warp::log("api"));
+        let routes = compression::negotiated(
+            network::with_allow_list(allow_list)
+                .and(guard::with_guard(guard.clone()))
+                .and(
+                    ping.or(connection_info)
+                        .or(pairing)
+                        .or(sync)
+                        .or(files)
+                        .or(history)
+                        .or(playlists)
+                        .or(push),
+                )
+                .recover(auth::handle_rejection),
+        )
+        .with(cors)
+        .with(warp::log("api"));
 
         let (shutdown_tx, shutdown_rx) = oneshot::channel();
 
-        let server_addr: SocketAddr = ([0, 0, 0, 0], port).into();
+        let server_addr = SocketAddr::new(addresses.bind, port);
 
-        let (_addr, server) = warp::serve(routes)
-            .try_bind_with_graceful_shutdown(server_addr, async {
-                shutdown_rx.await.ok();
-            })
-            .map_err(|e| format!("Port {} already in use: {}", port, e))?;
+        let shutdown = async {
+            shutdown_rx.await.ok();
+        };
 
-        tokio::spawn(server);
+        match &tls_identity {
+            Some(identity) => {
+                let (_addr, server) = warp::serve(routes)
+                    .tls()
+                    .cert(&identity.cert_pem)
+                    .key(&identity.key_pem)
+                    .try_bind_with_graceful_shutdown(server_addr, shutdown)
+                    .map_err(|e| format!("Failed to start HTTPS on port {}: {}", port, e))?;
+                tokio::spawn(server);
+            }
+            None => {
+                let (_addr, server) = warp::serve(routes)
+                    .try_bind_with_graceful_shutdown(server_addr, shutdown)
+                    .map_err(|e| format!("Port {} already in use: {}", port, e))?;
+                tokio::spawn(server);
+            }
+        }
+
+        let advertiser = if server_config.advertise {
+            let explicit_address = server_config.network.advertised_address.is_some()
+                || !addresses.bind.is_unspecified();
+            let advertisement = discovery::ServiceAdvertisement {
+                device_name: server_config
+                    .device_name
+                    .clone()
+                    .unwrap_or_else(|| DEFAULT_DEVICE_NAME.to_string()),
+                address: explicit_address.then_some(addresses.advertised),
+                port,
+                cert_fingerprint: server_info.cert_fingerprint.clone(),
+            };
+            match discovery::Advertiser::register(advertisement) {
+                Ok(advertiser) => Some(advertiser),
+                Err(e) => {
+                    log::warn!("{}", e);
+                    None
+                }
+            }
+        } else {
+            None
+        };
+        server_info.service_name = advertiser
+            .as_ref()
+            .map(|advertiser| advertiser.fullname().to_string());
 
         let timeout_running = Arc::new(AtomicBool::new(true));
         let timeout_flag = timeout_running.clone();
         let timeout_status = sync_status;
+        let status_interval = server_config.status_interval();
+        let inactivity_timeout = server_config.inactivity_timeout();
 
         tokio::spawn(async move {
             loop {
-                tokio::time::sleep(Duration::from_secs(5)).await;
+                tokio::time::sleep(status_interval).await;
 
                 if !timeout_flag.load(Ordering::Relaxed) {
                     break;
                 }
 
-                let mut data = timeout_status.lock().unwrap();
-                let is_active = data.status == "syncing" || data.status == "connected";
-
-                if is_active && data.last_activity.elapsed() > Duration::from_secs(15) {
-                    data.status = "timedOut".to_string();
-                }
+                timeout_status.expire_if_idle(inactivity_timeout);
             }
         });
 
         self.shutdown_tx = Some(shutdown_tx);
         self.token = Some(token);
+        self.pairings = Some(pairings);
+        self.guard = Some(guard);
+        self.advertiser = advertiser;
         self.timeout_running = Some(timeout_running);
         self.server_info = Some(server_info.clone());
 
@@ -208,9 +364,11 @@ impl ApiServer {
         }
         self.server_info = None;
         self.token = None;
-        let mut data = self.sync_status.lock().unwrap();
-        data.status = "waiting".to_string();
-        data.last_activity = Instant::now();
+        self.pairings = None;
+        self.guard = None;
+        self.advertiser = None;
+        self.sync_status.detach();
+        self.sync_status.reset();
     }
 
     pub fn is_running(&self) -> bool {
@@ -221,18 +379,40 @@ impl ApiServer {
         self.server_info.as_ref()
     }
 
-    pub fn get_sync_status(&self) -> String {
-        self.sync_status.lock().unwrap().status.clone()
+    pub fn get_sync_status(&self) -> sync_state::SyncSnapshot {
+        self.sync_status.snapshot()
+    }
+
+    pub fn list_pairing_requests(&self) -> Vec<pairing::PairingRequest> {
+        self.pairings
+            .as_ref()
+            .map(|pairings| pairings.list())
+            .unwrap_or_default()
+    }
+
+    pub fn resolve_pairing(&self, pairing_id: &str, approve: bool) -> Result<(), String> {
+        self.pairings
+            .as_ref()
+            .ok_or_else(|| "Server is not running".to_string())?
+            .resolve(pairing_id, approve)
+    }
+
+    pub fn blocked_clients(&self) -> Vec<guard::BlockedClient> {
+        self.guard
+            .as_ref()
+            .map(|guard| guard.blocked_clients())
+            .unwrap_or_default()
     }
 
     pub fn generate_qr_data(&self) -> Option<String> {
         self.server_info.as_ref().map(|info| {
             json!({
-                "version": "1.0",
+                "version": PROTOCOL_VERSION,
                 "host": info.ip,
                 "port": info.port,
                 "token": info.token,
-                "url": info.url
+                "url": info.url,
+                "certFingerprint": info.cert_fingerprint
             })
             .to_string()
         })
@@ -242,9 +422,13 @@ impl ApiServer {
 static API_SERVER: LazyLock<Arc<Mutex<ApiServer>>> =
     LazyLock::new(|| Arc::new(Mutex::new(ApiServer::new())));
 
-pub async fn start_api_server(app_data_dir: PathBuf) -> Result<ServerInfo, String> {
+pub async fn start_api_server(
+    app_data_dir: PathBuf,
+    events: EventSink,
+    server_config: config::ServerConfig,
+) -> Result<ServerInfo, String> {
     let mut server = API_SERVER.lock().await;
-    server.start(app_data_dir).await
+    server.start(app_data_dir, events, server_config).await
 }
 
 pub async fn stop_api_server() {
@@ -267,7 +451,22 @@ pub async fn generate_qr_data() -> Option<String> {
     server.generate_qr_data()
 }
 
-pub async fn get_sync_status() -> String {
+pub async fn get_sync_status() -> sync_state::SyncSnapshot {
     let server = API_SERVER.lock().await;
     server.get_sync_status()
 }
+
+pub async fn list_pairing_requests() -> Vec<pairing::PairingRequest> {
+    let server = API_SERVER.lock().await;
+    server.list_pairing_requests()
+}
+
+pub async fn resolve_pairing(pairing_id: &str, approve: bool) -> Result<(), String> {
+    let server = API_SERVER.lock().await;
+    server.resolve_pairing(pairing_id, approve)
+}
+
+pub async fn list_blocked_clients() -> Vec<guard::BlockedClient> {
+    let server = API_SERVER.lock().await;
+    server.blocked_clients()
+}
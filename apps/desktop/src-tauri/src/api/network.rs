@@ -0,0 +1,272 @@
+use std::net::{IpAddr, Ipv4Addr, SocketAddr};
+use std::sync::Arc;
+
+use ipnet::IpNet;
+
+use local_ip_address::{list_afinet_netifas, local_ip};
+
+use serde::{Deserialize, Serialize};
+
+use warp::{reject, Filter, Rejection};
+
+use super::auth::Forbidden;
+
+#[derive(Debug, Clone, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct NetworkInterface {
+    pub name: String,
+    pub address: IpAddr,
+    pub is_loopback: bool,
+    pub is_default: bool,
+}
+
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase", default)]
+pub struct NetworkOptions {
+    pub bind_interface: Option<String>,
+    pub bind_address: Option<IpAddr>,
+    pub advertised_address: Option<IpAddr>,
+    pub allowed_networks: Vec<String>,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct ResolvedAddresses {
+    pub bind: IpAddr,
+    pub advertised: IpAddr,
+}
+
+pub fn list_interfaces() -> Result<Vec<NetworkInterface>, String> {
+    let default_ip = local_ip().ok();
+    let mut interfaces: Vec<NetworkInterface> = list_afinet_netifas()
+        .map_err(|e| format!("Failed to list network interfaces: {}", e))?
+        .into_iter()
+        .filter(|(_, address)| !is_link_local(address))
+        .map(|(name, address)| NetworkInterface {
+            is_loopback: address.is_loopback(),
+            is_default: Some(address) == default_ip,
+            name,
+            address,
+        })
+        .collect();
+
+    interfaces.sort_by(|a, b| {
+        b.is_default
+            .cmp(&a.is_default)
+            .then(a.is_loopback.cmp(&b.is_loopback))
+            .then(a.address.is_ipv6().cmp(&b.address.is_ipv6()))
+            .then(a.name.cmp(&b.name))
+    });
+    Ok(interfaces)
+}
+
+pub fn is_link_local(address: &IpAddr) -> bool {
+    match address {
+        IpAddr::V4(v4) => v4.is_link_local(),
+        IpAddr::V6(v6) => (v6.segments()[0] & 0xffc0) == 0xfe80,
+    }
+}
+
+pub fn resolve_addresses(options: &NetworkOptions) -> Result<ResolvedAddresses, String> {
+    if options.bind_interface.is_some() && options.bind_address.is_some() {
+        return Err("Choose either a bind interface or a bind address, not both".to_string());
+    }
+
+    let needs_interfaces = options.bind_interface.is_some()
+        || options.advertised_address.is_some()
+        || options
+            .bind_address
+            .is_some_and(|address| !address.is_unspecified());
+    let interfaces = if needs_interfaces {
+        list_interfaces()?
+    } else {
+        Vec::new()
+    };
+
+    let bind = match (&options.bind_interface, options.bind_address) {
+        (_, Some(address)) if address.is_unspecified() => address,
+        (_, Some(address)) => {
+            if !interfaces
+                .iter()
+                .any(|interface| interface.address == address)
+            {
+                return Err(format!(
+                    "Bind address {} is not assigned to this machine",
+                    address
+                ));
+            }
+            address
+        }
+        (Some(name), None) => interfaces
+            .iter()
+            .filter(|interface| &interface.name == name)
+            .min_by_key(|interface| interface.address.is_ipv6())
+            .map(|interface| interface.address)
+            .ok_or_else(|| format!("Network interface {} has no usable address", name))?,
+        (None, None) => IpAddr::V4(Ipv4Addr::UNSPECIFIED),
+    };
+
+    let advertised = match options.advertised_address {
+        Some(address) => {
+            if !interfaces
+                .iter()
+                .any(|interface| interface.address == address)
+            {
+                return Err(format!(
+                    "Advertised address {} is not assigned to this machine",
+                    address
+                ));
+            }
+            if !bind.is_unspecified() && bind != address {
+                return Err(format!(
+                    "Advertised address {} is not reachable on the bound address {}",
+                    address, bind
+                ));
+            }
+            address
+        }
+        None if !bind.is_unspecified() => bind,
+        None => local_ip().map_err(|e| format!("Failed to detect local network IP: {}", e))?,
+    };
+
+    Ok(ResolvedAddresses { bind, advertised })
+}
+
+#[derive(Debug, Clone, Default)]
+pub struct AllowList {
+    networks: Vec<IpNet>,
+}
+
+impl AllowList {
+    pub fn parse(entries: &[String]) -> Result<Self, String> {
+        let networks = entries
+            .iter()
+            .map(|entry| entry.trim())
+            .filter(|entry| !entry.is_empty())
+            .map(|entry| {
+                entry
+                    .parse::<IpNet>()
+                    .or_else(|_| entry.parse::<IpAddr>().map(IpNet::from))
+                    .map(|network| network.trunc())
+                    .map_err(|_| format!("Invalid network in allow-list: {}", entry))
+            })
+            .collect::<Result<Vec<_>, _>>()?;
+        Ok(Self { networks })
+    }
+
+    pub fn allows(&self, ip: IpAddr) -> bool {
+        if self.networks.is_empty() || ip.is_loopback() {
+            return true;
+        }
+        let ip = ip.to_canonical();
+        self.networks.iter().any(|network| network.contains(&ip))
+    }
+}
+
+pub fn with_allow_list(
+    allow_list: Arc<AllowList>,
+) -> impl Filter<Extract = (), Error = Rejection> + Clone {
+    warp::addr::remote()
+        .and_then(move |remote: Option<SocketAddr>| {
+            let allow_list = allow_list.clone();
+            async move {
+                match remote {
+                    Some(remote) if !allow_list.allows(remote.ip()) => {
+                        log::warn!("Rejected client {} outside the allow-list", remote.ip());
+                        Err(reject::custom(Forbidden(
+                            "Client address is not allowed".to_string(),
+                        )))
+                    }
+                    _ => Ok(()),
+                }
+            }
+        })
+        .untuple_one()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn allow_list(entries: &[&str]) -> AllowList {
+        let entries: Vec<String> = entries.iter().map(|entry| entry.to_string()).collect();
+        AllowList::parse(&entries).unwrap()
+    }
+
+    fn ip(address: &str) -> IpAddr {
+        address.parse().unwrap()
+    }
+
+    #[test]
+    fn empty_allow_list_allows_everyone() {
+        let list = allow_list(&[" ", ""]);
+        assert!(list.allows(ip("203.0.113.7")));
+        assert!(list.allows(ip("2001:db8::1")));
+    }
+
+    #[test]
+    fn matches_addresses_inside_the_network() {
+        let list = allow_list(&["192.168.1.0/24", "10.0.0.0/8"]);
+        assert!(list.allows(ip("192.168.1.1")));
+        assert!(list.allows(ip("192.168.1.255")));
+        assert!(list.allows(ip("10.20.30.40")));
+        assert!(!list.allows(ip("192.168.2.1")));
+        assert!(!list.allows(ip("11.0.0.1")));
+    }
+
+    #[test]
+    fn truncates_host_bits_in_networks() {
+        let list = allow_list(&["192.168.1.77/24"]);
+        assert!(list.allows(ip("192.168.1.3")));
+        assert!(!list.allows(ip("192.168.0.3")));
+    }
+
+    #[test]
+    fn treats_bare_addresses_as_single_hosts() {
+        let list = allow_list(&["192.168.1.5", "fd00::1"]);
+        assert!(list.allows(ip("192.168.1.5")));
+        assert!(!list.allows(ip("192.168.1.6")));
+        assert!(list.allows(ip("fd00::1")));
+        assert!(!list.allows(ip("fd00::2")));
+    }
+
+    #[test]
+    fn matches_ipv6_networks() {
+        let list = allow_list(&["fd00:1234::/32"]);
+        assert!(list.allows(ip("fd00:1234:5678::9")));
+        assert!(!list.allows(ip("fd00:4321::9")));
+        assert!(!list.allows(ip("192.168.1.1")));
+    }
+
+    #[test]
+    fn matches_ipv4_mapped_ipv6_clients() {
+        let list = allow_list(&["192.168.1.0/24"]);
+        assert!(list.allows(ip("::ffff:192.168.1.9")));
+        assert!(!list.allows(ip("::ffff:192.168.2.9")));
+    }
+
+    #[test]
+    fn always_allows_loopback() {
+        let list = allow_list(&["192.168.1.0/24"]);
+        assert!(list.allows(ip("127.0.0.1")));
+        assert!(list.allows(ip("127.1.2.3")));
+        assert!(list.allows(ip("::1")));
+    }
+
+    #[test]
+    fn rejects_invalid_entries() {
+        let entries = vec!["192.168.1.0/24".to_string(), "not-a-network".to_string()];
+        assert_eq!(
+            AllowList::parse(&entries).unwrap_err(),
+            "Invalid network in allow-list: not-a-network"
+        );
+        assert!(AllowList::parse(&["10.0.0.0/33".to_string()]).is_err());
+    }
+
+    #[test]
+    fn detects_link_local_addresses() {
+        assert!(is_link_local(&ip("169.254.10.1")));
+        assert!(is_link_local(&ip("fe80::1")));
+        assert!(!is_link_local(&ip("192.168.1.1")));
+        assert!(!is_link_local(&ip("fd00::1")));
+    }
+}
@@ -0,0 +1,282 @@
+use std::collections::HashMap;
+use std::sync::Mutex;
+use std::time::{Duration, Instant};
+
+use base64::engine::general_purpose::STANDARD as BASE64;
+use base64::Engine;
+
+use ring::aead::{self, Aad, LessSafeKey, Nonce, UnboundKey, NONCE_LEN};
+use ring::agreement::{self, EphemeralPrivateKey, UnparsedPublicKey, X25519};
+use ring::hkdf::{self, Salt, HKDF_SHA256};
+use ring::rand::{SecureRandom, SystemRandom};
+
+use serde::Serialize;
+
+use sha2::{Digest, Sha256};
+
+pub const PAIRING_REQUEST_EVENT: &str = "sync-pairing-request";
+pub const PAIRING_RESOLVED_EVENT: &str = "sync-pairing-resolved";
+
+const PAIRING_TTL: Duration = Duration::from_secs(5 * 60);
+const PUBLIC_KEY_BYTES: usize = 32;
+const CREDENTIAL_INFO: &str = "tunno-pair-credential";
+const MAX_PENDING_PAIRINGS: usize = 16;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub enum PairingState {
+    Pending,
+    Approved,
+    Rejected,
+}
+
+#[derive(Debug, Clone, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct PairingRequest {
+    pub pairing_id: String,
+    pub device_name: String,
+    pub pin: String,
+    pub expires_in_secs: u64,
+}
+
+#[derive(Debug, Clone, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct PairingChallenge {
+    pub pairing_id: String,
+    pub nonce: String,
+    pub expires_in_secs: u64,
+}
+
+#[derive(Debug, Clone, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct SealedCredential {
+    pub server_public_key: String,
+    pub nonce: String,
+    pub ciphertext: String,
+}
+
+#[derive(Debug, Clone)]
+pub struct ClaimedPairing {
+    pub device_name: String,
+    pub public_key: String,
+    pub nonce: String,
+}
+
+#[derive(Debug, PartialEq, Eq)]
+pub enum PairingError {
+    NotFound,
+    KeyMismatch,
+}
+
+struct PendingPairing {
+    device_name: String,
+    public_key: String,
+    nonce: String,
+    pin: String,
+    state: PairingState,
+    created_at: Instant,
+}
+
+impl PendingPairing {
+    fn to_request(&self, pairing_id: &str) -> PairingRequest {
+        PairingRequest {
+            pairing_id: pairing_id.to_string(),
+            device_name: self.device_name.clone(),
+            pin: self.pin.clone(),
+            expires_in_secs: PAIRING_TTL
+                .saturating_sub(self.created_at.elapsed())
+                .as_secs(),
+        }
+    }
+}
+
+pub fn pairing_pin(session_token: &str, public_key: &str, nonce: &str) -> String {
+    let digest = Sha256::digest(format!(
+        "tunno-pair:{}:{}:{}",
+        session_token, public_key, nonce
+    ));
+    let value = u32::from_be_bytes([digest[0], digest[1], digest[2], digest[3]]);
+    format!("{:06}", value % 1_000_000)
+}
+
+fn decode_public_key(public_key: &str) -> Result<Vec<u8>, String> {
+    let bytes = BASE64
+        .decode(public_key)
+        .map_err(|_| "Public key must be base64 encoded".to_string())?;
+    if bytes.len() != PUBLIC_KEY_BYTES {
+        return Err(format!(
+            "Public key must be a {}-byte X25519 key",
+            PUBLIC_KEY_BYTES
+        ));
+    }
+    Ok(bytes)
+}
+
+pub fn validate_public_key(public_key: &str) -> Result<(), String> {
+    decode_public_key(public_key).map(|_| ())
+}
+
+struct CredentialKey;
+
+impl hkdf::KeyType for CredentialKey {
+    fn len(&self) -> usize {
+        aead::CHACHA20_POLY1305.key_len()
+    }
+}
+
+pub fn seal_credential(
+    pairing_id: &str,
+    pairing: &ClaimedPairing,
+    credential: &str,
+) -> Result<SealedCredential, String> {
+    let rng = SystemRandom::new();
+    let peer_public_key = decode_public_key(&pairing.public_key)?;
+
+    let private_key = EphemeralPrivateKey::generate(&X25519, &rng)
+        .map_err(|_| "Failed to generate pairing key".to_string())?;
+    let server_public_key = private_key
+        .compute_public_key()
+        .map_err(|_| "Failed to derive pairing public key".to_string())?;
+
+    let info = format!("{}:{}", CREDENTIAL_INFO, pairing_id);
+    let key_bytes = agreement::agree_ephemeral(
+        private_key,
+        &UnparsedPublicKey::new(&X25519, &peer_public_key),
+        |shared_secret| {
+            let mut key_bytes = [0u8; 32];
+            Salt::new(HKDF_SHA256, pairing.nonce.as_bytes())
+                .extract(shared_secret)
+                .expand(&[info.as_bytes()], CredentialKey)
+                .and_then(|okm| okm.fill(&mut key_bytes))
+                .map(|()| key_bytes)
+        },
+    )
+    .and_then(|result| result)
+    .map_err(|_| "Failed to agree on a pairing key".to_string())?;
+
+    let key = UnboundKey::new(&aead::CHACHA20_POLY1305, &key_bytes)
+        .map_err(|_| "Failed to create pairing cipher".to_string())?;
+    let mut nonce = [0u8; NONCE_LEN];
+    rng.fill(&mut nonce)
+        .map_err(|_| "Failed to generate pairing nonce".to_string())?;
+
+    let mut ciphertext = credential.as_bytes().to_vec();
+    LessSafeKey::new(key)
+        .seal_in_place_append_tag(
+            Nonce::assume_unique_for_key(nonce),
+            Aad::from(pairing_id.as_bytes()),
+            &mut ciphertext,
+        )
+        .map_err(|_| "Failed to encrypt device credential".to_string())?;
+
+    Ok(SealedCredential {
+        server_public_key: BASE64.encode(server_public_key.as_ref()),
+        nonce: BASE64.encode(nonce),
+        ciphertext: BASE64.encode(ciphertext),
+    })
+}
+
+#[derive(Default)]
+pub struct PairingRegistry {
+    pending: Mutex<HashMap<String, PendingPairing>>,
+}
+
+impl PairingRegistry {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    fn prune(pending: &mut HashMap<String, PendingPairing>) {
+        pending.retain(|_, pairing| pairing.created_at.elapsed() < PAIRING_TTL);
+    }
+
+    pub fn begin(
+        &self,
+        session_token: &str,
+        device_name: String,
+        public_key: String,
+    ) -> Result<(PairingChallenge, PairingRequest), String> {
+        let mut pending = self.pending.lock().unwrap();
+        Self::prune(&mut pending);
+
+        if pending.len() >= MAX_PENDING_PAIRINGS {
+            return Err("Too many pending pairing requests".to_string());
+        }
+
+        let pairing_id = uuid::Uuid::new_v4().to_string();
+        let nonce = uuid::Uuid::new_v4().simple().to_string();
+        let pairing = PendingPairing {
+            pin: pairing_pin(session_token, &public_key, &nonce),
+            device_name,
+            public_key,
+            nonce: nonce.clone(),
+            state: PairingState::Pending,
+            created_at: Instant::now(),
+        };
+
+        let request = pairing.to_request(&pairing_id);
+        let challenge = PairingChallenge {
+            pairing_id: pairing_id.clone(),
+            nonce,
+            expires_in_secs: request.expires_in_secs,
+        };
+        pending.insert(pairing_id, pairing);
+
+        Ok((challenge, request))
+    }
+
+    pub fn list(&self) -> Vec<PairingRequest> {
+        let mut pending = self.pending.lock().unwrap();
+        Self::prune(&mut pending);
+
+        pending
+            .iter()
+            .filter(|(_, pairing)| pairing.state == PairingState::Pending)
+            .map(|(id, pairing)| pairing.to_request(id))
+            .collect()
+    }
+
+    pub fn resolve(&self, pairing_id: &str, approve: bool) -> Result<(), String> {
+        let mut pending = self.pending.lock().unwrap();
+        Self::prune(&mut pending);
+
+        match pending.get_mut(pairing_id) {
+            Some(pairing) if pairing.state == PairingState::Pending => {
+                pairing.state = if approve {
+                    PairingState::Approved
+                } else {
+                    PairingState::Rejected
+                };
+                Ok(())
+            }
+            Some(_) => Err(format!("Pairing {} was already resolved", pairing_id)),
+            None => Err(format!("Pairing {} not found or expired", pairing_id)),
+        }
+    }
+
+    pub fn claim(
+        &self,
+        pairing_id: &str,
+        public_key: &str,
+    ) -> Result<(PairingState, ClaimedPairing), PairingError> {
+        let mut pending = self.pending.lock().unwrap();
+        Self::prune(&mut pending);
+
+        let pairing = pending.get(pairing_id).ok_or(PairingError::NotFound)?;
+        if pairing.public_key != public_key {
+            return Err(PairingError::KeyMismatch);
+        }
+
+        let state = pairing.state;
+        let claimed = ClaimedPairing {
+            device_name: pairing.device_name.clone(),
+            public_key: pairing.public_key.clone(),
+            nonce: pairing.nonce.clone(),
+        };
+        if state != PairingState::Pending {
+            pending.remove(pairing_id);
+        }
+
+        Ok((state, claimed))
+    }
+}
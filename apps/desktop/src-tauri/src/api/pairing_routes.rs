@@ -0,0 +1,193 @@
+use std::sync::Arc;
+
+use serde::{Deserialize, Serialize};
+
+use warp::http::StatusCode;
+use warp::Filter;
+
+use super::auth::{self, with_session_token, Authenticator};
+use super::compression;
+use super::db;
+use super::pairing::{self, PairingError, PairingRegistry, PairingState};
+use super::{EventSink, SyncStatus};
+
+const MAX_DEVICE_NAME_CHARS: usize = 64;
+
+#[derive(Debug, Deserialize)]
+#[serde(rename_all = "camelCase")]
+struct PairRequest {
+    device_name: String,
+    public_key: String,
+}
+
+#[derive(Debug, Deserialize)]
+#[serde(rename_all = "camelCase")]
+struct ClaimRequest {
+    public_key: String,
+}
+
+#[derive(Debug, Serialize)]
+#[serde(rename_all = "camelCase")]
+struct ClaimResponse {
+    device: db::PairedDevice,
+    credential: pairing::SealedCredential,
+}
+
+pub fn normalize_device_name(name: &str) -> Result<String, String> {
+    let name: String = name.trim().chars().take(MAX_DEVICE_NAME_CHARS).collect();
+    if name.is_empty() {
+        return Err("Device name must not be empty".to_string());
+    }
+    Ok(name)
+}
+
+fn error_reply(status: StatusCode, err: String) -> warp::reply::WithStatus<warp::reply::Json> {
+    warp::reply::with_status(
+        warp::reply::json(&serde_json::json!({ "error": err })),
+        status,
+    )
+}
+
+async fn handle_pair(
+    body: PairRequest,
+    auth: Arc<Authenticator>,
+    pairings: Arc<PairingRegistry>,
+    events: EventSink,
+    sync_status: SyncStatus,
+) -> Result<impl warp::Reply, warp::Rejection> {
+    sync_status.touch();
+
+    let device_name = match normalize_device_name(&body.device_name) {
+        Ok(name) => name,
+        Err(err) => return Ok(error_reply(StatusCode::BAD_REQUEST, err)),
+    };
+    if let Err(err) = pairing::validate_public_key(&body.public_key) {
+        return Ok(error_reply(StatusCode::BAD_REQUEST, err));
+    }
+
+    match pairings.begin(auth.session_token(), device_name, body.public_key) {
+        Ok((challenge, request)) => {
+            log::info!(
+                "Pairing requested by {} ({})",
+                request.device_name,
+                request.pairing_id
+            );
+            events(
+                pairing::PAIRING_REQUEST_EVENT,
+                serde_json::to_value(&request).unwrap_or_default(),
+            );
+            Ok(warp::reply::with_status(
+                warp::reply::json(&challenge),
+                StatusCode::ACCEPTED,
+            ))
+        }
+        Err(err) => Ok(error_reply(StatusCode::TOO_MANY_REQUESTS, err)),
+    }
+}
+
+async fn handle_claim(
+    pairing_id: String,
+    body: ClaimRequest,
+    auth: Arc<Authenticator>,
+    pairings: Arc<PairingRegistry>,
+    sync_status: SyncStatus,
+) -> Result<impl warp::Reply, warp::Rejection> {
+    sync_status.touch();
+
+    let claimed = match pairings.claim(&pairing_id, &body.public_key) {
+        Ok((PairingState::Pending, _)) => return Err(warp::reject::custom(auth::PendingApproval)),
+        Ok((PairingState::Rejected, _)) => {
+            return Ok(error_reply(
+                StatusCode::FORBIDDEN,
+                "Pairing was rejected on the desktop".to_string(),
+            ))
+        }
+        Ok((PairingState::Approved, claimed)) => claimed,
+        Err(PairingError::NotFound) => {
+            return Ok(error_reply(
+                StatusCode::NOT_FOUND,
+                "Pairing not found or expired".to_string(),
+            ))
+        }
+        Err(PairingError::KeyMismatch) => {
+            return Ok(error_reply(
+                StatusCode::FORBIDDEN,
+                "Public key does not match the pairing request".to_string(),
+            ))
+        }
+    };
+
+    let db_path = auth.db_path().clone();
+    let result = tokio::task::spawn_blocking(move || {
+        let conn = db::open_readwrite(&db_path).map_err(|e| e.to_string())?;
+
+        let device_id = uuid::Uuid::new_v4().to_string();
+        let secret = auth::generate_device_secret();
+        let credential = pairing::seal_credential(
+            &pairing_id,
+            &claimed,
+            &auth::device_credential(&device_id, &secret),
+        )?;
+        let device = db::insert_device(
+            &conn,
+            &device_id,
+            &claimed.device_name,
+            &auth::hash_secret(&secret),
+        )
+        .map_err(|e| format!("Failed to register device: {}", e))?;
+
+        Ok::<ClaimResponse, String>(ClaimResponse { device, credential })
+    })
+    .await
+    .map_err(|_| warp::reject::reject())?;
+
+    match result {
+        Ok(response) => {
+            log::info!(
+                "Paired device {} ({})",
+                response.device.name,
+                response.device.id
+            );
+            Ok(warp::reply::with_status(
+                warp::reply::json(&response),
+                StatusCode::CREATED,
+            ))
+        }
+        Err(err) => Ok(error_reply(StatusCode::INTERNAL_SERVER_ERROR, err)),
+    }
+}
+
+pub fn pairing_routes(
+    auth: Arc<Authenticator>,
+    pairings: Arc<PairingRegistry>,
+    events: EventSink,
+    sync_status: SyncStatus,
+) -> impl Filter<Extract = (impl warp::Reply,), Error = warp::Rejection> + Clone {
+    let auth_pair = auth.clone();
+    let auth_claim = auth.clone();
+    let pairings_pair = pairings.clone();
+    let pairings_claim = pairings;
+    let status_pair = sync_status.clone();
+    let status_claim = sync_status;
+
+    let pair = warp::path!("api" / "pair")
+        .and(warp::post())
+        .and(with_session_token(auth.clone()))
+        .and(compression::json_body())
+        .and(warp::any().map(move || auth_pair.clone()))
+        .and(warp::any().map(move || pairings_pair.clone()))
+        .and(warp::any().map(move || events.clone()))
+        .and(warp::any().map(move || status_pair.clone()))
+        .and_then(handle_pair);
+
+    let claim = warp::path!("api" / "pair" / String / "claim")
+        .and(warp::post())
+        .and(with_session_token(auth))
+        .and(compression::json_body())
+        .and(warp::any().map(move || auth_claim.clone()))
+        .and(warp::any().map(move || pairings_claim.clone()))
+        .and(warp::any().map(move || status_claim.clone()))
+        .and_then(handle_claim);
+
+    pair.or(claim)
+}
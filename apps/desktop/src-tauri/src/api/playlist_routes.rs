@@ -0,0 +1,128 @@
+use std::collections::HashSet;
+use std::path::PathBuf;
+use std::sync::Arc;
+
+use serde::Serialize;
+
+use warp::http::StatusCode;
+use warp::Filter;
+
+use super::auth::{with_scoped_caller, Authenticator, Caller};
+use super::db;
+use super::tokens::Scope;
+
+#[derive(Debug, Serialize)]
+#[serde(rename_all = "camelCase")]
+struct PlaylistContents {
+    playlist: db::PlaylistRow,
+    songs: Vec<db::SongRow>,
+    albums: Vec<db::AlbumRow>,
+    artists: Vec<db::ArtistRow>,
+}
+
+enum PlaylistLookup {
+    Found(Box<PlaylistContents>),
+    Missing,
+    Forbidden,
+}
+
+fn unique(fingerprints: impl Iterator<Item = String>) -> Vec<String> {
+    let mut seen = HashSet::new();
+    fingerprints
+        .filter(|fingerprint| seen.insert(fingerprint.clone()))
+        .collect()
+}
+
+fn load_playlist(
+    conn: &rusqlite::Connection,
+    caller: &Caller,
+    fingerprint: String,
+) -> Result<PlaylistLookup, String> {
+    if let Some(claims) = caller.allowed_collections() {
+        if !claims.playlists.contains(&fingerprint) {
+            return Ok(PlaylistLookup::Forbidden);
+        }
+    }
+
+    let Some(playlist) = db::get_playlists_by_fingerprints(conn, &[fingerprint])
+        .map_err(|e| e.to_string())?
+        .into_iter()
+        .next()
+    else {
+        return Ok(PlaylistLookup::Missing);
+    };
+
+    let mut songs = db::get_songs_by_fingerprints(conn, &playlist.song_fingerprints, false)
+        .map_err(|e| e.to_string())?;
+    songs.sort_by_key(|song| {
+        playlist
+            .song_fingerprints
+            .iter()
+            .position(|fingerprint| *fingerprint == song.fingerprint)
+    });
+
+    let album_fingerprints = unique(
+        songs
+            .iter()
+            .filter_map(|song| song.album_fingerprint.clone()),
+    );
+    let artist_fingerprints = unique(songs.iter().flat_map(|song| {
+        song.artist_fingerprints
+            .iter()
+            .map(|artist| artist.fingerprint.clone())
+    }));
+
+    let albums =
+        db::get_albums_by_fingerprints(conn, &album_fingerprints).map_err(|e| e.to_string())?;
+    let artists =
+        db::get_artists_by_fingerprints(conn, &artist_fingerprints).map_err(|e| e.to_string())?;
+
+    Ok(PlaylistLookup::Found(Box::new(PlaylistContents {
+        playlist,
+        songs,
+        albums,
+        artists,
+    })))
+}
+
+async fn handle_playlist(
+    fingerprint: String,
+    caller: Caller,
+    db_path: Arc<PathBuf>,
+) -> Result<impl warp::Reply, warp::Rejection> {
+    let result = tokio::task::spawn_blocking(move || {
+        let conn = db::open_readonly(&db_path).map_err(|e| e.to_string())?;
+        load_playlist(&conn, &caller, fingerprint)
+    })
+    .await
+    .map_err(|_| warp::reject::reject())?;
+
+    let (body, status) = match result {
+        Ok(PlaylistLookup::Found(contents)) => (serde_json::json!(contents), StatusCode::OK),
+        Ok(PlaylistLookup::Missing) => (
+            serde_json::json!({ "error": "Playlist not found" }),
+            StatusCode::NOT_FOUND,
+        ),
+        Ok(PlaylistLookup::Forbidden) => (
+            serde_json::json!({ "error": "Token does not grant access to this playlist" }),
+            StatusCode::FORBIDDEN,
+        ),
+        Err(err) => (
+            serde_json::json!({ "error": err }),
+            StatusCode::INTERNAL_SERVER_ERROR,
+        ),
+    };
+
+    Ok(warp::reply::with_status(warp::reply::json(&body), status))
+}
+
+pub fn playlist_routes(
+    auth: Arc<Authenticator>,
+    db_path: Arc<PathBuf>,
+) -> impl Filter<Extract = (impl warp::Reply,), Error = warp::Rejection> + Clone {
+    warp::path!("api" / "playlists" / String)
+        .and(warp::get())
+        .and(with_scoped_caller(auth, Scope::PlaylistRead))
+        .and(warp::any().map(move || db_path.clone()))
+        .and_then(handle_playlist)
+}
@@ -0,0 +1,521 @@
+use std::path::{Path, PathBuf};
+use std::sync::Arc;
+
+use futures_util::TryStreamExt;
+
+use serde::de::DeserializeOwned;
+
+use tokio::io::AsyncWriteExt;
+
+use warp::http::StatusCode;
+use warp::multipart::{FormData, Part};
+use warp::{Buf, Filter};
+
+use super::auth::{with_scope, Authenticator};
+use super::compression;
+use super::db;
+use super::file_routes::content_type_for_extension;
+use super::sync_state::EntityCounts;
+use super::tokens::Scope;
+use super::SyncStatus;
+
+const MAX_METADATA_BYTES: usize = 4 * 1024 * 1024;
+
+#[derive(Debug, Clone, Copy)]
+enum PushKind {
+    Artist,
+    Album,
+    Song,
+    Playlist,
+}
+
+impl PushKind {
+    fn counts(self) -> EntityCounts {
+        let mut counts = EntityCounts::default();
+        match self {
+            PushKind::Artist => counts.artists = 1,
+            PushKind::Album => counts.albums = 1,
+            PushKind::Song => counts.songs = 1,
+            PushKind::Playlist => counts.playlists = 1,
+        }
+        counts
+    }
+}
+
+#[derive(Debug)]
+struct FormError {
+    status: StatusCode,
+    message: String,
+}
+
+impl From<String> for FormError {
+    fn from(message: String) -> Self {
+        Self {
+            status: StatusCode::BAD_REQUEST,
+            message,
+        }
+    }
+}
+
+fn too_large(max_bytes: u64) -> FormError {
+    FormError {
+        status: StatusCode::PAYLOAD_TOO_LARGE,
+        message: format!("Upload exceeds the limit of {} bytes", max_bytes),
+    }
+}
+
+#[derive(Debug, Default)]
+struct PushForm {
+    metadata: Option<Vec<u8>>,
+    audio: Option<String>,
+    thumbnail: Option<String>,
+}
+
+fn extension_from_filename(filename: Option<&str>) -> String {
+    filename
+        .and_then(|name| Path::new(name).extension())
+        .and_then(|ext| ext.to_str())
+        .filter(|ext| !ext.is_empty() && ext.chars().all(|c| c.is_ascii_alphanumeric()))
+        .map(|ext| ext.to_lowercase())
+        .unwrap_or_else(|| "bin".to_string())
+}
+
+fn check_media_type(
+    field: &str,
+    content_type: Option<&str>,
+    extension: &str,
+    media: &str,
+) -> Result<(), FormError> {
+    let unsupported = |detail: String| FormError {
+        status: StatusCode::UNSUPPORTED_MEDIA_TYPE,
+        message: format!("Unsupported {} file: {}", field, detail),
+    };
+
+    if !content_type_for_extension(extension).starts_with(media) {
+        return Err(unsupported(format!(".{}", extension)));
+    }
+    match content_type {
+        Some(content_type)
+            if !content_type.starts_with(media) && content_type != "application/octet-stream" =>
+        {
+            Err(unsupported(content_type.to_string()))
+        }
+        _ => Ok(()),
+    }
+}
+
+struct UploadBudget {
+    max_bytes: u64,
+    used: u64,
+}
+
+impl UploadBudget {
+    fn consume(&mut self, len: usize) -> Result<(), FormError> {
+        self.used += len as u64;
+        if self.used > self.max_bytes {
+            return Err(too_large(self.max_bytes));
+        }
+        Ok(())
+    }
+}
+
+async fn save_part(
+    part: Part,
+    dir: &Path,
+    media: &str,
+    budget: &mut UploadBudget,
+) -> Result<String, FormError> {
+    let extension = extension_from_filename(part.filename());
+    check_media_type(part.name(), part.content_type(), &extension, media)?;
+
+    tokio::fs::create_dir_all(dir)
+        .await
+        .map_err(|e| format!("Failed to create directory: {}", e))?;
+
+    let file_name = format!("{}.{}", uuid::Uuid::new_v4(), extension);
+    let file_path = dir.join(&file_name);
+
+    let mut file = tokio::fs::File::create(&file_path)
+        .await
+        .map_err(|e| format!("Failed to create {}: {}", file_name, e))?;
+
+    let mut stream = part.stream();
+    let result: Result<(), FormError> = async {
+        while let Some(mut chunk) = stream
+            .try_next()
+            .await
+            .map_err(|e| format!("Failed to read upload: {}", e))?
+        {
+            budget.consume(chunk.remaining())?;
+            while chunk.has_remaining() {
+                let bytes = chunk.chunk();
+                file.write_all(bytes)
+                    .await
+                    .map_err(|e| format!("Failed to write {}: {}", file_name, e))?;
+                let len = bytes.len();
+                chunk.advance(len);
+            }
+        }
+        file.flush()
+            .await
+            .map_err(|e| FormError::from(format!("Failed to write {}: {}", file_name, e)))
+    }
+    .await;
+
+    if let Err(e) = result {
+        let _ = tokio::fs::remove_file(&file_path).await;
+        return Err(e);
+    }
+
+    Ok(file_name)
+}
+
+async fn read_metadata(part: Part, budget: &mut UploadBudget) -> Result<Vec<u8>, FormError> {
+    let mut data = Vec::new();
+    let mut stream = part.stream();
+
+    while let Some(mut chunk) = stream
+        .try_next()
+        .await
+        .map_err(|e| format!("Failed to read metadata: {}", e))?
+    {
+        if data.len() + chunk.remaining() > MAX_METADATA_BYTES {
+            return Err("Metadata part is too large".to_string().into());
+        }
+        budget.consume(chunk.remaining())?;
+        while chunk.has_remaining() {
+            let bytes = chunk.chunk();
+            data.extend_from_slice(bytes);
+            let len = bytes.len();
+            chunk.advance(len);
+        }
+    }
+
+    Ok(data)
+}
+
+async fn read_push_form(
+    mut form: FormData,
+    songs_dir: &Path,
+    thumbnails_dir: &Path,
+    max_bytes: u64,
+) -> Result<PushForm, (PushForm, FormError)> {
+    let mut push_form = PushForm::default();
+    let mut budget = UploadBudget { max_bytes, used: 0 };
+
+    loop {
+        let part = match form.try_next().await {
+            Ok(Some(part)) => part,
+            Ok(None) => break,
+            Err(e) => return Err((push_form, format!("Invalid multipart body: {}", e).into())),
+        };
+
+        let result = match part.name() {
+            "metadata" => read_metadata(part, &mut budget)
+                .await
+                .map(|data| push_form.metadata = Some(data)),
+            "audio" if push_form.audio.is_none() => {
+                save_part(part, songs_dir, "audio/", &mut budget)
+                    .await
+                    .map(|name| push_form.audio = Some(name))
+            }
+            "thumbnail" if push_form.thumbnail.is_none() => {
+                save_part(part, thumbnails_dir, "image/", &mut budget)
+                    .await
+                    .map(|name| push_form.thumbnail = Some(name))
+            }
+            other => Err(format!("Unexpected multipart field: {}", other).into()),
+        };
+
+        if let Err(e) = result {
+            return Err((push_form, e));
+        }
+    }
+
+    Ok(push_form)
+}
+
+fn discard_uploads(form: &PushForm, songs_dir: &Path, thumbnails_dir: &Path) {
+    if let Some(audio) = &form.audio {
+        let _ = std::fs::remove_file(songs_dir.join(audio));
+    }
+    if let Some(thumbnail) = &form.thumbnail {
+        let _ = std::fs::remove_file(thumbnails_dir.join(thumbnail));
+    }
+}
+
+fn parse_metadata<T: DeserializeOwned>(form: &PushForm) -> Result<T, String> {
+    let data = form
+        .metadata
+        .as_ref()
+        .ok_or_else(|| "Missing metadata field".to_string())?;
+    serde_json::from_slice(data).map_err(|e| format!("Invalid metadata: {}", e))
+}
+
+fn insert_pushed(
+    kind: PushKind,
+    conn: &mut rusqlite::Connection,
+    form: &PushForm,
+) -> Result<db::PushOutcome, String> {
+    let thumbnail = form.thumbnail.as_deref();
+
+    match kind {
+        PushKind::Artist => db::insert_artist(conn, &parse_metadata(form)?, thumbnail),
+        PushKind::Album => db::insert_album(conn, &parse_metadata(form)?, thumbnail),
+        PushKind::Song => {
+            let song: db::NewSongRow = parse_metadata(form)?;
+            let audio = form
+                .audio
+                .as_deref()
+                .ok_or_else(|| "Missing audio field".to_string())?;
+            db::insert_song(conn, &song, audio, thumbnail)
+        }
+        PushKind::Playlist => db::insert_playlist(conn, &parse_metadata(form)?, thumbnail),
+    }
+}
+
+async fn handle_push(
+    kind: PushKind,
+    form: FormData,
+    db_path: Arc<PathBuf>,
+    app_data_dir: Arc<PathBuf>,
+    sync_status: SyncStatus,
+    content_length: Option<u64>,
+    max_upload_bytes: u64,
+) -> Result<impl warp::Reply, warp::Rejection> {
+    if content_length.is_some_and(|length| length > max_upload_bytes) {
+        let err = too_large(max_upload_bytes);
+        return Ok(warp::reply::with_status(
+            warp::reply::json(&serde_json::json!({ "error": err.message })),
+            err.status,
+        ));
+    }
+
+    sync_status.begin_syncing();
+
+    let songs_dir = app_data_dir.join("songs");
+    let thumbnails_dir = app_data_dir.join("thumbnails");
+
+    let push_form = match read_push_form(form, &songs_dir, &thumbnails_dir, max_upload_bytes).await
+    {
+        Ok(push_form) => push_form,
+        Err((partial, err)) => {
+            discard_uploads(&partial, &songs_dir, &thumbnails_dir);
+            return Ok(warp::reply::with_status(
+                warp::reply::json(&serde_json::json!({ "error": err.message })),
+                err.status,
+            ));
+        }
+    };
+
+    sync_status.touch();
+
+    let result = tokio::task::spawn_blocking(move || {
+        let outcome = db::open_readwrite(&db_path)
+            .map_err(|e| e.to_string())
+            .and_then(|mut conn| insert_pushed(kind, &mut conn, &push_form));
+
+        if !matches!(outcome, Ok(db::PushOutcome { created: true, .. })) {
+            discard_uploads(&push_form, &songs_dir, &thumbnails_dir);
+        }
+
+        outcome
+    })
+    .await
+    .map_err(|_| warp::reject::reject())?;
+
+    match result {
+        Ok(outcome) => {
+            sync_status.record_received(&kind.counts());
+            Ok(warp::reply::with_status(
+                warp::reply::json(&outcome),
+                if outcome.created {
+                    StatusCode::CREATED
+                } else {
+                    StatusCode::OK
+                },
+            ))
+        }
+        Err(err) => Ok(warp::reply::with_status(
+            warp::reply::json(&serde_json::json!({ "error": err })),
+            StatusCode::UNPROCESSABLE_ENTITY,
+        )),
+    }
+}
+
+pub fn push_routes(
+    auth: Arc<Authenticator>,
+    db_path: Arc<PathBuf>,
+    app_data_dir: Arc<PathBuf>,
+    sync_status: SyncStatus,
+    max_upload_bytes: u64,
+) -> impl Filter<Extract = (impl warp::Reply,), Error = warp::Rejection> + Clone {
+    let kind = warp::path!("api" / "sync" / "push" / String).and_then(|kind: String| async move {
+        match kind.as_str() {
+            "artist" => Ok(PushKind::Artist),
+            "album" => Ok(PushKind::Album),
+            "song" => Ok(PushKind::Song),
+            "playlist" => Ok(PushKind::Playlist),
+            _ => Err(warp::reject::not_found()),
+        }
+    });
+
+    kind.and(warp::post())
+        .and(with_scope(auth, Scope::SyncWrite))
+        .and(compression::identity_body())
+        .and(warp::multipart::form().max_length(None))
+        .and(warp::any().map(move || db_path.clone()))
+        .and(warp::any().map(move || app_data_dir.clone()))
+        .and(warp::any().map(move || sync_status.clone()))
+        .and(warp::header::optional::<u64>("content-length"))
+        .and(warp::any().map(move || max_upload_bytes))
+        .and_then(handle_push)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const BOUNDARY: &str = "push-test-boundary";
+
+    fn multipart_body(parts: &[(&str, &str, &str, &[u8])]) -> Vec<u8> {
+        let mut body = Vec::new();
+        for (name, filename, content_type, data) in parts {
+            body.extend_from_slice(
+                format!(
+                    "--{}\r\nContent-Disposition: form-data; name=\"{}\"; filename=\"{}\"\r\nContent-Type: {}\r\n\r\n",
+                    BOUNDARY, name, filename, content_type
+                )
+                .as_bytes(),
+            );
+            body.extend_from_slice(data);
+            body.extend_from_slice(b"\r\n");
+        }
+        body.extend_from_slice(format!("--{}--\r\n", BOUNDARY).as_bytes());
+        body
+    }
+
+    async fn form_data(body: Vec<u8>) -> FormData {
+        warp::test::request()
+            .method("POST")
+            .header(
+                "content-type",
+                format!("multipart/form-data; boundary={}", BOUNDARY),
+            )
+            .body(body)
+            .filter(&warp::multipart::form())
+            .await
+            .unwrap()
+    }
+
+    #[test]
+    fn budget_allows_uploads_up_to_the_limit() {
+        let mut budget = UploadBudget {
+            max_bytes: 10,
+            used: 0,
+        };
+        assert!(budget.consume(4).is_ok());
+        assert!(budget.consume(6).is_ok());
+        assert_eq!(budget.used, 10);
+    }
+
+    #[test]
+    fn budget_rejects_bytes_past_the_limit() {
+        let mut budget = UploadBudget {
+            max_bytes: 10,
+            used: 0,
+        };
+        assert!(budget.consume(8).is_ok());
+        let err = budget.consume(3).unwrap_err();
+        assert_eq!(err.status, StatusCode::PAYLOAD_TOO_LARGE);
+        assert!(err.message.contains("10 bytes"));
+    }
+
+    #[test]
+    fn accepts_matching_or_generic_content_types() {
+        assert!(check_media_type("audio", Some("audio/mpeg"), "mp3", "audio/").is_ok());
+        assert!(
+            check_media_type("audio", Some("application/octet-stream"), "flac", "audio/").is_ok()
+        );
+        assert!(check_media_type("audio", None, "m4a", "audio/").is_ok());
+        assert!(check_media_type("thumbnail", Some("image/png"), "png", "image/").is_ok());
+    }
+
+    #[test]
+    fn rejects_extensions_outside_the_media_type() {
+        let err = check_media_type("audio", Some("audio/mpeg"), "jpg", "audio/").unwrap_err();
+        assert_eq!(err.status, StatusCode::UNSUPPORTED_MEDIA_TYPE);
+        assert_eq!(err.message, "Unsupported audio file: .jpg");
+
+        let err = check_media_type("thumbnail", None, "bin", "image/").unwrap_err();
+        assert_eq!(err.status, StatusCode::UNSUPPORTED_MEDIA_TYPE);
+    }
+
+    #[test]
+    fn rejects_mismatched_content_types() {
+        let err = check_media_type("audio", Some("image/png"), "mp3", "audio/").unwrap_err();
+        assert_eq!(err.status, StatusCode::UNSUPPORTED_MEDIA_TYPE);
+        assert_eq!(err.message, "Unsupported audio file: image/png");
+
+        let err = check_media_type("thumbnail", Some("text/plain"), "jpg", "image/").unwrap_err();
+        assert_eq!(err.status, StatusCode::UNSUPPORTED_MEDIA_TYPE);
+    }
+
+    #[test]
+    fn normalises_file_extensions() {
+        assert_eq!(extension_from_filename(Some("Song.MP3")), "mp3");
+        assert_eq!(extension_from_filename(Some("cover.jpeg")), "jpeg");
+        assert_eq!(extension_from_filename(Some("noext")), "bin");
+        assert_eq!(extension_from_filename(Some("bad.m/p3")), "bin");
+        assert_eq!(extension_from_filename(Some("bad.mp-3")), "bin");
+        assert_eq!(extension_from_filename(None), "bin");
+    }
+
+    #[tokio::test]
+    async fn budget_spans_every_part_of_the_form() {
+        let dir = std::env::temp_dir().join(format!("push-test-{}", uuid::Uuid::new_v4()));
+        let songs_dir = dir.join("songs");
+        let thumbnails_dir = dir.join("thumbnails");
+
+        let body = multipart_body(&[
+            ("metadata", "metadata.json", "application/json", b"{}"),
+            ("audio", "song.mp3", "audio/mpeg", &[0u8; 16]),
+            ("thumbnail", "cover.png", "image/png", &[0u8; 16]),
+        ]);
+        let form = form_data(body).await;
+
+        let (partial, err) = read_push_form(form, &songs_dir, &thumbnails_dir, 24)
+            .await
+            .unwrap_err();
+        assert_eq!(err.status, StatusCode::PAYLOAD_TOO_LARGE);
+        assert!(partial.audio.is_some());
+        assert!(partial.thumbnail.is_none());
+        assert_eq!(std::fs::read_dir(&thumbnails_dir).unwrap().count(), 0);
+
+        discard_uploads(&partial, &songs_dir, &thumbnails_dir);
+        assert_eq!(std::fs::read_dir(&songs_dir).unwrap().count(), 0);
+
+        let _ = std::fs::remove_dir_all(&dir);
+    }
+
+    #[tokio::test]
+    async fn accepts_a_form_within_the_budget() {
+        let dir = std::env::temp_dir().join(format!("push-test-{}", uuid::Uuid::new_v4()));
+        let songs_dir = dir.join("songs");
+        let thumbnails_dir = dir.join("thumbnails");
+
+        let body = multipart_body(&[
+            ("metadata", "metadata.json", "application/json", b"{}"),
+            ("audio", "song.mp3", "audio/mpeg", &[0u8; 16]),
+        ]);
+        let form = form_data(body).await;
+
+        let push_form = read_push_form(form, &songs_dir, &thumbnails_dir, 18)
+            .await
+            .unwrap();
+        assert_eq!(push_form.metadata.as_deref(), Some(&b"{}"[..]));
+        assert!(push_form.audio.as_deref().unwrap().ends_with(".mp3"));
+
+        let _ = std::fs::remove_dir_all(&dir);
+    }
+}
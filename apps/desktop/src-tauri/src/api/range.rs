@@ -0,0 +1,234 @@
+const MAX_RANGES: usize = 32;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct ByteRange {
+    pub start: u64,
+    pub end: u64,
+}
+
+impl ByteRange {
+    pub fn byte_len(&self) -> u64 {
+        self.end - self.start + 1
+    }
+
+    pub fn content_range(&self, total: u64) -> String {
+        format!("bytes {}-{}/{}", self.start, self.end, total)
+    }
+}
+
+#[derive(Debug, PartialEq, Eq)]
+pub enum RangeRequest {
+    Full,
+    Partial(Vec<ByteRange>),
+    Unsatisfiable,
+}
+
+enum RangeSpec {
+    FromTo(u64, Option<u64>),
+    Suffix(u64),
+}
+
+fn parse_spec(spec: &str) -> Option<RangeSpec> {
+    let (start, end) = spec.trim().split_once('-')?;
+    let start = start.trim();
+    let end = end.trim();
+
+    if start.is_empty() {
+        return end.parse().ok().map(RangeSpec::Suffix);
+    }
+
+    let start: u64 = start.parse().ok()?;
+    if end.is_empty() {
+        return Some(RangeSpec::FromTo(start, None));
+    }
+
+    let end: u64 = end.parse().ok()?;
+    if end < start {
+        return None;
+    }
+
+    Some(RangeSpec::FromTo(start, Some(end)))
+}
+
+fn resolve_spec(spec: RangeSpec, total: u64) -> Option<ByteRange> {
+    if total == 0 {
+        return None;
+    }
+
+    match spec {
+        RangeSpec::FromTo(start, end) => {
+            if start >= total {
+                return None;
+            }
+            let end = end.map_or(total - 1, |e| e.min(total - 1));
+            Some(ByteRange { start, end })
+        }
+        RangeSpec::Suffix(0) => None,
+        RangeSpec::Suffix(len) => Some(ByteRange {
+            start: total.saturating_sub(len),
+            end: total - 1,
+        }),
+    }
+}
+
+fn coalesce(mut ranges: Vec<ByteRange>) -> Vec<ByteRange> {
+    ranges.sort_by_key(|r| r.start);
+
+    let mut merged: Vec<ByteRange> = Vec::with_capacity(ranges.len());
+    for range in ranges {
+        match merged.last_mut() {
+            Some(last) if range.start <= last.end.saturating_add(1) => {
+                last.end = last.end.max(range.end);
+            }
+            _ => merged.push(range),
+        }
+    }
+
+    merged
+}
+
+pub fn parse_range_header(header: &str, total: u64) -> RangeRequest {
+    let specs = match header.trim().strip_prefix("bytes=") {
+        Some(specs) => specs,
+        None => return RangeRequest::Full,
+    };
+
+    let mut parsed = Vec::new();
+    for spec in specs.split(',').filter(|s| !s.trim().is_empty()) {
+        match parse_spec(spec) {
+            Some(spec) => parsed.push(spec),
+            None => return RangeRequest::Full,
+        }
+    }
+
+    if parsed.is_empty() || parsed.len() > MAX_RANGES {
+        return RangeRequest::Full;
+    }
+
+    let ranges: Vec<ByteRange> = parsed
+        .into_iter()
+        .filter_map(|spec| resolve_spec(spec, total))
+        .collect();
+
+    if ranges.is_empty() {
+        return RangeRequest::Unsatisfiable;
+    }
+
+    RangeRequest::Partial(coalesce(ranges))
+}
+
+pub fn http_date(time: std::time::SystemTime) -> String {
+    chrono::DateTime::<chrono::Utc>::from(time)
+        .format("%a, %d %b %Y %H:%M:%S GMT")
+        .to_string()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn partial(ranges: &[(u64, u64)]) -> RangeRequest {
+        RangeRequest::Partial(
+            ranges
+                .iter()
+                .map(|&(start, end)| ByteRange { start, end })
+                .collect(),
+        )
+    }
+
+    #[test]
+    fn parses_closed_range() {
+        assert_eq!(parse_range_header("bytes=0-99", 1000), partial(&[(0, 99)]));
+    }
+
+    #[test]
+    fn parses_suffix_range() {
+        assert_eq!(
+            parse_range_header("bytes=-100", 1000),
+            partial(&[(900, 999)])
+        );
+        assert_eq!(
+            parse_range_header("bytes=-5000", 1000),
+            partial(&[(0, 999)])
+        );
+    }
+
+    #[test]
+    fn parses_open_ended_range() {
+        assert_eq!(
+            parse_range_header("bytes=500-", 1000),
+            partial(&[(500, 999)])
+        );
+    }
+
+    #[test]
+    fn clamps_end_to_file_length() {
+        assert_eq!(
+            parse_range_header("bytes=900-5000", 1000),
+            partial(&[(900, 999)])
+        );
+    }
+
+    #[test]
+    fn rejects_unsatisfiable_ranges() {
+        assert_eq!(
+            parse_range_header("bytes=1000-", 1000),
+            RangeRequest::Unsatisfiable
+        );
+        assert_eq!(
+            parse_range_header("bytes=-0", 1000),
+            RangeRequest::Unsatisfiable
+        );
+        assert_eq!(
+            parse_range_header("bytes=0-10", 0),
+            RangeRequest::Unsatisfiable
+        );
+    }
+
+    #[test]
+    fn ignores_malformed_headers() {
+        for header in [
+            "items=0-10",
+            "bytes=",
+            "bytes=abc",
+            "bytes=10-5",
+            "bytes=1-2-3",
+            "bytes=-",
+        ] {
+            assert_eq!(
+                parse_range_header(header, 1000),
+                RangeRequest::Full,
+                "{}",
+                header
+            );
+        }
+    }
+
+    #[test]
+    fn coalesces_multiple_ranges() {
+        assert_eq!(
+            parse_range_header("bytes=500-599, 0-99, 100-199, 550-650", 1000),
+            partial(&[(0, 199), (500, 650)])
+        );
+        assert_eq!(
+            parse_range_header("bytes=0-9, 2000-3000", 1000),
+            partial(&[(0, 9)])
+        );
+    }
+
+    #[test]
+    fn ignores_too_many_ranges() {
+        let specs: Vec<String> = (0..=MAX_RANGES)
+            .map(|i| format!("{}-{}", i * 10, i * 10))
+            .collect();
+        let header = format!("bytes={}", specs.join(","));
+        assert_eq!(parse_range_header(&header, 1000), RangeRequest::Full);
+    }
+
+    #[test]
+    fn byte_len_is_inclusive() {
+        let range = ByteRange { start: 10, end: 19 };
+        assert_eq!(range.byte_len(), 10);
+        assert_eq!(range.content_range(100), "bytes 10-19/100");
+    }
+}
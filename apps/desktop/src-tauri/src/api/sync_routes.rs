@@ -1,14 +1,22 @@
-use std::collections::HashSet;
+use std::collections::{HashMap, HashSet};
 use std::path::PathBuf;
 use std::sync::Arc;
 
+use futures_util::stream;
+
 use serde::{Deserialize, Serialize};
 
-use warp::http::StatusCode;
-use warp::Filter;
+use tokio::sync::mpsc;
+
+use warp::http::{header, Response, StatusCode};
+use warp::hyper::body::{Body, Bytes};
+use warp::{Filter, Reply};
 
-use super::auth::with_auth;
+use super::auth::{with_scope, Authenticator};
+use super::compression;
 use super::db;
+use super::sync_state::{EntityCounts, SyncPhase};
+use super::tokens::Scope;
 use super::SyncStatus;
 
 #[derive(Debug, Deserialize)]
@@ -18,6 +26,14 @@ struct CompareRequest {
     album_fingerprints: Vec<String>,
     artist_fingerprints: Vec<String>,
     playlist_fingerprints: Vec<String>,
+    #[serde(default)]
+    song_versions: Vec<db::EntityVersion>,
+    #[serde(default)]
+    album_versions: Vec<db::EntityVersion>,
+    #[serde(default)]
+    artist_versions: Vec<db::EntityVersion>,
+    #[serde(default)]
+    playlist_versions: Vec<db::EntityVersion>,
 }
 
 #[derive(Debug, Serialize)]
@@ -28,6 +44,19 @@ struct CompareResponse {
     missing_artists: Vec<String>,
     missing_playlists: Vec<String>,
     totals: CompareTotals,
+    desktop_missing_songs: Vec<String>,
+    desktop_missing_albums: Vec<String>,
+    desktop_missing_artists: Vec<String>,
+    desktop_missing_playlists: Vec<String>,
+    desktop_totals: CompareTotals,
+    updated_songs: Vec<String>,
+    updated_albums: Vec<String>,
+    updated_artists: Vec<String>,
+    updated_playlists: Vec<String>,
+    desktop_stale_songs: Vec<String>,
+    desktop_stale_albums: Vec<String>,
+    desktop_stale_artists: Vec<String>,
+    desktop_stale_playlists: Vec<String>,
 }
 
 #[derive(Debug, Serialize)]
@@ -39,6 +68,34 @@ struct CompareTotals {
     playlists: usize,
 }
 
+pub const MAX_BATCH_SIZE: usize = 10_000;
+
+const NDJSON_CONTENT_TYPE: &str = "application/x-ndjson";
+const BATCH_STREAM_BUFFER: usize = 4;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+enum BatchEntity {
+    Artists,
+    Albums,
+    Songs,
+    Playlists,
+}
+
+const BATCH_ENTITIES: [BatchEntity; 4] = [
+    BatchEntity::Artists,
+    BatchEntity::Albums,
+    BatchEntity::Songs,
+    BatchEntity::Playlists,
+];
+
+#[derive(Debug, Clone, Copy, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+struct BatchCursor {
+    entity: BatchEntity,
+    offset: usize,
+}
+
 #[derive(Debug, Deserialize)]
 #[serde(rename_all = "camelCase")]
 struct BatchRequest {
@@ -46,8 +103,38 @@ struct BatchRequest {
     album_fingerprints: Vec<String>,
     artist_fingerprints: Vec<String>,
     playlist_fingerprints: Vec<String>,
-    #[allow(dead_code)]
     batch_index: Option<u32>,
+    #[serde(default)]
+    omit_song_playlists: bool,
+    resume_after: Option<BatchCursor>,
+}
+
+impl BatchRequest {
+    fn fingerprints(&self, entity: BatchEntity) -> &[String] {
+        match entity {
+            BatchEntity::Artists => &self.artist_fingerprints,
+            BatchEntity::Albums => &self.album_fingerprints,
+            BatchEntity::Songs => &self.song_fingerprints,
+            BatchEntity::Playlists => &self.playlist_fingerprints,
+        }
+    }
+
+    fn size(&self) -> usize {
+        BATCH_ENTITIES
+            .iter()
+            .map(|entity| self.fingerprints(*entity).len())
+            .sum()
+    }
+
+    fn pending(&self, entity: BatchEntity) -> (usize, &[String]) {
+        let fingerprints = self.fingerprints(entity);
+        let skip = match self.resume_after {
+            Some(cursor) if cursor.entity > entity => fingerprints.len(),
+            Some(cursor) if cursor.entity == entity => cursor.offset.min(fingerprints.len()),
+            _ => 0,
+        };
+        (skip, &fingerprints[skip..])
+    }
 }
 
 #[derive(Debug, Serialize)]
@@ -57,6 +144,101 @@ struct BatchResponse {
     albums: Vec<db::AlbumRow>,
     artists: Vec<db::ArtistRow>,
     playlists: Vec<db::PlaylistRow>,
+    batch_index: Option<u32>,
+}
+
+impl BatchResponse {
+    fn counts(&self) -> EntityCounts {
+        EntityCounts {
+            songs: self.songs.len() as u64,
+            albums: self.albums.len() as u64,
+            artists: self.artists.len() as u64,
+            playlists: self.playlists.len() as u64,
+        }
+    }
+}
+
+#[derive(Debug, Serialize)]
+#[serde(tag = "type", rename_all = "camelCase")]
+enum BatchRecord {
+    Artists {
+        items: Vec<db::ArtistRow>,
+        cursor: BatchCursor,
+    },
+    Albums {
+        items: Vec<db::AlbumRow>,
+        cursor: BatchCursor,
+    },
+    Songs {
+        items: Vec<db::SongRow>,
+        cursor: BatchCursor,
+    },
+    Playlists {
+        items: Vec<db::PlaylistRow>,
+        cursor: BatchCursor,
+    },
+    #[serde(rename_all = "camelCase")]
+    Error {
+        error: String,
+        resume_after: Option<BatchCursor>,
+    },
+    #[serde(rename_all = "camelCase")]
+    End { batch_index: Option<u32> },
+}
+
+#[derive(Debug, Default, Deserialize)]
+#[serde(rename_all = "camelCase")]
+struct UpdateRequest {
+    #[serde(default)]
+    songs: Vec<db::SongUpdate>,
+    #[serde(default)]
+    albums: Vec<db::AlbumUpdate>,
+    #[serde(default)]
+    artists: Vec<db::ArtistUpdate>,
+    #[serde(default)]
+    playlists: Vec<db::PlaylistUpdate>,
+}
+
+#[derive(Debug, Deserialize)]
+#[serde(rename_all = "camelCase")]
+struct DeltaRequest {
+    since: Option<i64>,
+}
+
+#[derive(Debug, Serialize)]
+#[serde(rename_all = "camelCase")]
+struct DeltaResponse {
+    since: i64,
+    watermark: i64,
+    changed_songs: Vec<String>,
+    changed_albums: Vec<String>,
+    changed_artists: Vec<String>,
+    changed_playlists: Vec<String>,
+    deleted: Vec<db::Tombstone>,
+    totals: CompareTotals,
+    full_resync_required: bool,
+}
+
+#[derive(Debug, Deserialize)]
+#[serde(rename_all = "camelCase")]
+struct TombstonesRequest {
+    since: Option<i64>,
+}
+
+#[derive(Debug, Serialize)]
+#[serde(rename_all = "camelCase")]
+struct TombstonesResponse {
+    since: i64,
+    watermark: i64,
+    tombstones: Vec<db::Tombstone>,
+    retention_secs: i64,
+    full_resync_required: bool,
+}
+
+fn requires_full_resync(conn: &rusqlite::Connection, since: i64) -> Result<bool, String> {
+    let compacted_through =
+        db::get_tombstones_compacted_through(conn).map_err(|e| e.to_string())?;
+    Ok(compacted_through.is_some_and(|through| since > 0 && since <= through))
 }
 
 fn compute_missing(desktop: &[String], mobile: &[String]) -> Vec<String> {
@@ -68,12 +250,41 @@ fn compute_missing(desktop: &[String], mobile: &[String]) -> Vec<String> {
         .collect()
 }
 
+fn compute_diverged(
+    desktop: &[db::EntityVersion],
+    mobile: &[db::EntityVersion],
+) -> (Vec<String>, Vec<String>) {
+    let desktop_map: HashMap<&str, &db::EntityVersion> = desktop
+        .iter()
+        .map(|version| (version.fingerprint.as_str(), version))
+        .collect();
+
+    let mut desktop_newer = Vec::new();
+    let mut mobile_newer = Vec::new();
+
+    for mobile_version in mobile {
+        let Some(desktop_version) = desktop_map.get(mobile_version.fingerprint.as_str()) else {
+            continue;
+        };
+        if desktop_version.content_hash == mobile_version.content_hash {
+            continue;
+        }
+        if desktop_version.updated_at >= mobile_version.updated_at {
+            desktop_newer.push(mobile_version.fingerprint.clone());
+        } else {
+            mobile_newer.push(mobile_version.fingerprint.clone());
+        }
+    }
+
+    (desktop_newer, mobile_newer)
+}
+
 async fn handle_compare(
     body: CompareRequest,
     db_path: Arc<PathBuf>,
     sync_status: SyncStatus,
 ) -> Result<impl warp::Reply, warp::Rejection> {
-    sync_status.lock().unwrap().last_activity = std::time::Instant::now();
+    sync_status.touch();
     let result = tokio::task::spawn_blocking(move || {
         let conn = db::open_readonly(&db_path).map_err(|e| e.to_string())?;
 
@@ -90,6 +301,37 @@ async fn handle_compare(
         let missing_artists = compute_missing(&desktop_artists, &body.artist_fingerprints);
         let missing_playlists = compute_missing(&desktop_playlists, &body.playlist_fingerprints);
 
+        let desktop_missing_songs = compute_missing(&body.song_fingerprints, &desktop_songs);
+        let desktop_missing_albums = compute_missing(&body.album_fingerprints, &desktop_albums);
+        let desktop_missing_artists = compute_missing(&body.artist_fingerprints, &desktop_artists);
+        let desktop_missing_playlists =
+            compute_missing(&body.playlist_fingerprints, &desktop_playlists);
+
+        let (updated_songs, desktop_stale_songs) = if body.song_versions.is_empty() {
+            (Vec::new(), Vec::new())
+        } else {
+            let versions = db::get_song_versions(&conn).map_err(|e| e.to_string())?;
+            compute_diverged(&versions, &body.song_versions)
+        };
+        let (updated_albums, desktop_stale_albums) = if body.album_versions.is_empty() {
+            (Vec::new(), Vec::new())
+        } else {
+            let versions = db::get_album_versions(&conn).map_err(|e| e.to_string())?;
+            compute_diverged(&versions, &body.album_versions)
+        };
+        let (updated_artists, desktop_stale_artists) = if body.artist_versions.is_empty() {
+            (Vec::new(), Vec::new())
+        } else {
+            let versions = db::get_artist_versions(&conn).map_err(|e| e.to_string())?;
+            compute_diverged(&versions, &body.artist_versions)
+        };
+        let (updated_playlists, desktop_stale_playlists) = if body.playlist_versions.is_empty() {
+            (Vec::new(), Vec::new())
+        } else {
+            let versions = db::get_playlist_versions(&conn).map_err(|e| e.to_string())?;
+            compute_diverged(&versions, &body.playlist_versions)
+        };
+
         Ok::<CompareResponse, String>(CompareResponse {
             totals: CompareTotals {
                 songs: missing_songs.len(),
@@ -101,6 +343,24 @@ async fn handle_compare(
             missing_albums,
             missing_artists,
             missing_playlists,
+            desktop_totals: CompareTotals {
+                songs: desktop_missing_songs.len(),
+                albums: desktop_missing_albums.len(),
+                artists: desktop_missing_artists.len(),
+                playlists: desktop_missing_playlists.len(),
+            },
+            desktop_missing_songs,
+            desktop_missing_albums,
+            desktop_missing_artists,
+            desktop_missing_playlists,
+            updated_songs,
+            updated_albums,
+            updated_artists,
+            updated_playlists,
+            desktop_stale_songs,
+            desktop_stale_albums,
+            desktop_stale_artists,
+            desktop_stale_playlists,
         })
     })
     .await
@@ -118,99 +378,611 @@ async fn handle_compare(
     }
 }
 
-async fn handle_batch(
-    body: BatchRequest,
+async fn handle_delta(
+    body: DeltaRequest,
     db_path: Arc<PathBuf>,
     sync_status: SyncStatus,
 ) -> Result<impl warp::Reply, warp::Rejection> {
-    {
-        let mut data = sync_status.lock().unwrap();
-        if data.status != "completed" {
-            data.status = "syncing".to_string();
-        }
-        data.last_activity = std::time::Instant::now();
+    sync_status.touch();
+    let result = tokio::task::spawn_blocking(move || {
+        let conn = db::open_readonly(&db_path).map_err(|e| e.to_string())?;
+
+        let since = body.since.unwrap_or(0).max(0);
+        let watermark = db::current_timestamp(&conn).map_err(|e| e.to_string())?;
+
+        let changed_songs =
+            db::get_changed_fingerprints(&conn, "songs", since).map_err(|e| e.to_string())?;
+        let changed_albums =
+            db::get_changed_fingerprints(&conn, "albums", since).map_err(|e| e.to_string())?;
+        let changed_artists =
+            db::get_changed_fingerprints(&conn, "artists", since).map_err(|e| e.to_string())?;
+        let changed_playlists =
+            db::get_changed_fingerprints(&conn, "playlists", since).map_err(|e| e.to_string())?;
+        let deleted = db::get_tombstones_since(&conn, since).map_err(|e| e.to_string())?;
+        let full_resync_required = requires_full_resync(&conn, since)?;
+
+        Ok::<DeltaResponse, String>(DeltaResponse {
+            since,
+            watermark,
+            totals: CompareTotals {
+                songs: changed_songs.len(),
+                albums: changed_albums.len(),
+                artists: changed_artists.len(),
+                playlists: changed_playlists.len(),
+            },
+            changed_songs,
+            changed_albums,
+            changed_artists,
+            changed_playlists,
+            deleted,
+            full_resync_required,
+        })
+    })
+    .await
+    .map_err(|_| warp::reject::reject())?;
+
+    match result {
+        Ok(response) => Ok(warp::reply::with_status(
+            warp::reply::json(&response),
+            StatusCode::OK,
+        )),
+        Err(err) => Ok(warp::reply::with_status(
+            warp::reply::json(&serde_json::json!({ "error": err })),
+            StatusCode::INTERNAL_SERVER_ERROR,
+        )),
     }
+}
+
+async fn handle_tombstones(
+    body: TombstonesRequest,
+    db_path: Arc<PathBuf>,
+    sync_status: SyncStatus,
+) -> Result<impl warp::Reply, warp::Rejection> {
+    sync_status.touch();
     let result = tokio::task::spawn_blocking(move || {
         let conn = db::open_readonly(&db_path).map_err(|e| e.to_string())?;
 
-        let songs = db::get_songs_by_fingerprints(&conn, &body.song_fingerprints)
+        let since = body.since.unwrap_or(0).max(0);
+        let watermark = db::current_timestamp(&conn).map_err(|e| e.to_string())?;
+        let tombstones = db::get_tombstones_since(&conn, since).map_err(|e| e.to_string())?;
+        let full_resync_required = requires_full_resync(&conn, since)?;
+
+        Ok::<TombstonesResponse, String>(TombstonesResponse {
+            since,
+            watermark,
+            tombstones,
+            retention_secs: db::TOMBSTONE_RETENTION_SECS,
+            full_resync_required,
+        })
+    })
+    .await
+    .map_err(|_| warp::reject::reject())?;
+
+    match result {
+        Ok(response) => Ok(warp::reply::with_status(
+            warp::reply::json(&response),
+            StatusCode::OK,
+        )),
+        Err(err) => Ok(warp::reply::with_status(
+            warp::reply::json(&serde_json::json!({ "error": err })),
+            StatusCode::INTERNAL_SERVER_ERROR,
+        )),
+    }
+}
+
+async fn handle_update(
+    body: UpdateRequest,
+    db_path: Arc<PathBuf>,
+    sync_status: SyncStatus,
+) -> Result<impl warp::Reply, warp::Rejection> {
+    sync_status.begin_syncing();
+    let received = EntityCounts {
+        songs: body.songs.len() as u64,
+        albums: body.albums.len() as u64,
+        artists: body.artists.len() as u64,
+        playlists: body.playlists.len() as u64,
+    };
+
+    let result = tokio::task::spawn_blocking(move || {
+        let mut conn = db::open_readwrite(&db_path).map_err(|e| e.to_string())?;
+
+        let mut outcome = db::UpdateOutcome::default();
+        db::apply_artist_updates(&mut conn, &body.artists, &mut outcome)
+            .map_err(|e| e.to_string())?;
+        db::apply_album_updates(&mut conn, &body.albums, &mut outcome)
             .map_err(|e| e.to_string())?;
-        let albums = db::get_albums_by_fingerprints(&conn, &body.album_fingerprints)
+        db::apply_song_updates(&mut conn, &body.songs, &mut outcome).map_err(|e| e.to_string())?;
+        db::apply_playlist_updates(&mut conn, &body.playlists, &mut outcome)
             .map_err(|e| e.to_string())?;
-        let artists = db::get_artists_by_fingerprints(&conn, &body.artist_fingerprints)
+
+        Ok::<db::UpdateOutcome, String>(outcome)
+    })
+    .await
+    .map_err(|_| warp::reject::reject())?;
+
+    match result {
+        Ok(response) => {
+            sync_status.record_received(&received);
+            Ok(warp::reply::with_status(
+                warp::reply::json(&response),
+                StatusCode::OK,
+            ))
+        }
+        Err(err) => Ok(warp::reply::with_status(
+            warp::reply::json(&serde_json::json!({ "error": err })),
+            StatusCode::INTERNAL_SERVER_ERROR,
+        )),
+    }
+}
+
+fn load_batch_chunk(
+    conn: &rusqlite::Connection,
+    body: &BatchRequest,
+    entity: BatchEntity,
+    fingerprints: &[String],
+    cursor: BatchCursor,
+) -> rusqlite::Result<BatchRecord> {
+    Ok(match entity {
+        BatchEntity::Artists => BatchRecord::Artists {
+            items: db::get_artists_by_fingerprints(conn, fingerprints)?,
+            cursor,
+        },
+        BatchEntity::Albums => BatchRecord::Albums {
+            items: db::get_albums_by_fingerprints(conn, fingerprints)?,
+            cursor,
+        },
+        BatchEntity::Songs => BatchRecord::Songs {
+            items: db::get_songs_by_fingerprints(conn, fingerprints, !body.omit_song_playlists)?,
+            cursor,
+        },
+        BatchEntity::Playlists => BatchRecord::Playlists {
+            items: db::get_playlists_by_fingerprints(conn, fingerprints)?,
+            cursor,
+        },
+    })
+}
+
+fn send_record(tx: &mpsc::Sender<Bytes>, record: &BatchRecord) -> Option<u64> {
+    let mut line = match serde_json::to_vec(record) {
+        Ok(line) => line,
+        Err(e) => {
+            log::error!("Failed to serialize batch record: {}", e);
+            return None;
+        }
+    };
+    line.push(b'\n');
+    let len = line.len() as u64;
+    tx.blocking_send(Bytes::from(line)).ok().map(|_| len)
+}
+
+impl BatchRecord {
+    fn counts(&self) -> EntityCounts {
+        match self {
+            BatchRecord::Artists { items, .. } => EntityCounts {
+                artists: items.len() as u64,
+                ..Default::default()
+            },
+            BatchRecord::Albums { items, .. } => EntityCounts {
+                albums: items.len() as u64,
+                ..Default::default()
+            },
+            BatchRecord::Songs { items, .. } => EntityCounts {
+                songs: items.len() as u64,
+                ..Default::default()
+            },
+            BatchRecord::Playlists { items, .. } => EntityCounts {
+                playlists: items.len() as u64,
+                ..Default::default()
+            },
+            BatchRecord::Error { .. } | BatchRecord::End { .. } => EntityCounts::default(),
+        }
+    }
+}
+
+fn stream_batch(
+    body: &BatchRequest,
+    db_path: &std::path::Path,
+    sync_status: &SyncStatus,
+    tx: &mpsc::Sender<Bytes>,
+) {
+    let mut resume_after = body.resume_after;
+
+    let conn = match db::open_readonly(db_path) {
+        Ok(conn) => conn,
+        Err(e) => {
+            send_record(
+                tx,
+                &BatchRecord::Error {
+                    error: e.to_string(),
+                    resume_after,
+                },
+            );
+            return;
+        }
+    };
+
+    for entity in BATCH_ENTITIES {
+        let (mut offset, pending) = body.pending(entity);
+
+        for chunk in pending.chunks(db::MAX_SQL_VARIABLES) {
+            offset += chunk.len();
+            let cursor = BatchCursor { entity, offset };
+
+            let record = match load_batch_chunk(&conn, body, entity, chunk, cursor) {
+                Ok(record) => record,
+                Err(e) => {
+                    send_record(
+                        tx,
+                        &BatchRecord::Error {
+                            error: e.to_string(),
+                            resume_after,
+                        },
+                    );
+                    return;
+                }
+            };
+
+            let Some(bytes) = send_record(tx, &record) else {
+                return;
+            };
+            resume_after = Some(cursor);
+            sync_status.record_sent(&record.counts(), bytes);
+        }
+    }
+
+    let end = BatchRecord::End {
+        batch_index: body.batch_index,
+    };
+    if let Some(bytes) = send_record(tx, &end) {
+        sync_status.record_batch_sent(&EntityCounts::default(), bytes);
+    }
+}
+
+fn stream_batch_response(
+    body: BatchRequest,
+    db_path: Arc<PathBuf>,
+    sync_status: SyncStatus,
+) -> Response<Body> {
+    let (tx, rx) = mpsc::channel::<Bytes>(BATCH_STREAM_BUFFER);
+
+    tokio::task::spawn_blocking(move || stream_batch(&body, &db_path, &sync_status, &tx));
+
+    let records = stream::unfold(rx, |mut rx| async move {
+        rx.recv()
+            .await
+            .map(|line| (Ok::<_, std::io::Error>(line), rx))
+    });
+
+    Response::builder()
+        .status(StatusCode::OK)
+        .header(header::CONTENT_TYPE, NDJSON_CONTENT_TYPE)
+        .header(header::CACHE_CONTROL, "no-store")
+        .body(Body::wrap_stream(records))
+        .unwrap_or_default()
+}
+
+async fn handle_batch(
+    accept: Option<String>,
+    body: BatchRequest,
+    db_path: Arc<PathBuf>,
+    sync_status: SyncStatus,
+) -> Result<Response<Body>, warp::Rejection> {
+    sync_status.begin_syncing();
+
+    if body.size() > MAX_BATCH_SIZE {
+        return Ok(warp::reply::with_status(
+            warp::reply::json(&serde_json::json!({
+                "error": format!("Batch exceeds {} fingerprints", MAX_BATCH_SIZE),
+                "maxBatchSize": MAX_BATCH_SIZE
+            })),
+            StatusCode::PAYLOAD_TOO_LARGE,
+        )
+        .into_response());
+    }
+
+    sync_status.record_batch(body.batch_index);
+
+    if accept
+        .as_deref()
+        .is_some_and(|accept| accept.contains(NDJSON_CONTENT_TYPE))
+    {
+        return Ok(stream_batch_response(body, db_path, sync_status));
+    }
+
+    let result = tokio::task::spawn_blocking(move || {
+        let conn = db::open_readonly(&db_path).map_err(|e| e.to_string())?;
+
+        let songs = db::get_songs_by_fingerprints(
+            &conn,
+            body.pending(BatchEntity::Songs).1,
+            !body.omit_song_playlists,
+        )
+        .map_err(|e| e.to_string())?;
+        let albums = db::get_albums_by_fingerprints(&conn, body.pending(BatchEntity::Albums).1)
             .map_err(|e| e.to_string())?;
-        let playlists = db::get_playlists_by_fingerprints(&conn, &body.playlist_fingerprints)
+        let artists = db::get_artists_by_fingerprints(&conn, body.pending(BatchEntity::Artists).1)
             .map_err(|e| e.to_string())?;
+        let playlists =
+            db::get_playlists_by_fingerprints(&conn, body.pending(BatchEntity::Playlists).1)
+                .map_err(|e| e.to_string())?;
 
         Ok::<BatchResponse, String>(BatchResponse {
             songs,
             albums,
             artists,
             playlists,
+            batch_index: body.batch_index,
         })
     })
     .await
     .map_err(|_| warp::reject::reject())?;
 
-    match result {
-        Ok(response) => Ok(warp::reply::with_status(
-            warp::reply::json(&response),
-            StatusCode::OK,
-        )),
+    match result.and_then(|response| {
+        serde_json::to_vec(&response)
+            .map(|json| (response.counts(), json))
+            .map_err(|e| e.to_string())
+    }) {
+        Ok((counts, json)) => {
+            sync_status.record_batch_sent(&counts, json.len() as u64);
+            Ok(Response::builder()
+                .status(StatusCode::OK)
+                .header(header::CONTENT_TYPE, "application/json")
+                .body(Body::from(json))
+                .unwrap_or_default())
+        }
         Err(err) => Ok(warp::reply::with_status(
             warp::reply::json(&serde_json::json!({ "error": err })),
             StatusCode::INTERNAL_SERVER_ERROR,
-        )),
+        )
+        .into_response()),
+    }
+}
+
+fn finish_sync(
+    sync_status: &SyncStatus,
+    phase: SyncPhase,
+) -> warp::reply::WithStatus<warp::reply::Json> {
+    match sync_status.transition(phase) {
+        Ok(()) => warp::reply::with_status(
+            warp::reply::json(&serde_json::json!({ "status": phase })),
+            StatusCode::OK,
+        ),
+        Err(err) => warp::reply::with_status(
+            warp::reply::json(&serde_json::json!({
+                "error": err,
+                "status": sync_status.phase()
+            })),
+            StatusCode::CONFLICT,
+        ),
     }
 }
 
 pub fn sync_routes(
-    token: Arc<String>,
+    auth: Arc<Authenticator>,
     db_path: Arc<PathBuf>,
     sync_status: SyncStatus,
 ) -> impl Filter<Extract = (impl warp::Reply,), Error = warp::Rejection> + Clone {
     let db_path_compare = db_path.clone();
+    let db_path_delta = db_path.clone();
+    let db_path_tombstones = db_path.clone();
+    let db_path_update = db_path.clone();
     let db_path_batch = db_path;
     let status_compare = sync_status.clone();
+    let status_delta = sync_status.clone();
+    let status_tombstones = sync_status.clone();
+    let status_update = sync_status.clone();
     let status_batch = sync_status.clone();
     let status_complete = sync_status.clone();
     let status_abort = sync_status;
 
     let compare = warp::path!("api" / "sync" / "compare")
         .and(warp::post())
-        .and(with_auth(token.clone()))
-        .and(warp::body::json())
+        .and(with_scope(auth.clone(), Scope::SyncRead))
+        .and(compression::json_body())
         .and(warp::any().map(move || db_path_compare.clone()))
         .and(warp::any().map(move || status_compare.clone()))
         .and_then(handle_compare);
 
+    let delta = warp::path!("api" / "sync" / "delta")
+        .and(warp::post())
+        .and(with_scope(auth.clone(), Scope::SyncRead))
+        .and(compression::json_body())
+        .and(warp::any().map(move || db_path_delta.clone()))
+        .and(warp::any().map(move || status_delta.clone()))
+        .and_then(handle_delta);
+
+    let tombstones = warp::path!("api" / "sync" / "tombstones")
+        .and(warp::post())
+        .and(with_scope(auth.clone(), Scope::SyncRead))
+        .and(compression::json_body())
+        .and(warp::any().map(move || db_path_tombstones.clone()))
+        .and(warp::any().map(move || status_tombstones.clone()))
+        .and_then(handle_tombstones);
+
+    let update = warp::path!("api" / "sync" / "update")
+        .and(warp::post())
+        .and(with_scope(auth.clone(), Scope::SyncWrite))
+        .and(compression::json_body())
+        .and(warp::any().map(move || db_path_update.clone()))
+        .and(warp::any().map(move || status_update.clone()))
+        .and_then(handle_update);
+
     let batch = warp::path!("api" / "sync" / "batch")
         .and(warp::post())
-        .and(with_auth(token.clone()))
-        .and(warp::body::json())
+        .and(with_scope(auth.clone(), Scope::SyncRead))
+        .and(warp::header::optional::<String>("accept"))
+        .and(compression::json_body())
         .and(warp::any().map(move || db_path_batch.clone()))
         .and(warp::any().map(move || status_batch.clone()))
         .and_then(handle_batch);
 
     let complete = warp::path!("api" / "sync" / "complete")
         .and(warp::post())
-        .and(with_auth(token.clone()))
-        .map(move || {
-            let mut data = status_complete.lock().unwrap();
-            data.status = "completed".to_string();
-            data.last_activity = std::time::Instant::now();
-            warp::reply::json(&serde_json::json!({ "status": "completed" }))
-        });
+        .and(with_scope(auth.clone(), Scope::SyncWrite))
+        .map(move || finish_sync(&status_complete, SyncPhase::Completed));
 
     let abort = warp::path!("api" / "sync" / "abort")
         .and(warp::post())
-        .and(with_auth(token))
-        .map(move || {
-            let mut data = status_abort.lock().unwrap();
-            data.status = "cancelled".to_string();
-            data.last_activity = std::time::Instant::now();
-            warp::reply::json(&serde_json::json!({ "status": "cancelled" }))
-        });
-
-    compare.or(batch).or(complete).or(abort)
+        .and(with_scope(auth, Scope::SyncWrite))
+        .map(move || finish_sync(&status_abort, SyncPhase::Cancelled));
+
+    compare
+        .or(delta)
+        .or(tombstones)
+        .or(batch)
+        .or(update)
+        .or(complete)
+        .or(abort)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    use serde_json::{json, Value};
+
+    fn fingerprints(prefix: &str, count: usize) -> Vec<String> {
+        (0..count).map(|i| format!("{}{}", prefix, i)).collect()
+    }
+
+    fn request(artists: usize, songs: usize, resume_after: Option<BatchCursor>) -> BatchRequest {
+        BatchRequest {
+            song_fingerprints: fingerprints("song", songs),
+            album_fingerprints: vec![],
+            artist_fingerprints: fingerprints("artist", artists),
+            playlist_fingerprints: vec![],
+            batch_index: Some(3),
+            omit_song_playlists: false,
+            resume_after,
+        }
+    }
+
+    fn cursor(entity: BatchEntity, offset: usize) -> Option<BatchCursor> {
+        Some(BatchCursor { entity, offset })
+    }
+
+    fn artists_db(fingerprints: &[&str]) -> (PathBuf, PathBuf) {
+        let dir = std::env::temp_dir().join(format!("sync-test-{}", uuid::Uuid::new_v4()));
+        std::fs::create_dir_all(&dir).unwrap();
+        let db_path = db::resolve_db_path(&dir);
+
+        let conn = db::open_readwrite(&db_path).unwrap();
+        conn.execute_batch(
+            "CREATE TABLE artists (fingerprint TEXT, name TEXT, is_favorite INTEGER, \
+             thumbnail TEXT, updated_at INTEGER)",
+        )
+        .unwrap();
+        for fingerprint in fingerprints {
+            conn.execute(
+                "INSERT INTO artists (fingerprint, name, is_favorite, thumbnail, updated_at) \
+                 VALUES (?1, 'Artist', 0, NULL, 1)",
+                [fingerprint],
+            )
+            .unwrap();
+        }
+
+        (dir, db_path)
+    }
+
+    fn run_batch(body: &BatchRequest, db_path: &std::path::Path) -> Vec<Value> {
+        let (tx, mut rx) = mpsc::channel::<Bytes>(64);
+        stream_batch(body, db_path, &SyncStatus::default(), &tx);
+        drop(tx);
+
+        let mut records = Vec::new();
+        while let Ok(line) = rx.try_recv() {
+            records.push(serde_json::from_slice(&line).unwrap());
+        }
+        records
+    }
+
+    #[test]
+    fn pending_starts_at_the_beginning_without_a_cursor() {
+        let body = request(3, 2, None);
+        assert_eq!(
+            body.pending(BatchEntity::Artists),
+            (0, &body.artist_fingerprints[..])
+        );
+        assert_eq!(
+            body.pending(BatchEntity::Songs),
+            (0, &body.song_fingerprints[..])
+        );
+        assert_eq!(body.size(), 5);
+    }
+
+    #[test]
+    fn pending_resumes_after_the_cursor() {
+        let body = request(3, 4, cursor(BatchEntity::Songs, 1));
+        assert_eq!(body.pending(BatchEntity::Artists), (3, &[][..]));
+        assert_eq!(body.pending(BatchEntity::Albums), (0, &[][..]));
+        assert_eq!(
+            body.pending(BatchEntity::Songs),
+            (1, &body.song_fingerprints[1..])
+        );
+        assert_eq!(body.pending(BatchEntity::Playlists), (0, &[][..]));
+    }
+
+    #[test]
+    fn pending_clamps_an_offset_past_the_end() {
+        let body = request(3, 0, cursor(BatchEntity::Artists, 10));
+        assert_eq!(body.pending(BatchEntity::Artists), (3, &[][..]));
+    }
+
+    #[test]
+    fn streams_fingerprints_in_sql_variable_sized_chunks() {
+        let (dir, db_path) = artists_db(&["artist0", "artist700"]);
+        let body = request(db::MAX_SQL_VARIABLES * 2 + 1, 0, None);
+
+        let records = run_batch(&body, &db_path);
+        let _ = std::fs::remove_dir_all(&dir);
+
+        assert_eq!(records.len(), 4);
+        for (record, offset) in records.iter().zip([500, 1000, 1001]) {
+            assert_eq!(record["type"], "artists");
+            assert_eq!(
+                record["cursor"],
+                json!({ "entity": "artists", "offset": offset })
+            );
+        }
+        assert_eq!(records[0]["items"][0]["fingerprint"], "artist0");
+        assert_eq!(records[1]["items"][0]["fingerprint"], "artist700");
+        assert_eq!(records[2]["items"], json!([]));
+        assert_eq!(records[3], json!({ "type": "end", "batchIndex": 3 }));
+    }
+
+    #[test]
+    fn resumes_streaming_after_the_last_acknowledged_chunk() {
+        let (dir, db_path) = artists_db(&["artist0", "artist700"]);
+        let body = request(
+            db::MAX_SQL_VARIABLES * 2 + 1,
+            0,
+            cursor(BatchEntity::Artists, db::MAX_SQL_VARIABLES),
+        );
+
+        let records = run_batch(&body, &db_path);
+        let _ = std::fs::remove_dir_all(&dir);
+
+        assert_eq!(records.len(), 3);
+        assert_eq!(records[0]["cursor"]["offset"], 1000);
+        assert_eq!(records[0]["items"][0]["fingerprint"], "artist700");
+        assert_eq!(records[1]["cursor"]["offset"], 1001);
+        assert_eq!(records[2]["type"], "end");
+    }
+
+    #[test]
+    fn reports_the_last_cursor_when_a_chunk_fails() {
+        let (dir, db_path) = artists_db(&[]);
+        let body = request(db::MAX_SQL_VARIABLES + 1, 1, None);
+
+        let records = run_batch(&body, &db_path);
+        let _ = std::fs::remove_dir_all(&dir);
+
+        assert_eq!(records.len(), 3);
+        assert_eq!(records[1]["cursor"]["offset"], 501);
+        assert_eq!(records[2]["type"], "error");
+        assert_eq!(
+            records[2]["resumeAfter"],
+            json!({ "entity": "artists", "offset": 501 })
+        );
+    }
 }
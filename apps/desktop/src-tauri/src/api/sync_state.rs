@@ -0,0 +1,312 @@
+use std::sync::Mutex;
+use std::time::{Duration, Instant};
+
+use serde::Serialize;
+
+use super::EventSink;
+
+pub const SYNC_STATE_EVENT: &str = "sync-state";
+pub const SYNC_PROGRESS_EVENT: &str = "sync-progress";
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub enum SyncPhase {
+    Waiting,
+    Connected,
+    Syncing,
+    Completed,
+    Cancelled,
+    TimedOut,
+}
+
+impl SyncPhase {
+    pub fn as_str(self) -> &'static str {
+        match self {
+            SyncPhase::Waiting => "waiting",
+            SyncPhase::Connected => "connected",
+            SyncPhase::Syncing => "syncing",
+            SyncPhase::Completed => "completed",
+            SyncPhase::Cancelled => "cancelled",
+            SyncPhase::TimedOut => "timedOut",
+        }
+    }
+
+    pub fn is_active(self) -> bool {
+        matches!(self, SyncPhase::Connected | SyncPhase::Syncing)
+    }
+
+    pub fn is_finished(self) -> bool {
+        matches!(
+            self,
+            SyncPhase::Completed | SyncPhase::Cancelled | SyncPhase::TimedOut
+        )
+    }
+
+    pub fn can_move_to(self, next: SyncPhase) -> bool {
+        use SyncPhase::*;
+
+        matches!(
+            (self, next),
+            (Waiting, Connected | Syncing | Completed | Cancelled)
+                | (Connected, Syncing | Completed | Cancelled | TimedOut)
+                | (Syncing, Completed | Cancelled | TimedOut)
+                | (TimedOut, Connected | Syncing | Completed | Cancelled)
+                | (Completed | Cancelled, Connected | Syncing)
+        )
+    }
+}
+
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct EntityCounts {
+    pub songs: u64,
+    pub albums: u64,
+    pub artists: u64,
+    pub playlists: u64,
+}
+
+impl EntityCounts {
+    pub fn total(&self) -> u64 {
+        self.songs + self.albums + self.artists + self.playlists
+    }
+
+    fn add(&mut self, other: &EntityCounts) {
+        self.songs += other.songs;
+        self.albums += other.albums;
+        self.artists += other.artists;
+        self.playlists += other.playlists;
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct SyncDevice {
+    pub id: String,
+    pub name: String,
+}
+
+#[derive(Debug, Clone, Default, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct SyncProgress {
+    pub bytes_sent: u64,
+    pub sent: EntityCounts,
+    pub received: EntityCounts,
+    pub batch_index: Option<u32>,
+    pub batches_sent: u64,
+}
+
+#[derive(Debug, Clone, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct SyncSnapshot {
+    pub status: SyncPhase,
+    pub device: Option<SyncDevice>,
+    pub progress: SyncProgress,
+    pub idle_secs: u64,
+}
+
+struct SyncStateData {
+    phase: SyncPhase,
+    last_activity: Instant,
+    device: Option<SyncDevice>,
+    progress: SyncProgress,
+}
+
+impl SyncStateData {
+    fn enter(&mut self, next: SyncPhase) {
+        if self.phase.is_finished() && next.is_active() {
+            self.progress = SyncProgress::default();
+        }
+        self.phase = next;
+    }
+
+    fn snapshot(&self) -> SyncSnapshot {
+        SyncSnapshot {
+            status: self.phase,
+            device: self.device.clone(),
+            progress: self.progress.clone(),
+            idle_secs: self.last_activity.elapsed().as_secs(),
+        }
+    }
+}
+
+pub struct SyncState {
+    data: Mutex<SyncStateData>,
+    events: Mutex<Option<EventSink>>,
+}
+
+impl Default for SyncState {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl SyncState {
+    pub fn new() -> Self {
+        Self {
+            data: Mutex::new(SyncStateData {
+                phase: SyncPhase::Waiting,
+                last_activity: Instant::now(),
+                device: None,
+                progress: SyncProgress::default(),
+            }),
+            events: Mutex::new(None),
+        }
+    }
+
+    pub fn attach(&self, events: EventSink) {
+        *self.events.lock().unwrap() = Some(events);
+    }
+
+    pub fn detach(&self) {
+        *self.events.lock().unwrap() = None;
+    }
+
+    fn emit(&self, event: &str, snapshot: SyncSnapshot) {
+        let events = self.events.lock().unwrap().clone();
+        if let Some(events) = events {
+            match serde_json::to_value(&snapshot) {
+                Ok(payload) => events(event, payload),
+                Err(e) => log::warn!("Failed to encode {}: {}", event, e),
+            }
+        }
+    }
+
+    fn update<F>(&self, event: &str, apply: F)
+    where
+        F: FnOnce(&mut SyncStateData) -> bool,
+    {
+        let snapshot = {
+            let mut data = self.data.lock().unwrap();
+            data.last_activity = Instant::now();
+            if !apply(&mut data) {
+                return;
+            }
+            data.snapshot()
+        };
+        self.emit(event, snapshot);
+    }
+
+    pub fn reset(&self) {
+        self.update(SYNC_STATE_EVENT, |data| {
+            data.phase = SyncPhase::Waiting;
+            data.device = None;
+            data.progress = SyncProgress::default();
+            true
+        });
+    }
+
+    pub fn touch(&self) {
+        self.data.lock().unwrap().last_activity = Instant::now();
+    }
+
+    pub fn phase(&self) -> SyncPhase {
+        self.data.lock().unwrap().phase
+    }
+
+    pub fn snapshot(&self) -> SyncSnapshot {
+        self.data.lock().unwrap().snapshot()
+    }
+
+    pub fn transition(&self, next: SyncPhase) -> Result<(), String> {
+        let mut result = Ok(());
+        self.update(SYNC_STATE_EVENT, |data| {
+            if data.phase == next {
+                return false;
+            }
+            if !data.phase.can_move_to(next) {
+                result = Err(format!(
+                    "Cannot move sync from {} to {}",
+                    data.phase.as_str(),
+                    next.as_str()
+                ));
+                return false;
+            }
+            data.enter(next);
+            true
+        });
+        result
+    }
+
+    pub fn begin_syncing(&self) {
+        self.update(SYNC_STATE_EVENT, |data| {
+            if !data.phase.can_move_to(SyncPhase::Syncing) {
+                return false;
+            }
+            data.enter(SyncPhase::Syncing);
+            true
+        });
+    }
+
+    pub fn mark_connected(&self) {
+        self.update(SYNC_STATE_EVENT, |data| {
+            if data.phase != SyncPhase::Waiting && !data.phase.is_finished() {
+                return false;
+            }
+            data.enter(SyncPhase::Connected);
+            true
+        });
+    }
+
+    pub fn set_device(&self, device: SyncDevice) {
+        self.update(SYNC_STATE_EVENT, |data| {
+            if data.device.as_ref() == Some(&device) {
+                return false;
+            }
+            data.device = Some(device);
+            true
+        });
+    }
+
+    pub fn expire_if_idle(&self, timeout: Duration) {
+        let snapshot = {
+            let mut data = self.data.lock().unwrap();
+            if !data.phase.is_active() || data.last_activity.elapsed() <= timeout {
+                return;
+            }
+            data.phase = SyncPhase::TimedOut;
+            data.snapshot()
+        };
+        self.emit(SYNC_STATE_EVENT, snapshot);
+    }
+
+    pub fn record_batch(&self, batch_index: Option<u32>) {
+        self.update(SYNC_PROGRESS_EVENT, |data| {
+            data.progress.batch_index = batch_index;
+            true
+        });
+    }
+
+    pub fn record_sent(&self, counts: &EntityCounts, bytes: u64) {
+        self.update(SYNC_PROGRESS_EVENT, |data| {
+            data.progress.sent.add(counts);
+            data.progress.bytes_sent += bytes;
+            true
+        });
+    }
+
+    pub fn record_batch_sent(&self, counts: &EntityCounts, bytes: u64) {
+        self.update(SYNC_PROGRESS_EVENT, |data| {
+            data.progress.sent.add(counts);
+            data.progress.bytes_sent += bytes;
+            data.progress.batches_sent += 1;
+            true
+        });
+    }
+
+    pub fn record_bytes(&self, bytes: u64) {
+        if bytes == 0 {
+            return;
+        }
+        self.record_sent(&EntityCounts::default(), bytes);
+    }
+
+    pub fn record_received(&self, counts: &EntityCounts) {
+        if counts.total() == 0 {
+            return;
+        }
+        self.update(SYNC_PROGRESS_EVENT, |data| {
+            data.progress.received.add(counts);
+            true
+        });
+    }
+}
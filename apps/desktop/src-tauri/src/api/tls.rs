@@ -0,0 +1,105 @@
+use base64::engine::general_purpose::STANDARD as BASE64;
+use base64::Engine;
+
+use sha2::{Digest, Sha256};
+
+use std::path::{Path, PathBuf};
+
+const TLS_DIR: &str = "sync-tls";
+const CERT_FILE: &str = "cert.pem";
+const KEY_FILE: &str = "key.pem";
+const SUBJECT_ALT_NAMES: [&str; 2] = ["tunno.local", "localhost"];
+
+#[derive(Clone)]
+pub struct TlsIdentity {
+    pub cert_pem: String,
+    pub key_pem: String,
+    pub fingerprint: String,
+}
+
+pub fn tls_dir(app_data_dir: &Path) -> PathBuf {
+    app_data_dir.join(TLS_DIR)
+}
+
+fn pem_to_der(pem: &str) -> Result<Vec<u8>, String> {
+    let body: String = pem
+        .lines()
+        .skip_while(|line| !line.starts_with("-----BEGIN CERTIFICATE-----"))
+        .skip(1)
+        .take_while(|line| !line.starts_with("-----END CERTIFICATE-----"))
+        .map(str::trim)
+        .collect();
+
+    if body.is_empty() {
+        return Err("Certificate PEM contains no certificate".to_string());
+    }
+
+    BASE64
+        .decode(body)
+        .map_err(|e| format!("Invalid certificate PEM: {}", e))
+}
+
+pub fn cert_fingerprint(cert_pem: &str) -> Result<String, String> {
+    let der = pem_to_der(cert_pem)?;
+    Ok(format!("{:x}", Sha256::digest(der)))
+}
+
+fn write_private(path: &Path, contents: &str) -> Result<(), String> {
+    std::fs::write(path, contents)
+        .map_err(|e| format!("Failed to write {}: {}", path.display(), e))?;
+
+    #[cfg(unix)]
+    {
+        use std::os::unix::fs::PermissionsExt;
+        std::fs::set_permissions(path, std::fs::Permissions::from_mode(0o600))
+            .map_err(|e| format!("Failed to restrict {}: {}", path.display(), e))?;
+    }
+
+    Ok(())
+}
+
+fn generate_identity(dir: &Path) -> Result<TlsIdentity, String> {
+    let names: Vec<String> = SUBJECT_ALT_NAMES.iter().map(|n| n.to_string()).collect();
+    let certified = rcgen::generate_simple_self_signed(names)
+        .map_err(|e| format!("Failed to generate certificate: {}", e))?;
+
+    let cert_pem = certified.cert.pem();
+    let key_pem = certified.key_pair.serialize_pem();
+
+    std::fs::create_dir_all(dir).map_err(|e| format!("Failed to create TLS directory: {}", e))?;
+    write_private(&dir.join(KEY_FILE), &key_pem)?;
+    std::fs::write(dir.join(CERT_FILE), &cert_pem)
+        .map_err(|e| format!("Failed to write certificate: {}", e))?;
+
+    Ok(TlsIdentity {
+        fingerprint: format!("{:x}", Sha256::digest(certified.cert.der())),
+        cert_pem,
+        key_pem,
+    })
+}
+
+pub fn load_or_create_identity(app_data_dir: &Path) -> Result<TlsIdentity, String> {
+    let dir = tls_dir(app_data_dir);
+    let cert_path = dir.join(CERT_FILE);
+    let key_path = dir.join(KEY_FILE);
+
+    if cert_path.is_file() && key_path.is_file() {
+        let cert_pem = std::fs::read_to_string(&cert_path)
+            .map_err(|e| format!("Failed to read certificate: {}", e))?;
+        let key_pem = std::fs::read_to_string(&key_path)
+            .map_err(|e| format!("Failed to read private key: {}", e))?;
+
+        match cert_fingerprint(&cert_pem) {
+            Ok(fingerprint) => {
+                return Ok(TlsIdentity {
+                    cert_pem,
+                    key_pem,
+                    fingerprint,
+                })
+            }
+            Err(e) => log::warn!("Regenerating unreadable sync certificate: {}", e),
+        }
+    }
+
+    generate_identity(&dir)
+}
@@ -0,0 +1,218 @@
+use base64::engine::general_purpose::URL_SAFE_NO_PAD as BASE64_URL;
+use base64::Engine;
+
+use hmac::{Hmac, Mac};
+
+use rusqlite::Connection;
+
+use serde::{Deserialize, Serialize};
+
+use sha2::Sha256;
+
+use super::db;
+
+type HmacSha256 = Hmac<Sha256>;
+
+const TOKEN_PREFIX: &str = "at";
+pub const MIN_TOKEN_TTL_SECS: u64 = 60;
+pub const MAX_TOKEN_TTL_SECS: u64 = 30 * 24 * 60 * 60;
+const MAX_LABEL_CHARS: usize = 64;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Serialize, Deserialize)]
+pub enum Scope {
+    #[serde(rename = "sync:read")]
+    SyncRead,
+    #[serde(rename = "sync:write")]
+    SyncWrite,
+    #[serde(rename = "files:audio")]
+    FilesAudio,
+    #[serde(rename = "files:thumbnail")]
+    FilesThumbnail,
+    #[serde(rename = "playlist:read")]
+    PlaylistRead,
+}
+
+impl Scope {
+    pub fn as_str(self) -> &'static str {
+        match self {
+            Scope::SyncRead => "sync:read",
+            Scope::SyncWrite => "sync:write",
+            Scope::FilesAudio => "files:audio",
+            Scope::FilesThumbnail => "files:thumbnail",
+            Scope::PlaylistRead => "playlist:read",
+        }
+    }
+
+    fn is_sync(self) -> bool {
+        matches!(self, Scope::SyncRead | Scope::SyncWrite)
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct AccessClaims {
+    pub id: String,
+    pub scopes: Vec<Scope>,
+    #[serde(default)]
+    pub playlists: Vec<String>,
+    #[serde(default)]
+    pub albums: Vec<String>,
+    pub expires_at: i64,
+}
+
+impl AccessClaims {
+    pub fn has_scope(&self, scope: Scope) -> bool {
+        self.scopes.contains(&scope)
+    }
+
+    pub fn is_restricted(&self) -> bool {
+        !self.playlists.is_empty() || !self.albums.is_empty()
+    }
+}
+
+#[derive(Debug, Clone, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct MintedAccessToken {
+    pub record: db::AccessTokenRecord,
+    pub token: String,
+}
+
+#[derive(Debug, Clone, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct AccessTokenRequest {
+    pub label: String,
+    pub scopes: Vec<Scope>,
+    #[serde(default)]
+    pub playlists: Vec<String>,
+    #[serde(default)]
+    pub albums: Vec<String>,
+    pub ttl_secs: u64,
+}
+
+fn signature(key: &str, payload: &str) -> HmacSha256 {
+    let mut mac =
+        HmacSha256::new_from_slice(key.as_bytes()).expect("HMAC accepts keys of any length");
+    mac.update(payload.as_bytes());
+    mac
+}
+
+fn sign(key: &str, claims: &AccessClaims) -> Result<String, String> {
+    let json =
+        serde_json::to_vec(claims).map_err(|e| format!("Failed to encode token claims: {}", e))?;
+    let payload = BASE64_URL.encode(json);
+    let sig = BASE64_URL.encode(signature(key, &payload).finalize().into_bytes());
+    Ok(format!("{}.{}.{}", TOKEN_PREFIX, payload, sig))
+}
+
+pub fn is_access_token(provided: &str) -> bool {
+    provided
+        .strip_prefix(TOKEN_PREFIX)
+        .is_some_and(|rest| rest.starts_with('.'))
+}
+
+pub fn verify(key: &str, provided: &str, now: i64) -> Option<AccessClaims> {
+    let mut parts = provided.split('.');
+    if parts.next()? != TOKEN_PREFIX {
+        return None;
+    }
+    let payload = parts.next()?;
+    let sig = BASE64_URL.decode(parts.next()?).ok()?;
+    if parts.next().is_some() {
+        return None;
+    }
+
+    signature(key, payload).verify_slice(&sig).ok()?;
+
+    let claims: AccessClaims = serde_json::from_slice(&BASE64_URL.decode(payload).ok()?).ok()?;
+    if claims.expires_at <= now {
+        return None;
+    }
+    Some(claims)
+}
+
+fn dedup(values: Vec<String>) -> Vec<String> {
+    let mut out: Vec<String> = Vec::with_capacity(values.len());
+    for value in values {
+        let value = value.trim().to_string();
+        if !value.is_empty() && !out.contains(&value) {
+            out.push(value);
+        }
+    }
+    out
+}
+
+pub fn mint(conn: &Connection, request: AccessTokenRequest) -> Result<MintedAccessToken, String> {
+    let label: String = request.label.trim().chars().take(MAX_LABEL_CHARS).collect();
+    if label.is_empty() {
+        return Err("Token label must not be empty".to_string());
+    }
+
+    let mut scopes = request.scopes;
+    scopes.sort_by_key(|scope| scope.as_str());
+    scopes.dedup();
+    if scopes.is_empty() {
+        return Err("Token must carry at least one scope".to_string());
+    }
+
+    if !(MIN_TOKEN_TTL_SECS..=MAX_TOKEN_TTL_SECS).contains(&request.ttl_secs) {
+        return Err(format!(
+            "Token lifetime must be between {} and {} seconds",
+            MIN_TOKEN_TTL_SECS, MAX_TOKEN_TTL_SECS
+        ));
+    }
+
+    let playlists = dedup(request.playlists);
+    let albums = dedup(request.albums);
+    let restricted = !playlists.is_empty() || !albums.is_empty();
+    if restricted && scopes.iter().any(|scope| scope.is_sync()) {
+        return Err(
+            "Sync scopes cannot be combined with a playlist or album allow-list".to_string(),
+        );
+    }
+
+    let missing = db::missing_fingerprints(conn, "playlists", &playlists)
+        .map_err(|e| format!("Failed to validate playlists: {}", e))?;
+    if !missing.is_empty() {
+        return Err(format!("Unknown playlists: {}", missing.join(", ")));
+    }
+    let missing = db::missing_fingerprints(conn, "albums", &albums)
+        .map_err(|e| format!("Failed to validate albums: {}", e))?;
+    if !missing.is_empty() {
+        return Err(format!("Unknown albums: {}", missing.join(", ")));
+    }
+
+    let candidate = format!(
+        "{}{}",
+        uuid::Uuid::new_v4().simple(),
+        uuid::Uuid::new_v4().simple()
+    );
+    let key = db::get_or_create_access_token_key(conn, &candidate)
+        .map_err(|e| format!("Failed to load token signing key: {}", e))?;
+
+    let claims = AccessClaims {
+        id: uuid::Uuid::new_v4().to_string(),
+        scopes,
+        playlists,
+        albums,
+        expires_at: chrono::Utc::now().timestamp() + request.ttl_secs as i64,
+    };
+    let token = sign(&key, &claims)?;
+
+    let scope_names: Vec<String> = claims
+        .scopes
+        .iter()
+        .map(|scope| scope.as_str().to_string())
+        .collect();
+    let record = db::insert_access_token(
+        conn,
+        &claims.id,
+        &label,
+        &scope_names,
+        &claims.playlists,
+        &claims.albums,
+        claims.expires_at,
+    )
+    .map_err(|e| format!("Failed to store access token: {}", e))?;
+
+    Ok(MintedAccessToken { record, token })
+}
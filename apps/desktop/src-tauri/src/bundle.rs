@@ -0,0 +1,538 @@
+use chrono::{DateTime, Utc};
+
+use serde::{Deserialize, Serialize};
+use serde_json::Value;
+
+use sha2::{Digest, Sha256};
+
+use std::collections::HashSet;
+use std::io::{self, Write};
+
+pub const MANIFEST_FILE: &str = "manifest.json";
+pub const CHECKSUMS_FILE: &str = "checksums.json";
+pub const TRACK_METADATA_FILE: &str = "metadata.json";
+
+pub const BUNDLE_FORMAT_VERSION: u32 = 3;
+pub const MIN_BUNDLE_FORMAT_VERSION: u32 = 1;
+
+pub const CHECKSUMS_VERSION: u32 = 1;
+pub const CHECKSUMS_ALGORITHM: &str = "sha256";
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct ChecksumEntry {
+    pub path: String,
+    pub size: u64,
+    pub sha256: String,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct BundleChecksums {
+    pub version: u32,
+    pub algorithm: String,
+    pub files: Vec<ChecksumEntry>,
+}
+
+impl BundleChecksums {
+    pub fn new() -> Self {
+        Self {
+            version: CHECKSUMS_VERSION,
+            algorithm: CHECKSUMS_ALGORITHM.to_string(),
+            files: Vec::new(),
+        }
+    }
+}
+
+impl Default for BundleChecksums {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+pub struct HashingWriter<'a, W: Write> {
+    inner: &'a mut W,
+    hasher: Sha256,
+    size: u64,
+}
+
+impl<'a, W: Write> HashingWriter<'a, W> {
+    pub fn new(inner: &'a mut W) -> Self {
+        Self {
+            inner,
+            hasher: Sha256::new(),
+            size: 0,
+        }
+    }
+
+    pub fn finish(self, path: &str) -> ChecksumEntry {
+        ChecksumEntry {
+            path: path.to_string(),
+            size: self.size,
+            sha256: format!("{:x}", self.hasher.finalize()),
+        }
+    }
+}
+
+impl<W: Write> Write for HashingWriter<'_, W> {
+    fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+        let written = self.inner.write(buf)?;
+        self.hasher.update(&buf[..written]);
+        self.size += written as u64;
+        Ok(written)
+    }
+
+    fn flush(&mut self) -> io::Result<()> {
+        self.inner.flush()
+    }
+}
+
+pub fn track_entry_dir(path: &str) -> Option<&str> {
+    let rest = path.strip_prefix("tracks/")?;
+    let (dir_name, _) = rest.split_once('/')?;
+    (!dir_name.is_empty()).then_some(dir_name)
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub enum BundleOrigin {
+    Cli,
+    Desktop,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct ManifestSource {
+    #[serde(rename = "type")]
+    pub origin: BundleOrigin,
+    pub version: String,
+    pub os: String,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct ManifestStats {
+    pub total_tracks: usize,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct ManifestTrack {
+    pub dir_name: String,
+    pub title: String,
+    pub artists: Vec<String>,
+    pub album: String,
+    pub thumbnail: Option<String>,
+    pub audio_file: String,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct ManifestPlaylist {
+    pub name: String,
+    pub tracks: Vec<String>,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct BundleManifest {
+    pub version: u32,
+    pub created_at: DateTime<Utc>,
+    pub source: ManifestSource,
+    pub stats: ManifestStats,
+    pub tracks: Vec<ManifestTrack>,
+    pub playlists: Vec<ManifestPlaylist>,
+}
+
+#[derive(Debug, Clone, Deserialize)]
+#[serde(rename_all = "camelCase")]
+struct ManifestTrackV1 {
+    dir_name: String,
+    title: String,
+    artists: Vec<String>,
+    album: String,
+    thumbnail: Option<String>,
+}
+
+#[derive(Debug, Clone, Deserialize)]
+#[serde(rename_all = "camelCase")]
+struct BundleManifestV1 {
+    created_at: DateTime<Utc>,
+    source: ManifestSource,
+    stats: ManifestStats,
+    tracks: Vec<ManifestTrackV1>,
+}
+
+#[derive(Debug, Clone, Deserialize)]
+#[serde(rename_all = "camelCase")]
+struct BundleManifestV2 {
+    created_at: DateTime<Utc>,
+    source: ManifestSource,
+    stats: ManifestStats,
+    tracks: Vec<ManifestTrack>,
+}
+
+fn migrate_v1<F>(
+    manifest: BundleManifestV1,
+    mut audio_file_for: F,
+) -> Result<BundleManifestV2, String>
+where
+    F: FnMut(&str) -> Result<String, String>,
+{
+    let tracks = manifest
+        .tracks
+        .into_iter()
+        .map(|track| {
+            Ok(ManifestTrack {
+                audio_file: audio_file_for(&track.dir_name)?,
+                dir_name: track.dir_name,
+                title: track.title,
+                artists: track.artists,
+                album: track.album,
+                thumbnail: track.thumbnail.filter(|thumbnail| !thumbnail.is_empty()),
+            })
+        })
+        .collect::<Result<Vec<_>, String>>()?;
+
+    Ok(BundleManifestV2 {
+        created_at: manifest.created_at,
+        source: manifest.source,
+        stats: manifest.stats,
+        tracks,
+    })
+}
+
+fn migrate_v2(manifest: BundleManifestV2) -> BundleManifest {
+    BundleManifest {
+        version: 3,
+        created_at: manifest.created_at,
+        source: manifest.source,
+        stats: manifest.stats,
+        tracks: manifest.tracks,
+        playlists: Vec::new(),
+    }
+}
+
+fn is_plain_file_name(name: &str) -> bool {
+    !name.is_empty()
+        && name != "."
+        && name != ".."
+        && !name.contains(['/', '\\'])
+        && !name.chars().any(char::is_control)
+}
+
+impl BundleManifest {
+    pub fn parse<F>(json: &str, audio_file_for: F) -> Result<Self, String>
+    where
+        F: FnMut(&str) -> Result<String, String>,
+    {
+        let value: Value =
+            serde_json::from_str(json).map_err(|e| format!("Invalid bundle manifest: {}", e))?;
+        let version = value
+            .get("version")
+            .and_then(Value::as_u64)
+            .ok_or_else(|| "Bundle manifest has no format version".to_string())?;
+
+        if version > BUNDLE_FORMAT_VERSION as u64 {
+            return Err(format!(
+                "Bundle format version {} is newer than this app supports (up to {}). Please update Tunno.",
+                version, BUNDLE_FORMAT_VERSION
+            ));
+        }
+        if version < MIN_BUNDLE_FORMAT_VERSION as u64 {
+            return Err(format!(
+                "Bundle format version {} is no longer supported (minimum {})",
+                version, MIN_BUNDLE_FORMAT_VERSION
+            ));
+        }
+
+        let manifest = match version {
+            1 => {
+                let manifest: BundleManifestV1 = serde_json::from_value(value)
+                    .map_err(|e| format!("Invalid version 1 bundle manifest: {}", e))?;
+                migrate_v2(migrate_v1(manifest, audio_file_for)?)
+            }
+            2 => {
+                let manifest: BundleManifestV2 = serde_json::from_value(value)
+                    .map_err(|e| format!("Invalid version 2 bundle manifest: {}", e))?;
+                migrate_v2(manifest)
+            }
+            _ => serde_json::from_value(value)
+                .map_err(|e| format!("Invalid version {} bundle manifest: {}", version, e))?,
+        };
+
+        manifest.validate()?;
+        Ok(manifest)
+    }
+
+    pub fn validate(&self) -> Result<(), String> {
+        if self.version != BUNDLE_FORMAT_VERSION {
+            return Err(format!(
+                "Bundle manifest must be format version {}, found {}",
+                BUNDLE_FORMAT_VERSION, self.version
+            ));
+        }
+        if self.tracks.is_empty() {
+            return Err("Bundle manifest lists no tracks".to_string());
+        }
+        if self.stats.total_tracks != self.tracks.len() {
+            return Err(format!(
+                "Bundle manifest counts {} tracks but lists {}",
+                self.stats.total_tracks,
+                self.tracks.len()
+            ));
+        }
+
+        let mut dir_names = HashSet::new();
+        for (index, track) in self.tracks.iter().enumerate() {
+            let position = index + 1;
+            if !is_plain_file_name(&track.dir_name) {
+                return Err(format!(
+                    "Track {} has an invalid directory name {:?}",
+                    position, track.dir_name
+                ));
+            }
+            if !dir_names.insert(track.dir_name.as_str()) {
+                return Err(format!(
+                    "Track {} reuses directory {:?}",
+                    position, track.dir_name
+                ));
+            }
+            if track.title.trim().is_empty() {
+                return Err(format!("Track {:?} is missing a title", track.dir_name));
+            }
+            if track.artists.iter().all(|artist| artist.trim().is_empty()) {
+                return Err(format!("Track {:?} has no artists", track.dir_name));
+            }
+            if track.album.trim().is_empty() {
+                return Err(format!("Track {:?} is missing an album", track.dir_name));
+            }
+            if !is_plain_file_name(&track.audio_file) {
+                return Err(format!(
+                    "Track {:?} has an invalid audio file name {:?}",
+                    track.dir_name, track.audio_file
+                ));
+            }
+        }
+
+        for (index, playlist) in self.playlists.iter().enumerate() {
+            if playlist.name.trim().is_empty() {
+                return Err(format!("Playlist {} is missing a name", index + 1));
+            }
+            if let Some(dir_name) = playlist
+                .tracks
+                .iter()
+                .find(|dir_name| !dir_names.contains(dir_name.as_str()))
+            {
+                return Err(format!(
+                    "Playlist {:?} references unknown track {:?}",
+                    playlist.name, dir_name
+                ));
+            }
+        }
+
+        Ok(())
+    }
+
+    pub fn to_json(&self) -> Result<String, String> {
+        serde_json::to_string_pretty(self).map_err(|e| format!("Failed to encode manifest: {}", e))
+    }
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct TrackArtist {
+    pub name: String,
+    #[serde(default)]
+    pub thumbnail: Option<String>,
+    #[serde(default)]
+    pub genres: Option<Vec<String>>,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct TrackAlbum {
+    pub name: String,
+    #[serde(default)]
+    pub thumbnail: String,
+    #[serde(default)]
+    pub release_year: i32,
+    pub album_type: String,
+    #[serde(default)]
+    pub artists: Vec<TrackArtist>,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct TrackLyric {
+    pub text: String,
+    pub start_time: f64,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct TrackMetadata {
+    pub song: String,
+    pub title: String,
+    #[serde(default)]
+    pub thumbnail: String,
+    pub duration: f64,
+    pub artists: Vec<TrackArtist>,
+    pub album: TrackAlbum,
+    #[serde(default)]
+    pub lyrics: Option<Vec<TrackLyric>>,
+}
+
+impl TrackMetadata {
+    pub fn parse(json: &str, dir_name: &str) -> Result<Self, String> {
+        let metadata: TrackMetadata = serde_json::from_str(json)
+            .map_err(|e| format!("Invalid metadata for track {:?}: {}", dir_name, e))?;
+        metadata.validate(dir_name)?;
+        Ok(metadata)
+    }
+
+    pub fn validate(&self, dir_name: &str) -> Result<(), String> {
+        if !is_plain_file_name(&self.song) {
+            return Err(format!(
+                "Track {:?} references an invalid audio file {:?}",
+                dir_name, self.song
+            ));
+        }
+        if self.title.trim().is_empty() {
+            return Err(format!("Track {:?} metadata is missing a title", dir_name));
+        }
+        if !self.duration.is_finite() || self.duration < 0.0 {
+            return Err(format!(
+                "Track {:?} has an invalid duration {}",
+                dir_name, self.duration
+            ));
+        }
+        if self.artists.is_empty() {
+            return Err(format!("Track {:?} metadata lists no artists", dir_name));
+        }
+        if self
+            .artists
+            .iter()
+            .any(|artist| artist.name.trim().is_empty())
+        {
+            return Err(format!(
+                "Track {:?} metadata has an artist without a name",
+                dir_name
+            ));
+        }
+        if self.album.name.trim().is_empty() {
+            return Err(format!("Track {:?} metadata is missing an album", dir_name));
+        }
+        Ok(())
+    }
+
+    pub fn check_against(&self, track: &ManifestTrack) -> Result<(), String> {
+        if self.song != track.audio_file {
+            return Err(format!(
+                "Track {:?} lists audio file {:?} in the manifest but {:?} in its metadata",
+                track.dir_name, track.audio_file, self.song
+            ));
+        }
+        Ok(())
+    }
+
+    pub fn to_json(&self) -> Result<String, String> {
+        serde_json::to_string_pretty(self)
+            .map_err(|e| format!("Failed to encode track metadata: {}", e))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    use serde_json::json;
+
+    fn source() -> Value {
+        json!({ "type": "cli", "version": "1.0.0", "os": "linux" })
+    }
+
+    fn track(dir_name: &str) -> Value {
+        json!({
+            "dirName": dir_name,
+            "title": "Song",
+            "artists": ["Artist"],
+            "album": "Album",
+            "thumbnail": null,
+            "audioFile": "song.mp3"
+        })
+    }
+
+    fn parse(manifest: Value) -> Result<BundleManifest, String> {
+        BundleManifest::parse(&manifest.to_string(), |_| Ok("song.mp3".to_string()))
+    }
+
+    #[test]
+    fn upgrades_v1_manifest() {
+        let manifest = parse(json!({
+            "version": 1,
+            "createdAt": "2024-01-01T00:00:00Z",
+            "source": source(),
+            "stats": { "totalTracks": 1 },
+            "tracks": [{
+                "dirName": "Song",
+                "title": "Song",
+                "artists": ["Artist"],
+                "album": "Album",
+                "thumbnail": ""
+            }]
+        }))
+        .unwrap();
+
+        assert_eq!(manifest.version, BUNDLE_FORMAT_VERSION);
+        assert_eq!(manifest.tracks[0].audio_file, "song.mp3");
+        assert_eq!(manifest.tracks[0].thumbnail, None);
+        assert!(manifest.playlists.is_empty());
+    }
+
+    #[test]
+    fn upgrades_v2_manifest() {
+        let manifest = parse(json!({
+            "version": 2,
+            "createdAt": "2024-01-01T00:00:00Z",
+            "source": source(),
+            "stats": { "totalTracks": 1 },
+            "tracks": [track("Song")]
+        }))
+        .unwrap();
+
+        assert_eq!(manifest.version, BUNDLE_FORMAT_VERSION);
+        assert_eq!(manifest.tracks[0].dir_name, "Song");
+        assert!(manifest.playlists.is_empty());
+    }
+
+    #[test]
+    fn rejects_newer_manifest() {
+        let err = parse(json!({
+            "version": BUNDLE_FORMAT_VERSION + 1,
+            "createdAt": "2024-01-01T00:00:00Z",
+            "source": source(),
+            "stats": { "totalTracks": 1 },
+            "tracks": [track("Song")],
+            "playlists": []
+        }))
+        .unwrap_err();
+
+        assert!(err.contains("newer than this app supports"), "{}", err);
+    }
+
+    #[test]
+    fn rejects_traversal_dir_name() {
+        let err = parse(json!({
+            "version": BUNDLE_FORMAT_VERSION,
+            "createdAt": "2024-01-01T00:00:00Z",
+            "source": source(),
+            "stats": { "totalTracks": 1 },
+            "tracks": [track("../escape")],
+            "playlists": []
+        }))
+        .unwrap_err();
+
+        assert!(err.contains("invalid directory name"), "{}", err);
+    }
+}
@@ -3,15 +3,29 @@ use tauri::Manager;
 
 use chrono::Utc;
 
+use serde::Serialize;
+
+use sha2::{Digest, Sha256};
+
+use std::collections::{BTreeMap, BTreeSet, HashMap, HashSet};
 use std::fs::{self, File};
-use std::path::Path;
+use std::io::{self, Read, Seek};
+use std::path::{Component, Path, PathBuf};
 
 use zip::ZipArchive;
 
+use crate::api::commands::event_sink;
+use crate::bundle::{
+    track_entry_dir, BundleChecksums, BundleManifest, ChecksumEntry, TrackMetadata, CHECKSUMS_FILE,
+    MANIFEST_FILE, TRACK_METADATA_FILE,
+};
+use crate::transfer::{TransferJob, TransferStage, BUNDLE_IMPORT_PROGRESS_EVENT, COPY_CHUNK_SIZE};
+
 #[command]
 pub async fn fast_upload_copy_bundle_to_cache(
     app: tauri::AppHandle,
     bundle_path: String,
+    job_id: Option<String>,
 ) -> Result<String, String> {
     let timestamp = Utc::now().timestamp();
 
@@ -22,43 +36,113 @@ pub async fn fast_upload_copy_bundle_to_cache(
 
     let cache_dir = cache_base.join("FastUpload").join(timestamp.to_string());
 
-    fs::create_dir_all(&cache_dir)
-        .map_err(|e| format!("Failed to create cache directory: {}", e))?;
-
-    let bundle_source = Path::new(&bundle_path);
+    let bundle_source = PathBuf::from(&bundle_path);
     if !bundle_source.exists() {
         return Err("Bundle file does not exist".to_string());
     }
 
+    let mut job = TransferJob::register(job_id, BUNDLE_IMPORT_PROGRESS_EVENT, event_sink(&app))?;
+
+    let import_dir = cache_dir.clone();
+    let result = tokio::task::spawn_blocking(move || {
+        let result = import_bundle(&bundle_source, &import_dir, &mut job);
+        (result, job.is_cancelled())
+    })
+    .await
+    .map_err(|e| format!("Task failed: {}", e))?;
+
+    match result {
+        (Ok(()), _) => cache_dir
+            .to_str()
+            .ok_or_else(|| "Failed to convert path to string".to_string())
+            .map(|s| s.to_string()),
+        (Err(e), cancelled) => {
+            if cache_dir.exists() {
+                if let Err(remove_err) = fs::remove_dir_all(&cache_dir) {
+                    log::warn!("Failed to remove partial import: {}", remove_err);
+                }
+            }
+            if cancelled {
+                return Err("Bundle import was cancelled".to_string());
+            }
+            Err(e)
+        }
+    }
+}
+
+fn import_bundle(
+    bundle_source: &Path,
+    cache_dir: &Path,
+    job: &mut TransferJob,
+) -> Result<(), String> {
+    fs::create_dir_all(cache_dir)
+        .map_err(|e| format!("Failed to create cache directory: {}", e))?;
+
     let bundle_dest = cache_dir.join("bundle.zip");
-    fs::copy(bundle_source, &bundle_dest)
+    let mut buffer = vec![0u8; COPY_CHUNK_SIZE];
+
+    let mut source =
+        File::open(bundle_source).map_err(|e| format!("Failed to open bundle: {}", e))?;
+    let bundle_size = source
+        .metadata()
+        .map_err(|e| format!("Failed to read bundle size: {}", e))?
+        .len();
+    let mut dest =
+        File::create(&bundle_dest).map_err(|e| format!("Failed to copy bundle to cache: {}", e))?;
+
+    job.begin_stage(TransferStage::Copying, bundle_size);
+    job.copy(&mut source, &mut dest, &mut buffer, None)
         .map_err(|e| format!("Failed to copy bundle to cache: {}", e))?;
+    drop(dest);
 
-    extract_bundle(&bundle_dest, &cache_dir)?;
+    verify_bundle(&bundle_dest, job, &mut buffer)?.into_result()?;
+
+    extract_bundle(&bundle_dest, cache_dir, job, &mut buffer)?;
 
     fs::remove_file(&bundle_dest).map_err(|e| format!("Failed to remove bundle ZIP: {}", e))?;
 
-    cache_dir
-        .to_str()
-        .ok_or_else(|| "Failed to convert path to string".to_string())
-        .map(|s| s.to_string())
+    job.finish();
+    Ok(())
+}
+
+fn track_dir_name(path: &Path) -> Option<String> {
+    let mut components = path.components();
+    match components.next() {
+        Some(Component::Normal(first)) if first == "tracks" => {}
+        _ => return None,
+    }
+    match components.next() {
+        Some(Component::Normal(dir_name)) => dir_name.to_str().map(str::to_string),
+        _ => None,
+    }
 }
 
-fn extract_bundle(zip_path: &Path, output_dir: &Path) -> Result<(), String> {
+fn extract_bundle(
+    zip_path: &Path,
+    output_dir: &Path,
+    job: &mut TransferJob,
+    buffer: &mut [u8],
+) -> Result<(), String> {
     let file = File::open(zip_path).map_err(|e| format!("Failed to open bundle: {}", e))?;
 
     let mut archive =
         ZipArchive::new(file).map_err(|e| format!("Bundle file is corrupted: {}", e))?;
 
+    let total_size = archive
+        .decompressed_size()
+        .map(|size| u64::try_from(size).unwrap_or(u64::MAX))
+        .unwrap_or(0);
+    job.begin_stage(TransferStage::Extracting, total_size);
+
     for i in 0..archive.len() {
         let mut file = archive
             .by_index(i)
             .map_err(|e| format!("Failed to read file from bundle: {}", e))?;
 
-        let outpath = match file.enclosed_name() {
-            Some(path) => output_dir.join(path),
-            None => continue,
+        let Some(relative_path) = file.enclosed_name() else {
+            continue;
         };
+        let outpath = output_dir.join(&relative_path);
 
         if file.name().ends_with('/') {
             fs::create_dir_all(&outpath)
@@ -72,7 +156,8 @@ fn extract_bundle(zip_path: &Path, output_dir: &Path) -> Result<(), String> {
             }
             let mut outfile = File::create(&outpath)
                 .map_err(|e| format!("Failed to create output file: {}", e))?;
-            std::io::copy(&mut file, &mut outfile)
+            let dir_name = track_dir_name(&relative_path);
+            job.copy(&mut file, &mut outfile, buffer, dir_name.as_deref())
                 .map_err(|e| format!("Failed to write file: {}", e))?;
         }
     }
@@ -80,15 +165,62 @@ fn extract_bundle(zip_path: &Path, output_dir: &Path) -> Result<(), String> {
     Ok(())
 }
 
-#[command]
-pub async fn fast_upload_extract_manifest(cache_path: String) -> Result<String, String> {
-    let manifest_path = Path::new(&cache_path).join("manifest.json");
+fn read_track_metadata(cache_dir: &Path, dir_name: &str) -> Result<TrackMetadata, String> {
+    let metadata_path = cache_dir
+        .join("tracks")
+        .join(dir_name)
+        .join(TRACK_METADATA_FILE);
+    if !metadata_path.is_file() {
+        return Err(format!("Track {:?} has no metadata file", dir_name));
+    }
+
+    let json = fs::read_to_string(&metadata_path)
+        .map_err(|e| format!("Failed to read metadata for track {:?}: {}", dir_name, e))?;
+    TrackMetadata::parse(&json, dir_name)
+}
+
+fn load_manifest(cache_dir: &Path) -> Result<BundleManifest, String> {
+    let manifest_path = cache_dir.join(MANIFEST_FILE);
 
     if !manifest_path.exists() {
         return Err("Invalid bundle - missing manifest.json".to_string());
     }
 
-    fs::read_to_string(&manifest_path).map_err(|e| format!("Failed to read manifest: {}", e))
+    let json = fs::read_to_string(&manifest_path)
+        .map_err(|e| format!("Failed to read manifest: {}", e))?;
+
+    let mut loaded: HashMap<String, TrackMetadata> = HashMap::new();
+    let manifest = BundleManifest::parse(&json, |dir_name| {
+        let metadata = read_track_metadata(cache_dir, dir_name)?;
+        let audio_file = metadata.song.clone();
+        loaded.insert(dir_name.to_string(), metadata);
+        Ok(audio_file)
+    })?;
+
+    for track in &manifest.tracks {
+        let metadata = match loaded.remove(&track.dir_name) {
+            Some(metadata) => metadata,
+            None => read_track_metadata(cache_dir, &track.dir_name)?,
+        };
+        metadata.check_against(track)?;
+    }
+
+    let upgraded = manifest.to_json()?;
+    if upgraded != json {
+        fs::write(&manifest_path, &upgraded)
+            .map_err(|e| format!("Failed to write upgraded manifest: {}", e))?;
+    }
+
+    Ok(manifest)
+}
+
+#[command]
+pub async fn fast_upload_extract_manifest(cache_path: String) -> Result<BundleManifest, String> {
+    let cache_dir = PathBuf::from(&cache_path);
+
+    tokio::task::spawn_blocking(move || load_manifest(&cache_dir))
+        .await
+        .map_err(|e| format!("Task failed: {}", e))?
 }
 
 #[command]
@@ -129,3 +261,333 @@ pub async fn fast_upload_cleanup_all_cache(app: tauri::AppHandle) -> Result<(),
 
     Ok(())
 }
+
+#[derive(Debug, Clone, Copy, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub enum CorruptReason {
+    Unreadable,
+    SizeMismatch,
+    ChecksumMismatch,
+}
+
+#[derive(Debug, Clone, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct CorruptEntry {
+    pub path: String,
+    pub reason: CorruptReason,
+    pub expected_size: Option<u64>,
+    pub actual_size: Option<u64>,
+    pub detail: Option<String>,
+}
+
+#[derive(Debug, Clone, Copy, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub enum TrackIssueReason {
+    MissingMetadata,
+    InvalidMetadata,
+    MissingAudio,
+}
+
+#[derive(Debug, Clone, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct TrackIssue {
+    pub dir_name: String,
+    pub reason: TrackIssueReason,
+    pub audio_file: Option<String>,
+}
+
+#[derive(Debug, Clone, Default, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct BundleVerifyReport {
+    pub valid: bool,
+    pub has_checksums: bool,
+    pub files_checked: u64,
+    pub bytes_checked: u64,
+    pub missing: Vec<String>,
+    pub corrupt: Vec<CorruptEntry>,
+    pub unexpected: Vec<String>,
+    pub track_issues: Vec<TrackIssue>,
+}
+
+impl BundleVerifyReport {
+    fn unreadable(&mut self, path: &str, detail: String) {
+        self.corrupt.push(CorruptEntry {
+            path: path.to_string(),
+            reason: CorruptReason::Unreadable,
+            expected_size: None,
+            actual_size: None,
+            detail: Some(detail),
+        });
+    }
+
+    fn problems(&self) -> Vec<String> {
+        let missing = self.missing.iter().map(|path| format!("missing {}", path));
+        let corrupt = self.corrupt.iter().map(|entry| {
+            let reason = match entry.reason {
+                CorruptReason::Unreadable => "unreadable",
+                CorruptReason::SizeMismatch => "size mismatch",
+                CorruptReason::ChecksumMismatch => "checksum mismatch",
+            };
+            match &entry.detail {
+                Some(detail) => format!("corrupt {} ({}: {})", entry.path, reason, detail),
+                None => format!("corrupt {} ({})", entry.path, reason),
+            }
+        });
+        let unexpected = self
+            .unexpected
+            .iter()
+            .map(|path| format!("unexpected {}", path));
+        let track_issues = self.track_issues.iter().map(|issue| {
+            let reason = match issue.reason {
+                TrackIssueReason::MissingMetadata => "missing metadata",
+                TrackIssueReason::InvalidMetadata => "invalid metadata",
+                TrackIssueReason::MissingAudio => "missing audio",
+            };
+            format!("{} in {}", reason, issue.dir_name)
+        });
+
+        missing
+            .chain(corrupt)
+            .chain(unexpected)
+            .chain(track_issues)
+            .collect()
+    }
+
+    fn into_result(self) -> Result<(), String> {
+        if self.valid {
+            return Ok(());
+        }
+
+        let problems = self.problems();
+        let more = if problems.len() > 3 { ", ..." } else { "" };
+        Err(format!(
+            "Bundle is incomplete or damaged ({}{}). Please copy the bundle again or re-create it.",
+            problems[..problems.len().min(3)].join(", "),
+            more
+        ))
+    }
+}
+
+fn hash_entry<R: Read>(
+    reader: &mut R,
+    keep: bool,
+    job: &mut TransferJob,
+    buffer: &mut [u8],
+    dir_name: Option<&str>,
+) -> Result<(String, u64, Vec<u8>), String> {
+    let mut hasher = Sha256::new();
+    let mut contents = Vec::new();
+    let mut size = 0u64;
+
+    loop {
+        let read = match reader.read(buffer) {
+            Ok(0) => break,
+            Ok(read) => read,
+            Err(e) if e.kind() == io::ErrorKind::Interrupted => continue,
+            Err(e) => return Err(e.to_string()),
+        };
+        hasher.update(&buffer[..read]);
+        if keep {
+            contents.extend_from_slice(&buffer[..read]);
+        }
+        size += read as u64;
+        job.advance(read as u64, dir_name)?;
+    }
+
+    Ok((format!("{:x}", hasher.finalize()), size, contents))
+}
+
+fn load_checksums<R: Read + Seek>(
+    archive: &mut ZipArchive<R>,
+    report: &mut BundleVerifyReport,
+) -> Option<BundleChecksums> {
+    let mut file = archive.by_name(CHECKSUMS_FILE).ok()?;
+    report.has_checksums = true;
+
+    let mut json = String::new();
+    if let Err(e) = file.read_to_string(&mut json) {
+        report.unreadable(CHECKSUMS_FILE, e.to_string());
+        return None;
+    }
+    match serde_json::from_str::<BundleChecksums>(&json) {
+        Ok(checksums) => Some(checksums),
+        Err(e) => {
+            report.unreadable(CHECKSUMS_FILE, format!("Invalid checksums: {}", e));
+            None
+        }
+    }
+}
+
+fn verify_bundle(
+    bundle_path: &Path,
+    job: &mut TransferJob,
+    buffer: &mut [u8],
+) -> Result<BundleVerifyReport, String> {
+    let file = File::open(bundle_path).map_err(|e| format!("Failed to open bundle: {}", e))?;
+
+    let mut archive =
+        ZipArchive::new(file).map_err(|e| format!("Bundle file is corrupted: {}", e))?;
+
+    let total_size = archive
+        .decompressed_size()
+        .map(|size| u64::try_from(size).unwrap_or(u64::MAX))
+        .unwrap_or(0);
+    job.begin_stage(TransferStage::Verifying, total_size);
+
+    let mut report = BundleVerifyReport::default();
+    let checksums = load_checksums(&mut archive, &mut report);
+    let expected: HashMap<&str, &ChecksumEntry> = checksums
+        .iter()
+        .flat_map(|checksums| checksums.files.iter())
+        .map(|entry| (entry.path.as_str(), entry))
+        .collect();
+
+    let mut present: HashSet<String> = HashSet::new();
+    let mut track_dirs: BTreeSet<String> = BTreeSet::new();
+    let mut track_audio: BTreeMap<String, Option<String>> = BTreeMap::new();
+
+    for i in 0..archive.len() {
+        let mut file = archive
+            .by_index(i)
+            .map_err(|e| format!("Failed to read file from bundle: {}", e))?;
+
+        let path = file.name().to_string();
+        if let Some(dir_name) = track_entry_dir(&path) {
+            track_dirs.insert(dir_name.to_string());
+        }
+        if file.is_dir() || path == CHECKSUMS_FILE {
+            continue;
+        }
+
+        let metadata_dir = track_entry_dir(&path)
+            .filter(|dir_name| path == format!("tracks/{}/{}", dir_name, TRACK_METADATA_FILE))
+            .map(str::to_string);
+
+        let dir_name = track_entry_dir(&path).map(str::to_string);
+        let hashed = hash_entry(
+            &mut file,
+            metadata_dir.is_some(),
+            job,
+            buffer,
+            dir_name.as_deref(),
+        );
+        let (sha256, size, contents) = match hashed {
+            Ok(result) => result,
+            Err(e) => {
+                job.check_cancelled()?;
+                report.unreadable(&path, e);
+                present.insert(path);
+                continue;
+            }
+        };
+
+        report.files_checked += 1;
+        report.bytes_checked += size;
+
+        if let Some(dir_name) = metadata_dir {
+            let audio_file = std::str::from_utf8(&contents)
+                .ok()
+                .and_then(|json| TrackMetadata::parse(json, &dir_name).ok())
+                .map(|metadata| metadata.song);
+            track_audio.insert(dir_name, audio_file);
+        }
+
+        match expected.get(path.as_str()) {
+            Some(entry) if entry.size != size => report.corrupt.push(CorruptEntry {
+                path: path.clone(),
+                reason: CorruptReason::SizeMismatch,
+                expected_size: Some(entry.size),
+                actual_size: Some(size),
+                detail: None,
+            }),
+            Some(entry) if !entry.sha256.eq_ignore_ascii_case(&sha256) => {
+                report.corrupt.push(CorruptEntry {
+                    path: path.clone(),
+                    reason: CorruptReason::ChecksumMismatch,
+                    expected_size: Some(entry.size),
+                    actual_size: Some(size),
+                    detail: None,
+                })
+            }
+            Some(_) => {}
+            None if checksums.is_some() => report.unexpected.push(path.clone()),
+            None => {}
+        }
+
+        present.insert(path);
+    }
+
+    report.missing = expected
+        .keys()
+        .filter(|path| !present.contains(**path))
+        .map(|path| path.to_string())
+        .collect();
+    if !present.contains(MANIFEST_FILE) && !expected.contains_key(MANIFEST_FILE) {
+        report.missing.push(MANIFEST_FILE.to_string());
+    }
+    report.missing.sort();
+
+    for dir_name in track_dirs {
+        let issue = match track_audio.get(&dir_name) {
+            None => TrackIssue {
+                dir_name,
+                reason: TrackIssueReason::MissingMetadata,
+                audio_file: None,
+            },
+            Some(None) => TrackIssue {
+                dir_name,
+                reason: TrackIssueReason::InvalidMetadata,
+                audio_file: None,
+            },
+            Some(Some(audio_file)) => {
+                if present.contains(&format!("tracks/{}/{}", dir_name, audio_file)) {
+                    continue;
+                }
+                TrackIssue {
+                    dir_name,
+                    reason: TrackIssueReason::MissingAudio,
+                    audio_file: Some(audio_file.clone()),
+                }
+            }
+        };
+        report.track_issues.push(issue);
+    }
+
+    report.valid = report.missing.is_empty()
+        && report.corrupt.is_empty()
+        && report.unexpected.is_empty()
+        && report.track_issues.is_empty();
+
+    Ok(report)
+}
+
+#[command]
+pub async fn fast_upload_verify_bundle(
+    app: tauri::AppHandle,
+    bundle_path: String,
+    job_id: Option<String>,
+) -> Result<BundleVerifyReport, String> {
+    let bundle_path = PathBuf::from(&bundle_path);
+    if !bundle_path.exists() {
+        return Err("Bundle file does not exist".to_string());
+    }
+
+    let mut job = TransferJob::register(job_id, BUNDLE_IMPORT_PROGRESS_EVENT, event_sink(&app))?;
+
+    let result = tokio::task::spawn_blocking(move || {
+        let mut buffer = vec![0u8; COPY_CHUNK_SIZE];
+        let result = verify_bundle(&bundle_path, &mut job, &mut buffer);
+        if result.is_ok() {
+            job.finish();
+        }
+        (result, job.is_cancelled())
+    })
+    .await
+    .map_err(|e| format!("Task failed: {}", e))?;
+
+    match result {
+        (Ok(report), _) => Ok(report),
+        (Err(_), true) => Err("Bundle verification was cancelled".to_string()),
+        (Err(e), false) => Err(e),
+    }
+}
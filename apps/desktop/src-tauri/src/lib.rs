@@ -1,20 +1,23 @@
 use tauri::{
-    menu::{Menu, MenuItem},
+    menu::{Menu, MenuItem, PredefinedMenuItem},
     plugin::TauriPlugin,
     tray::{MouseButton, MouseButtonState, TrayIcon, TrayIconBuilder, TrayIconEvent},
-    Manager, WebviewWindow, WindowEvent,
+    AppHandle, Emitter, Listener, Manager, Runtime, WebviewWindow, WindowEvent,
 };
 
 use tauri_plugin_window_state::StateFlags;
 
 mod api;
+mod bundle;
 mod fast_upload;
 mod sync;
+mod transfer;
 mod utils;
 
 use api::commands::*;
 use fast_upload::*;
 use sync::*;
+use transfer::*;
 use utils::*;
 
 #[cfg(debug_assertions)]
@@ -30,6 +33,23 @@ fn prevent_default() -> TauriPlugin<tauri::Wry> {
     tauri_plugin_prevent_default::Builder::new().build()
 }
 
+fn show_main_window<R: Runtime>(app: &AppHandle<R>) {
+    if let Some(window) = app.get_webview_window("main") {
+        let _ = window.unminimize();
+        let _ = window.show();
+        let _ = window.set_focus();
+    }
+}
+
+fn update_server_items<R: Runtime>(toggle: &MenuItem<R>, show_qr: &MenuItem<R>, running: bool) {
+    let _ = toggle.set_text(if running {
+        "Stop Server"
+    } else {
+        "Start Server"
+    });
+    let _ = show_qr.set_enabled(running);
+}
+
 #[cfg_attr(mobile, tauri::mobile_entry_point)]
 pub fn run() {
     tauri::Builder::default()
@@ -58,6 +78,8 @@ pub fn run() {
         }))
         .invoke_handler(tauri::generate_handler![
             start_server,
+            get_server_config,
+            set_server_config,
             stop_server,
             is_server_running,
             get_server_info,
@@ -68,18 +90,49 @@ pub fn run() {
             fast_upload_cleanup_cache_directory,
             fast_upload_check_cache_exists,
             fast_upload_cleanup_all_cache,
-            sync_create_bundle,
+            fast_upload_verify_bundle,
+            sync_export_library,
+            cancel_bundle_transfer,
             backfill_fingerprints,
-            get_sync_status
+            get_sync_status,
+            discover_sync_servers,
+            list_blocked_clients,
+            list_network_interfaces,
+            list_paired_devices,
+            rename_paired_device,
+            revoke_paired_device,
+            list_pairing_requests,
+            approve_pairing,
+            reject_pairing,
+            mint_access_token,
+            list_access_tokens,
+            revoke_access_token
         ])
         .setup(|app| {
             #[cfg(desktop)]
             {
                 let main_window: WebviewWindow = app.get_webview_window("main").unwrap();
 
+                let toggle_server_item =
+                    MenuItem::with_id(app, "toggle_server", "Start Server", true, None::<&str>)?;
+                let show_qr_item =
+                    MenuItem::with_id(app, "show_qr", "Show QR", false, None::<&str>)?;
+                let separator = PredefinedMenuItem::separator(app)?;
                 let quit_item = MenuItem::with_id(app, "quit", "&Quit", true, None::<&str>)?;
 
-                let menu = Menu::with_items(app, &[&quit_item])?;
+                let menu = Menu::with_items(
+                    app,
+                    &[&toggle_server_item, &show_qr_item, &separator, &quit_item],
+                )?;
+
+                let (toggle_item, qr_item) = (toggle_server_item.clone(), show_qr_item.clone());
+                app.listen(api::SERVER_STATE_EVENT, move |event| {
+                    let running = serde_json::from_str::<serde_json::Value>(event.payload())
+                        .ok()
+                        .and_then(|payload| payload["running"].as_bool())
+                        .unwrap_or(false);
+                    update_server_items(&toggle_item, &qr_item, running);
+                });
 
                 TrayIconBuilder::new()
                     .icon(app.default_window_icon().unwrap().clone())
@@ -95,19 +148,29 @@ pub fn run() {
                             let app = tray_handle.app_handle();
                             if let Some(window) = app.get_webview_window("main") {
                                 if !window.is_focused().unwrap_or(false) {
-                                    let _ = window.unminimize();
-                                    let _ = window.show();
-                                    let _ = window.set_focus();
+                                    show_main_window(app);
                                 }
                             }
                         }
                         _ => {}
                     })
                     .on_menu_event(|app, event| match event.id.as_ref() {
+                        "toggle_server" => {
+                            let app = app.clone();
+                            tauri::async_runtime::spawn(async move {
+                                if api::is_server_running().await {
+                                    stop_server_for_app(&app).await;
+                                } else if let Err(e) = start_server_for_app(&app).await {
+                                    log::error!("Failed to start sync server: {}", e);
+                                }
+                            });
+                        }
+                        "show_qr" => {
+                            show_main_window(app);
+                            let _ = app.emit(api::SHOW_QR_EVENT, ());
+                        }
                         "quit" => {
-                            for (_, win) in app.webview_windows() {
-                                let _ = win.close();
-                            }
+                            app.exit(0);
                         }
                         _ => {
                             println!("menu item {:?} not handled", event.id);
@@ -115,14 +178,30 @@ pub fn run() {
                     })
                     .build(app)?;
 
+                if serves_in_background(app.handle()) {
+                    let handle = app.handle().clone();
+                    tauri::async_runtime::spawn(async move {
+                        if let Err(e) = start_server_for_app(&handle).await {
+                            log::error!("Failed to start sync server in background: {}", e);
+                        }
+                    });
+                }
+
                 Ok(())
             }
         })
         .on_window_event(|window, event| match event {
-            WindowEvent::CloseRequested { .. } => {
+            WindowEvent::CloseRequested { api: close, .. } => {
                 let app = window.app_handle();
-                for (_, win) in app.webview_windows() {
-                    let _ = win.close();
+                if serves_in_background(app) {
+                    close.prevent_close();
+                    for (_, win) in app.webview_windows() {
+                        let _ = win.hide();
+                    }
+                } else {
+                    for (_, win) in app.webview_windows() {
+                        let _ = win.close();
+                    }
                 }
             }
             _ => {}
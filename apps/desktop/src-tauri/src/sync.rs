@@ -3,77 +3,128 @@ use tauri::Manager;
 
 use chrono::Utc;
 
-use serde::Deserialize;
+use serde::Serialize;
 
-use std::fs::File;
-use std::io::{BufReader, Read, Write};
-use std::path::PathBuf;
+use std::collections::{HashMap, HashSet};
+use std::fs::{self, File};
+use std::io::Write;
+use std::path::{Path, PathBuf};
 
 use zip::write::FileOptions;
 use zip::ZipWriter;
 
-#[derive(Debug, Deserialize, Clone)]
-pub struct TrackExportData {
-    pub dir_name: String,
-    pub audio_file: String,
-    pub thumbnails: Vec<String>,
-    pub metadata_json: String,
+use crate::api::commands::event_sink;
+use crate::api::db::{
+    self, ExportAlbum, ExportArtist, ExportPlaylist, ExportSelection, ExportSong,
+};
+use crate::bundle::{
+    BundleChecksums, BundleManifest, BundleOrigin, HashingWriter, ManifestPlaylist, ManifestSource,
+    ManifestStats, ManifestTrack, TrackAlbum, TrackArtist, TrackLyric, TrackMetadata,
+    BUNDLE_FORMAT_VERSION, CHECKSUMS_FILE, MANIFEST_FILE, TRACK_METADATA_FILE,
+};
+use crate::transfer::{TransferJob, TransferStage, BUNDLE_EXPORT_PROGRESS_EVENT, COPY_CHUNK_SIZE};
+
+#[derive(Debug, Clone)]
+struct TrackExportData {
+    dir_name: String,
+    thumbnails: Vec<String>,
+    metadata: TrackMetadata,
 }
 
-fn create_bundle_sync(
-    zip_path: PathBuf,
-    songs_dir: PathBuf,
-    thumbnails_dir: PathBuf,
-    manifest_json: String,
-    tracks_data: Vec<TrackExportData>,
-) -> Result<String, String> {
-    let zip_file =
-        File::create(&zip_path).map_err(|e| format!("Failed to create ZIP file: {}", e))?;
+const ZIP64_SIZE_THRESHOLD: u64 = u32::MAX as u64;
+const ZIP64_ENTRY_THRESHOLD: usize = u16::MAX as usize;
 
-    let mut zip = ZipWriter::new(zip_file);
+struct BundleEntry {
+    path: String,
+    dir_name: Option<String>,
+    source: BundleSource,
+}
 
-    let options: FileOptions<'_, ()> = FileOptions::default()
-        .compression_method(zip::CompressionMethod::Stored);
+enum BundleSource {
+    Directory,
+    Text(String),
+    File(PathBuf, u64),
+}
+
+fn check_bundle(manifest: &BundleManifest, tracks_data: &[TrackExportData]) -> Result<(), String> {
+    manifest.validate()?;
+    if manifest.tracks.len() != tracks_data.len() {
+        return Err(format!(
+            "Manifest lists {} tracks but {} were exported",
+            manifest.tracks.len(),
+            tracks_data.len()
+        ));
+    }
+
+    let mut dir_names = HashSet::new();
+    for track in tracks_data {
+        if !dir_names.insert(track.dir_name.as_str()) {
+            return Err("Exported tracks reuse a directory name".to_string());
+        }
+
+        let Some(manifest_track) = manifest
+            .tracks
+            .iter()
+            .find(|manifest_track| manifest_track.dir_name == track.dir_name)
+        else {
+            return Err(format!(
+                "Track {:?} was exported but is not listed in the manifest",
+                track.dir_name
+            ));
+        };
+
+        track.metadata.validate(&track.dir_name)?;
+        track.metadata.check_against(manifest_track)?;
+    }
 
-    zip.start_file("manifest.json", options.clone())
-        .map_err(|e| format!("Failed to add manifest.json: {}", e))?;
-    zip.write_all(manifest_json.as_bytes())
-        .map_err(|e| format!("Failed to write manifest.json: {}", e))?;
+    Ok(())
+}
 
-    zip.add_directory("tracks/", options.clone())
-        .map_err(|e| format!("Failed to add tracks directory: {}", e))?;
+fn plan_bundle(
+    songs_dir: &Path,
+    thumbnails_dir: &Path,
+    manifest: BundleManifest,
+    tracks_data: Vec<TrackExportData>,
+) -> Result<Vec<BundleEntry>, String> {
+    let mut entries = vec![
+        BundleEntry {
+            path: MANIFEST_FILE.to_string(),
+            dir_name: None,
+            source: BundleSource::Text(manifest.to_json()?),
+        },
+        BundleEntry {
+            path: "tracks/".to_string(),
+            dir_name: None,
+            source: BundleSource::Directory,
+        },
+    ];
 
     for track in tracks_data {
         let track_dir = format!("tracks/{}", track.dir_name);
 
-        zip.add_directory(format!("{}/", track_dir), options.clone())
-            .map_err(|e| format!("Failed to add track directory {}: {}", track.dir_name, e))?;
-
-        let metadata_path = format!("{}/metadata.json", track_dir);
-        zip.start_file(&metadata_path, options.clone())
-            .map_err(|e| format!("Failed to add metadata for {}: {}", track.dir_name, e))?;
-        zip.write_all(track.metadata_json.as_bytes())
-            .map_err(|e| format!("Failed to write metadata for {}: {}", track.dir_name, e))?;
-
-        let audio_source = songs_dir.join(&track.audio_file);
-        if audio_source.exists() {
-            let audio_path = format!("{}/{}", track_dir, track.audio_file);
-
-            let file = File::open(&audio_source)
-                .map_err(|e| format!("Failed to open audio file {}: {}", track.audio_file, e))?;
-            let mut reader = BufReader::new(file);
-            let mut audio_data = Vec::new();
-            reader
-                .read_to_end(&mut audio_data)
-                .map_err(|e| format!("Failed to read audio file {}: {}", track.audio_file, e))?;
-
-            zip.start_file(&audio_path, options.clone())
-                .map_err(|e| format!("Failed to add audio file {}: {}", track.audio_file, e))?;
-            zip.write_all(&audio_data)
-                .map_err(|e| format!("Failed to write audio file {}: {}", track.audio_file, e))?;
-        } else {
-            return Err(format!("Audio file not found: {}", track.audio_file));
-        }
+        entries.push(BundleEntry {
+            path: format!("{}/", track_dir),
+            dir_name: Some(track.dir_name.clone()),
+            source: BundleSource::Directory,
+        });
+        entries.push(BundleEntry {
+            path: format!("{}/{}", track_dir, TRACK_METADATA_FILE),
+            dir_name: Some(track.dir_name.clone()),
+            source: BundleSource::Text(track.metadata.to_json()?),
+        });
+
+        let audio_file = &track.metadata.song;
+        let audio_source = songs_dir.join(audio_file);
+        let audio_size = fs::metadata(&audio_source)
+            .ok()
+            .filter(|metadata| metadata.is_file())
+            .map(|metadata| metadata.len())
+            .ok_or_else(|| format!("Audio file not found: {}", audio_file))?;
+        entries.push(BundleEntry {
+            path: format!("{}/{}", track_dir, audio_file),
+            dir_name: Some(track.dir_name.clone()),
+            source: BundleSource::File(audio_source, audio_size),
+        });
 
         for thumbnail in &track.thumbnails {
             if thumbnail.is_empty() {
@@ -81,61 +132,428 @@ fn create_bundle_sync(
             }
 
             let thumb_source = thumbnails_dir.join(thumbnail);
-            if thumb_source.exists() {
-                let thumb_path = format!("{}/{}", track_dir, thumbnail);
-
-                let file = File::open(&thumb_source)
-                    .map_err(|e| format!("Failed to open thumbnail {}: {}", thumbnail, e))?;
-                let mut reader = BufReader::new(file);
-                let mut thumb_data = Vec::new();
-                reader
-                    .read_to_end(&mut thumb_data)
-                    .map_err(|e| format!("Failed to read thumbnail {}: {}", thumbnail, e))?;
-
-                zip.start_file(&thumb_path, options.clone())
-                    .map_err(|e| format!("Failed to add thumbnail {}: {}", thumbnail, e))?;
-                zip.write_all(&thumb_data)
-                    .map_err(|e| format!("Failed to write thumbnail {}: {}", thumbnail, e))?;
+            if let Some(metadata) = fs::metadata(&thumb_source)
+                .ok()
+                .filter(|metadata| metadata.is_file())
+            {
+                entries.push(BundleEntry {
+                    path: format!("{}/{}", track_dir, thumbnail),
+                    dir_name: Some(track.dir_name.clone()),
+                    source: BundleSource::File(thumb_source, metadata.len()),
+                });
             }
         }
     }
 
+    Ok(entries)
+}
+
+fn bundle_size(entries: &[BundleEntry]) -> u64 {
+    entries
+        .iter()
+        .map(|entry| match &entry.source {
+            BundleSource::Directory => 0,
+            BundleSource::Text(text) => text.len() as u64,
+            BundleSource::File(_, size) => *size,
+        })
+        .sum()
+}
+
+fn write_bundle(
+    zip_path: &Path,
+    entries: Vec<BundleEntry>,
+    job: &mut TransferJob,
+) -> Result<(), String> {
+    let total_size = bundle_size(&entries);
+    let zip64 = entries.len() > ZIP64_ENTRY_THRESHOLD || total_size > ZIP64_SIZE_THRESHOLD;
+
+    let zip_file =
+        File::create(zip_path).map_err(|e| format!("Failed to create ZIP file: {}", e))?;
+
+    let mut zip = ZipWriter::new(zip_file);
+
+    let options: FileOptions<'_, ()> = FileOptions::default()
+        .compression_method(zip::CompressionMethod::Stored)
+        .large_file(zip64);
+
+    let mut buffer = vec![0u8; COPY_CHUNK_SIZE];
+    let mut checksums = BundleChecksums::new();
+
+    job.begin_stage(TransferStage::Writing, total_size);
+
+    for entry in entries {
+        let dir_name = entry.dir_name.as_deref();
+        match entry.source {
+            BundleSource::Directory => {
+                zip.add_directory(&entry.path, options.clone())
+                    .map_err(|e| format!("Failed to add directory {}: {}", entry.path, e))?;
+            }
+            BundleSource::Text(text) => {
+                zip.start_file(&entry.path, options.clone())
+                    .map_err(|e| format!("Failed to add {}: {}", entry.path, e))?;
+                let mut writer = HashingWriter::new(&mut zip);
+                writer
+                    .write_all(text.as_bytes())
+                    .map_err(|e| format!("Failed to write {}: {}", entry.path, e))?;
+                checksums.files.push(writer.finish(&entry.path));
+                job.advance(text.len() as u64, dir_name)?;
+            }
+            BundleSource::File(source, size) => {
+                zip.start_file(
+                    &entry.path,
+                    options
+                        .clone()
+                        .large_file(zip64 || size > ZIP64_SIZE_THRESHOLD),
+                )
+                .map_err(|e| format!("Failed to add {}: {}", entry.path, e))?;
+                let mut file = File::open(&source)
+                    .map_err(|e| format!("Failed to open {}: {}", entry.path, e))?;
+                let mut writer = HashingWriter::new(&mut zip);
+                job.copy(&mut file, &mut writer, &mut buffer, dir_name)
+                    .map_err(|e| format!("Failed to write {}: {}", entry.path, e))?;
+                checksums.files.push(writer.finish(&entry.path));
+            }
+        }
+    }
+
+    job.check_cancelled()?;
+
+    let checksums_json = serde_json::to_string_pretty(&checksums)
+        .map_err(|e| format!("Failed to encode checksums: {}", e))?;
+    zip.start_file(CHECKSUMS_FILE, options)
+        .map_err(|e| format!("Failed to add {}: {}", CHECKSUMS_FILE, e))?;
+    zip.write_all(checksums_json.as_bytes())
+        .map_err(|e| format!("Failed to write {}: {}", CHECKSUMS_FILE, e))?;
+
     zip.finish()
         .map_err(|e| format!("Failed to finalize ZIP: {}", e))?;
 
+    job.finish();
+    Ok(())
+}
+
+fn create_bundle_sync(
+    zip_path: PathBuf,
+    songs_dir: PathBuf,
+    thumbnails_dir: PathBuf,
+    manifest: BundleManifest,
+    tracks_data: Vec<TrackExportData>,
+    mut job: TransferJob,
+) -> Result<String, String> {
+    check_bundle(&manifest, &tracks_data)?;
+    let entries = plan_bundle(&songs_dir, &thumbnails_dir, manifest, tracks_data)?;
+
+    if let Err(e) = write_bundle(&zip_path, entries, &mut job) {
+        if zip_path.exists() {
+            if let Err(remove_err) = fs::remove_file(&zip_path) {
+                log::warn!("Failed to remove partial ZIP: {}", remove_err);
+            }
+        }
+        if job.is_cancelled() {
+            return Err("Bundle export was cancelled".to_string());
+        }
+        return Err(e);
+    }
+
     zip_path
         .to_str()
         .ok_or_else(|| "Failed to convert path to string".to_string())
         .map(|s| s.to_string())
 }
 
-#[command]
-pub async fn sync_create_bundle(
-    app: tauri::AppHandle,
-    output_dir: String,
-    manifest_json: String,
-    tracks_data: Vec<TrackExportData>,
-) -> Result<String, String> {
+fn bundle_zip_path(output_dir: &str) -> Result<PathBuf, String> {
     let timestamp = Utc::now().format("%Y%m%d_%H%M%S").to_string();
     let zip_filename = format!("Tunno_FastUpload_{}.zip", timestamp);
 
-    let output_path = PathBuf::from(&output_dir);
+    let output_path = PathBuf::from(output_dir);
     if !output_path.exists() {
         return Err("Output directory does not exist".to_string());
     }
 
-    let zip_path = output_path.join(&zip_filename);
+    Ok(output_path.join(&zip_filename))
+}
+
+const MAX_DIR_NAME_CHARS: usize = 200;
+const ILLEGAL_DIR_NAME_CHARS: &[char] = &['/', '?', '<', '>', '\\', ':', '*', '|', '"'];
+const RESERVED_DIR_NAMES: &[&str] = &["con", "prn", "aux", "nul"];
+const RESERVED_DIR_NAME_PREFIXES: &[&str] = &["com", "lpt"];
+
+fn is_reserved_dir_name(name: &str) -> bool {
+    let stem = name.split('.').next().unwrap_or_default().to_lowercase();
+    RESERVED_DIR_NAMES.contains(&stem.as_str())
+        || RESERVED_DIR_NAME_PREFIXES.iter().any(|prefix| {
+            stem.strip_prefix(prefix)
+                .is_some_and(|rest| rest.len() == 1 && rest.chars().all(|c| c.is_ascii_digit()))
+        })
+}
+
+fn sanitize_dir_name(input: &str) -> String {
+    let cleaned: String = input
+        .chars()
+        .filter(|c| !ILLEGAL_DIR_NAME_CHARS.contains(c) && !c.is_control())
+        .collect();
+    let mut sanitized = cleaned
+        .split_whitespace()
+        .collect::<Vec<_>>()
+        .join(" ")
+        .trim_end_matches(['.', ' '])
+        .to_string();
+
+    if is_reserved_dir_name(&sanitized) {
+        sanitized = format!("_{}", sanitized);
+    }
+
+    if sanitized.chars().count() > MAX_DIR_NAME_CHARS {
+        sanitized = sanitized
+            .chars()
+            .take(MAX_DIR_NAME_CHARS)
+            .collect::<String>()
+            .trim_end_matches(['.', ' '])
+            .to_string();
+    }
+
+    if sanitized.is_empty() {
+        return "Unknown".to_string();
+    }
+    sanitized
+}
+
+fn unique_dir_name(title: &str, seen: &mut HashSet<String>) -> String {
+    let base = sanitize_dir_name(title);
+    let mut dir_name = base.clone();
+    let mut counter = 1;
+
+    while seen.contains(&dir_name) {
+        counter += 1;
+        dir_name = format!("{} ({})", base, counter);
+    }
+
+    seen.insert(dir_name.clone());
+    dir_name
+}
+
+fn track_artist(artist: &ExportArtist) -> TrackArtist {
+    TrackArtist {
+        name: artist.name.clone(),
+        thumbnail: artist.thumbnail.clone(),
+        genres: None,
+    }
+}
+
+fn song_thumbnails(song: &ExportSong, album: &ExportAlbum) -> Vec<String> {
+    let mut thumbnails: Vec<String> = Vec::new();
+    let candidates = std::iter::once(&song.thumbnail)
+        .chain(song.artists.iter().map(|artist| &artist.thumbnail))
+        .chain(std::iter::once(&album.thumbnail))
+        .chain(album.artists.iter().map(|artist| &artist.thumbnail));
+
+    for thumbnail in candidates.flatten() {
+        if !thumbnail.is_empty() && !thumbnails.contains(thumbnail) {
+            thumbnails.push(thumbnail.clone());
+        }
+    }
+    thumbnails
+}
+
+struct LibraryExport {
+    manifest: BundleManifest,
+    tracks_data: Vec<TrackExportData>,
+    skipped: usize,
+}
+
+fn build_library_export(
+    songs: Vec<ExportSong>,
+    playlists: Vec<ExportPlaylist>,
+    app_version: &str,
+) -> Result<LibraryExport, String> {
+    let mut seen_dir_names: HashSet<String> = HashSet::new();
+    let mut dir_names: HashMap<i64, String> = HashMap::new();
+    let mut tracks: Vec<ManifestTrack> = Vec::new();
+    let mut tracks_data: Vec<TrackExportData> = Vec::new();
+    let mut skipped = 0;
+
+    for song in songs {
+        let Some(album) = &song.album else {
+            skipped += 1;
+            continue;
+        };
+        let artists = if song.artists.is_empty() {
+            &album.artists
+        } else {
+            &song.artists
+        };
+        if artists.is_empty() {
+            skipped += 1;
+            continue;
+        }
+
+        let dir_name = unique_dir_name(&song.name, &mut seen_dir_names);
+
+        let metadata = TrackMetadata {
+            song: song.file.clone(),
+            title: song.name.clone(),
+            thumbnail: song.thumbnail.clone().unwrap_or_default(),
+            duration: song.duration as f64,
+            artists: artists.iter().map(track_artist).collect(),
+            album: TrackAlbum {
+                name: album.name.clone(),
+                thumbnail: album.thumbnail.clone().unwrap_or_default(),
+                release_year: album
+                    .release_year
+                    .filter(|year| *year != 0)
+                    .or(song.release_year)
+                    .unwrap_or(0) as i32,
+                album_type: album.album_type.clone(),
+                artists: album.artists.iter().map(track_artist).collect(),
+            },
+            lyrics: song
+                .lyrics
+                .as_deref()
+                .and_then(|lyrics| serde_json::from_str::<Vec<TrackLyric>>(lyrics).ok()),
+        };
+
+        tracks.push(ManifestTrack {
+            dir_name: dir_name.clone(),
+            title: song.name.clone(),
+            artists: artists.iter().map(|artist| artist.name.clone()).collect(),
+            album: album.name.clone(),
+            thumbnail: song.thumbnail.clone(),
+            audio_file: song.file.clone(),
+        });
+        tracks_data.push(TrackExportData {
+            dir_name: dir_name.clone(),
+            thumbnails: song_thumbnails(&song, album),
+            metadata,
+        });
+        dir_names.insert(song.id, dir_name);
+    }
+
+    if tracks.is_empty() {
+        if skipped > 0 {
+            return Err("All selected songs are missing album information".to_string());
+        }
+        return Err("No songs match the export selection".to_string());
+    }
+
+    let playlists = playlists
+        .into_iter()
+        .filter_map(|playlist| {
+            let tracks: Vec<String> = playlist
+                .song_ids
+                .iter()
+                .filter_map(|song_id| dir_names.get(song_id).cloned())
+                .collect();
+            (!tracks.is_empty()).then_some(ManifestPlaylist {
+                name: playlist.name,
+                tracks,
+            })
+        })
+        .collect();
+
+    let manifest = BundleManifest {
+        version: BUNDLE_FORMAT_VERSION,
+        created_at: Utc::now(),
+        source: ManifestSource {
+            origin: BundleOrigin::Desktop,
+            version: app_version.to_string(),
+            os: std::env::consts::OS.to_string(),
+        },
+        stats: ManifestStats {
+            total_tracks: tracks.len(),
+        },
+        tracks,
+        playlists,
+    };
+
+    Ok(LibraryExport {
+        manifest,
+        tracks_data,
+        skipped,
+    })
+}
+
+#[derive(Debug, Clone, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct LibraryExportResult {
+    pub bundle_path: String,
+    pub exported_tracks: usize,
+    pub skipped_tracks: usize,
+    pub playlists: usize,
+}
+
+fn export_library_sync(
+    db_path: PathBuf,
+    zip_path: PathBuf,
+    songs_dir: PathBuf,
+    thumbnails_dir: PathBuf,
+    selection: ExportSelection,
+    app_version: String,
+    job: TransferJob,
+) -> Result<LibraryExportResult, String> {
+    let conn = db::open_readonly(&db_path).map_err(|e| e.to_string())?;
+
+    let song_ids = db::select_export_song_ids(&conn, &selection)
+        .map_err(|e| format!("Failed to select songs: {}", e))?;
+    let songs = db::get_export_songs(&conn, &song_ids)
+        .map_err(|e| format!("Failed to load songs: {}", e))?;
+    let playlists = db::get_export_playlists(&conn, &selection)
+        .map_err(|e| format!("Failed to load playlists: {}", e))?;
+    drop(conn);
+
+    let export = build_library_export(songs, playlists, &app_version)?;
+    let result = LibraryExportResult {
+        bundle_path: String::new(),
+        exported_tracks: export.tracks_data.len(),
+        skipped_tracks: export.skipped,
+        playlists: export.manifest.playlists.len(),
+    };
+
+    let bundle_path = create_bundle_sync(
+        zip_path,
+        songs_dir,
+        thumbnails_dir,
+        export.manifest,
+        export.tracks_data,
+        job,
+    )?;
+
+    Ok(LibraryExportResult {
+        bundle_path,
+        ..result
+    })
+}
+
+#[command]
+pub async fn sync_export_library(
+    app: tauri::AppHandle,
+    output_dir: String,
+    selection: ExportSelection,
+    job_id: Option<String>,
+) -> Result<LibraryExportResult, String> {
+    let zip_path = bundle_zip_path(&output_dir)?;
 
     let app_data_dir = app
         .path()
         .app_data_dir()
         .map_err(|e| format!("Failed to get app data directory: {}", e))?;
 
+    let db_path = db::resolve_db_path(&app_data_dir);
     let songs_dir = app_data_dir.join("songs");
     let thumbnails_dir = app_data_dir.join("thumbnails");
+    let app_version = app.package_info().version.to_string();
+
+    let job = TransferJob::register(job_id, BUNDLE_EXPORT_PROGRESS_EVENT, event_sink(&app))?;
 
     tokio::task::spawn_blocking(move || {
-        create_bundle_sync(zip_path, songs_dir, thumbnails_dir, manifest_json, tracks_data)
+        export_library_sync(
+            db_path,
+            zip_path,
+            songs_dir,
+            thumbnails_dir,
+            selection,
+            app_version,
+            job,
+        )
     })
     .await
     .map_err(|e| format!("Task failed: {}", e))?
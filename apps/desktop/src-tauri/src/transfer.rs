@@ -0,0 +1,188 @@
+use tauri::command;
+
+use serde::Serialize;
+
+use std::collections::HashMap;
+use std::io::{ErrorKind, Read, Write};
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::{Arc, LazyLock, Mutex};
+use std::time::{Duration, Instant};
+
+use crate::api::EventSink;
+
+pub const BUNDLE_EXPORT_PROGRESS_EVENT: &str = "bundle-export-progress";
+pub const BUNDLE_IMPORT_PROGRESS_EVENT: &str = "bundle-import-progress";
+
+pub const COPY_CHUNK_SIZE: usize = 256 * 1024;
+
+const EMIT_INTERVAL: Duration = Duration::from_millis(100);
+
+static TRANSFERS: LazyLock<Mutex<HashMap<String, Arc<AtomicBool>>>> =
+    LazyLock::new(|| Mutex::new(HashMap::new()));
+
+#[derive(Debug, Clone, Copy, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub enum TransferStage {
+    Copying,
+    Verifying,
+    Extracting,
+    Writing,
+}
+
+#[derive(Debug, Clone, Serialize)]
+#[serde(rename_all = "camelCase")]
+struct TransferProgressPayload<'a> {
+    job_id: &'a str,
+    stage: TransferStage,
+    bytes_done: u64,
+    bytes_total: u64,
+    dir_name: Option<&'a str>,
+    eta_secs: Option<u64>,
+    finished: bool,
+}
+
+pub struct TransferJob {
+    job_id: String,
+    cancelled: Arc<AtomicBool>,
+    event: &'static str,
+    events: EventSink,
+    stage: TransferStage,
+    bytes_done: u64,
+    bytes_total: u64,
+    started_at: Instant,
+    last_emit: Option<Instant>,
+}
+
+impl TransferJob {
+    pub fn register(
+        job_id: Option<String>,
+        event: &'static str,
+        events: EventSink,
+    ) -> Result<Self, String> {
+        let job_id = job_id.unwrap_or_else(|| uuid::Uuid::new_v4().to_string());
+        let cancelled = Arc::new(AtomicBool::new(false));
+
+        let mut transfers = TRANSFERS.lock().unwrap();
+        if transfers.contains_key(&job_id) {
+            return Err(format!("Transfer {} is already running", job_id));
+        }
+        transfers.insert(job_id.clone(), cancelled.clone());
+
+        Ok(Self {
+            job_id,
+            cancelled,
+            event,
+            events,
+            stage: TransferStage::Copying,
+            bytes_done: 0,
+            bytes_total: 0,
+            started_at: Instant::now(),
+            last_emit: None,
+        })
+    }
+
+    pub fn begin_stage(&mut self, stage: TransferStage, bytes_total: u64) {
+        self.stage = stage;
+        self.bytes_done = 0;
+        self.bytes_total = bytes_total;
+        self.started_at = Instant::now();
+        self.last_emit = None;
+        self.emit(None, false);
+    }
+
+    pub fn is_cancelled(&self) -> bool {
+        self.cancelled.load(Ordering::Relaxed)
+    }
+
+    pub fn check_cancelled(&self) -> Result<(), String> {
+        if self.is_cancelled() {
+            return Err(format!("Transfer {} was cancelled", self.job_id));
+        }
+        Ok(())
+    }
+
+    pub fn advance(&mut self, bytes: u64, dir_name: Option<&str>) -> Result<(), String> {
+        self.check_cancelled()?;
+        self.bytes_done += bytes;
+
+        let due = self
+            .last_emit
+            .is_none_or(|last_emit| last_emit.elapsed() >= EMIT_INTERVAL);
+        if due {
+            self.emit(dir_name, false);
+        }
+        Ok(())
+    }
+
+    pub fn finish(&mut self) {
+        self.bytes_done = self.bytes_total;
+        self.emit(None, true);
+    }
+
+    fn eta_secs(&self) -> Option<u64> {
+        if self.bytes_done == 0 || self.bytes_done >= self.bytes_total {
+            return None;
+        }
+        let elapsed = self.started_at.elapsed().as_secs_f64();
+        let remaining = (self.bytes_total - self.bytes_done) as f64;
+        Some((elapsed * remaining / self.bytes_done as f64).ceil() as u64)
+    }
+
+    fn emit(&mut self, dir_name: Option<&str>, finished: bool) {
+        self.last_emit = Some(Instant::now());
+        let payload = TransferProgressPayload {
+            job_id: &self.job_id,
+            stage: self.stage,
+            bytes_done: self.bytes_done,
+            bytes_total: self.bytes_total,
+            dir_name,
+            eta_secs: self.eta_secs(),
+            finished,
+        };
+        match serde_json::to_value(&payload) {
+            Ok(payload) => (self.events)(self.event, payload),
+            Err(e) => log::warn!("Failed to encode {}: {}", self.event, e),
+        }
+    }
+
+    pub fn copy<R: Read, W: Write>(
+        &mut self,
+        reader: &mut R,
+        writer: &mut W,
+        buffer: &mut [u8],
+        dir_name: Option<&str>,
+    ) -> Result<u64, String> {
+        let mut copied = 0u64;
+
+        loop {
+            let read = match reader.read(buffer) {
+                Ok(0) => return Ok(copied),
+                Ok(read) => read,
+                Err(e) if e.kind() == ErrorKind::Interrupted => continue,
+                Err(e) => return Err(format!("Failed to read: {}", e)),
+            };
+            writer
+                .write_all(&buffer[..read])
+                .map_err(|e| format!("Failed to write: {}", e))?;
+            copied += read as u64;
+            self.advance(read as u64, dir_name)?;
+        }
+    }
+}
+
+impl Drop for TransferJob {
+    fn drop(&mut self) {
+        TRANSFERS.lock().unwrap().remove(&self.job_id);
+    }
+}
+
+#[command]
+pub async fn cancel_bundle_transfer(job_id: String) -> bool {
+    match TRANSFERS.lock().unwrap().get(&job_id) {
+        Some(cancelled) => {
+            cancelled.store(true, Ordering::Relaxed);
+            true
+        }
+        None => false,
+    }
+}
@@ -0,0 +1,72 @@
+import { sql } from "drizzle-orm"
+import { index, integer, sqliteTable, text } from "drizzle-orm/sqlite-core"
+
+/**
+ * Desktop-only tables used by the sync server. They are read and written by the Rust side
+ * and are declared here so drizzle migrations describe the full desktop schema.
+ *
+ * The triggers that keep `updated_at` and `sync_tombstones` current cannot be expressed in
+ * drizzle and live in the migration SQL.
+ */
+
+/**
+ * Schema definition for the `sync_tombstones` table.
+ * Records deleted entities by fingerprint so paired devices can remove them during delta sync.
+ */
+export const syncTombstones = sqliteTable(
+  "sync_tombstones",
+  {
+    id: integer("id").primaryKey({ autoIncrement: true }),
+    entityType: text("entity_type").notNull(),
+    fingerprint: text("fingerprint").notNull(),
+    deletedAt: integer("deleted_at")
+      .notNull()
+      .default(sql`(unixepoch())`)
+  },
+  (table) => [
+    index("sync_tombstones_deleted_at_idx").on(table.deletedAt),
+    index("sync_tombstones_entity_idx").on(table.entityType, table.fingerprint)
+  ]
+)
+
+/**
+ * Schema definition for the `sync_meta` table.
+ * Key-value store for sync server state, such as the tombstone compaction watermark and the
+ * access token signing key.
+ */
+export const syncMeta = sqliteTable("sync_meta", {
+  key: text("key").primaryKey(),
+  value: text("value").notNull()
+})
+
+/**
+ * Schema definition for the `sync_devices` table.
+ * Stores paired devices and the hash of the credential issued to each of them.
+ */
+export const syncDevices = sqliteTable("sync_devices", {
+  id: text("id").primaryKey(),
+  name: text("name").notNull(),
+  secretHash: text("secret_hash").notNull(),
+  createdAt: integer("created_at")
+    .notNull()
+    .default(sql`(unixepoch())`),
+  lastSeenAt: integer("last_seen_at"),
+  revokedAt: integer("revoked_at")
+})
+
+/**
+ * Schema definition for the `sync_access_tokens` table.
+ * Stores issued scoped access tokens so they can be listed and revoked.
+ */
+export const syncAccessTokens = sqliteTable("sync_access_tokens", {
+  id: text("id").primaryKey(),
+  label: text("label").notNull(),
+  scopes: text("scopes").notNull(),
+  playlists: text("playlists").notNull(),
+  albums: text("albums").notNull(),
+  createdAt: integer("created_at")
+    .notNull()
+    .default(sql`(unixepoch())`),
+  expiresAt: integer("expires_at").notNull(),
+  revokedAt: integer("revoked_at")
+})
@@ -1,25 +1,94 @@
 import { invoke } from "@tauri-apps/api/core"
 
+import { type FastUploadManifest } from "../types"
+
+/**
+ * A bundle entry whose contents do not match the bundle's `checksums.json`.
+ */
+export type CorruptBundleEntry = {
+  path: string
+  reason: "unreadable" | "sizeMismatch" | "checksumMismatch"
+  expectedSize: number | null
+  actualSize: number | null
+  detail: string | null
+}
+
+/**
+ * A track directory that cannot be imported as-is.
+ */
+export type BundleTrackIssue = {
+  dirName: string
+  reason: "missingMetadata" | "invalidMetadata" | "missingAudio"
+  audioFile: string | null
+}
+
+/**
+ * The outcome of checking a bundle against its embedded checksums.
+ *
+ * Bundles created before checksums were embedded have `hasChecksums` set to `false`,
+ * in which case only readability and track structure are checked.
+ */
+export type BundleVerifyReport = {
+  valid: boolean
+  hasChecksums: boolean
+  filesChecked: number
+  bytesChecked: number
+  missing: string[]
+  corrupt: CorruptBundleEntry[]
+  unexpected: string[]
+  trackIssues: BundleTrackIssue[]
+}
+
+/**
+ * Invokes the Tauri backend to check a bundle's files against its checksums and make sure
+ * every track directory holds the audio file its metadata references.
+ *
+ * The same check runs as part of `copyBundleToCache`; this command only reports on a bundle
+ * without importing it. Progress is reported through the `bundle-import-progress` event and the
+ * check can be aborted with `cancelBundleTransfer` using the same `jobId`.
+ *
+ * @param bundlePath - The absolute path to the bundle ZIP file.
+ * @param jobId - Optional id used to match progress events and to cancel the check.
+ * @returns A Promise that resolves to a report listing missing, corrupt and unexpected files.
+ */
+export async function verifyBundle(
+  bundlePath: string,
+  jobId?: string
+): Promise<BundleVerifyReport> {
+  return await invoke<BundleVerifyReport>("fast_upload_verify_bundle", { bundlePath, jobId })
+}
+
 /**
  * Invokes the Tauri backend to copy the music bundle from its original location
  * to a temporary cache directory for processing.
  *
+ * The cached copy is checked against the bundle's embedded checksums before it is extracted, and
+ * the import fails if any file is missing, corrupt or unexpected, or if a track directory does
+ * not hold the audio file its metadata references.
+ *
+ * Progress is reported through the `bundle-import-progress` event and the import can be
+ * aborted with `cancelBundleTransfer` using the same `jobId`.
+ *
  * @param bundlePath - The absolute path to the music bundle directory.
+ * @param jobId - Optional id used to match progress events and to cancel the import.
  * @returns A Promise that resolves to the path of the created cache directory.
  */
-export async function copyBundleToCache(bundlePath: string): Promise<string> {
-  return await invoke<string>("fast_upload_copy_bundle_to_cache", { bundlePath })
+export async function copyBundleToCache(bundlePath: string, jobId?: string): Promise<string> {
+  return await invoke<string>("fast_upload_copy_bundle_to_cache", { bundlePath, jobId })
 }
 
 /**
- * Invokes the Tauri backend to extract the manifest JSON content from the specified cache path.
+ * Invokes the Tauri backend to read the manifest from the specified cache path.
  * This manifest typically contains metadata about the tracks within the bundle.
  *
+ * The manifest and every track's `metadata.json` are validated, and manifests written by older
+ * versions are upgraded to the current format. Bundles from newer versions are rejected.
+ *
  * @param cachePath - The path to the cache directory where the manifest is located.
- * @returns A Promise that resolves to the JSON content of the manifest as a string.
+ * @returns A Promise that resolves to the validated manifest.
  */
-export async function extractManifest(cachePath: string): Promise<string> {
-  return await invoke<string>("fast_upload_extract_manifest", { cachePath })
+export async function extractManifest(cachePath: string): Promise<FastUploadManifest> {
+  return await invoke<FastUploadManifest>("fast_upload_extract_manifest", { cachePath })
 }
 
 /**
@@ -25,7 +25,8 @@ type ValidationResult = {
  * A custom hook that provides validation logic for a fast upload bundle file.
  *
  * This hook encapsulates the process of validating a `.tunno-bundle` file. It
- * copies the bundle to a temporary cache directory, extracts the `manifest.json`,
+ * copies the bundle to a temporary cache directory, where it is checked against its embedded
+ * checksums, extracts the `manifest.json`,
  * and performs a series of checks to ensure the bundle is valid, compatible, and
  * structurally sound before processing can begin. It returns detailed error
  * information if validation fails.
@@ -57,16 +58,7 @@ export function useBundleValidation() {
     try {
       const cachePath = await copyBundleToCache(bundlePath)
 
-      const manifestJson = await extractManifest(cachePath)
-      const manifest = JSON.parse(manifestJson) as FastUploadManifest
-
-      if (!manifest.version || manifest.version !== 1) {
-        return {
-          isValid: false,
-          error: `Bundle version ${manifest.version || "unknown"} is not supported. This app requires version 1. Please update your CLI to the latest version.`,
-          errorType: "incompatible_version"
-        }
-      }
+      const manifest = await extractManifest(cachePath)
 
       if (!manifest.tracks || !Array.isArray(manifest.tracks)) {
         return {
@@ -126,6 +118,14 @@ export function useBundleValidation() {
         }
       }
 
+      if (errorLower.includes("incomplete or damaged")) {
+        return {
+          isValid: false,
+          error: errorMessage,
+          errorType: "corrupted_zip"
+        }
+      }
+
       if (
         errorLower.includes("no space left") ||
         errorLower.includes("disk full") ||
@@ -139,6 +139,14 @@ export function useBundleValidation() {
         }
       }
 
+      if (errorLower.includes("format version")) {
+        return {
+          isValid: false,
+          error: errorMessage,
+          errorType: "incompatible_version"
+        }
+      }
+
       if (
         errorLower.includes("manifest.json") ||
         errorLower.includes("not found") ||
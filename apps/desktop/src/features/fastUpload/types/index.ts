@@ -1,6 +1,7 @@
 /**
  * Represents the structure of the manifest file contained within a fast upload bundle.
  * This manifest provides metadata about the entire bundle and its tracks.
+ * Older bundles are upgraded by the backend, so `version` is always the current format.
  */
 export type FastUploadManifest = {
   version: number
@@ -14,6 +15,16 @@ export type FastUploadManifest = {
     totalTracks: number
   }
   tracks: FastUploadTrackMeta[]
+  playlists: FastUploadPlaylist[]
+}
+
+/**
+ * Represents a playlist within the {@link FastUploadManifest}.
+ * `tracks` lists the `dirName` of each track in playlist order.
+ */
+export type FastUploadPlaylist = {
+  name: string
+  tracks: string[]
 }
 
 /**
@@ -25,6 +36,7 @@ export type FastUploadTrackMeta = {
   artists: string[]
   album: string
   thumbnail: string | null
+  audioFile: string
 }
 
 /**
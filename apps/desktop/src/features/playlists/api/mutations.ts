@@ -1,6 +1,6 @@
 import { database, schema } from "@database/client"
 
-import { and, eq, inArray } from "drizzle-orm"
+import { and, eq, inArray, max } from "drizzle-orm"
 
 import {
   deleteFile,
@@ -199,8 +199,10 @@ export async function deletePlaylist(id: number): Promise<Playlist> {
  * Adds multiple songs to multiple playlists.
  *
  * This function creates associations between a list of song IDs and a list of playlist IDs
- * in the `playlistsToSongs` table. It uses `onConflictDoNothing` to prevent duplicate entries
- * if an association already exists. After adding, it updates the statistics for all affected playlists.
+ * in the `playlistsToSongs` table. New songs are appended after the current last position of
+ * each playlist, in the order they are given. It uses `onConflictDoNothing` to prevent duplicate
+ * entries if an association already exists. After adding, it updates the statistics for all
+ * affected playlists.
  *
  * @param songIds - An array of song IDs to add.
  * @param playlistIds - An array of playlist IDs to add the songs to.
@@ -214,10 +216,24 @@ export async function updateSongsToPlaylists(
     return
   }
 
-  const associations = songIds.flatMap((songId) =>
+  const lastPositions = await database
+    .select({
+      playlistId: schema.playlistsToSongs.playlistId,
+      position: max(schema.playlistsToSongs.position)
+    })
+    .from(schema.playlistsToSongs)
+    .where(inArray(schema.playlistsToSongs.playlistId, playlistIds))
+    .groupBy(schema.playlistsToSongs.playlistId)
+
+  const nextPosition = new Map(
+    lastPositions.map((row) => [row.playlistId, (row.position ?? -1) + 1])
+  )
+
+  const associations = songIds.flatMap((songId, index) =>
     playlistIds.map((playlistId) => ({
       songId,
-      playlistId
+      playlistId,
+      position: (nextPosition.get(playlistId) ?? 0) + index
     }))
   )
 
@@ -134,7 +134,7 @@ export async function getPlaylistById(id: number): Promise<Playlist | null> {
 
 /**
  * Retrieves a single playlist from the database by its ID, including all its relations
- * (songs, stats).
+ * (songs, stats). Songs are returned in playlist order.
  *
  * @param id - The ID of the playlist to retrieve.
  * @returns A Promise that resolves to the `PlaylistWithAllRelations` object.
@@ -149,7 +149,8 @@ export async function getPlaylistByIdWithAllRelations(
       songs: {
         with: {
           song: true
-        }
+        },
+        orderBy: [asc(schema.playlistsToSongs.position), asc(schema.playlistsToSongs.addedAt)]
       },
       stats: true
     }
@@ -163,7 +164,8 @@ export async function getPlaylistByIdWithAllRelations(
 }
 
 /**
- * Retrieves a single playlist from the database by its ID, including its associated songs.
+ * Retrieves a single playlist from the database by its ID, including its associated songs
+ * in playlist order.
  *
  * @param id - The ID of the playlist to retrieve.
  * @returns A Promise that resolves to the `PlaylistWithSongs` object.
@@ -176,7 +178,8 @@ export async function getPlaylistByIdWithSongs(id: number): Promise<PlaylistWith
       songs: {
         with: {
           song: true
-        }
+        },
+        orderBy: [asc(schema.playlistsToSongs.position), asc(schema.playlistsToSongs.addedAt)]
       }
     }
   })
@@ -221,7 +224,7 @@ export async function getPlaylistIdsOnly(params?: QueryPlaylistParams): Promise<
 }
 
 /**
- * Retrieves a list of song IDs that belong to a specific playlist.
+ * Retrieves a list of song IDs that belong to a specific playlist, in playlist order.
  *
  * @param id - The ID of the playlist for which to retrieve song IDs.
  * @returns A Promise that resolves to an array of song IDs.
@@ -231,6 +234,7 @@ export async function getSongIdsByPlaylistId(id: number): Promise<number[]> {
     .select({ songId: schema.playlistsToSongs.songId })
     .from(schema.playlistsToSongs)
     .where(eq(schema.playlistsToSongs.playlistId, id))
+    .orderBy(asc(schema.playlistsToSongs.position), asc(schema.playlistsToSongs.addedAt))
 
   return result.map((row) => row.songId)
 }
@@ -51,7 +51,11 @@ const PlaylistInfo = () => {
 
   const playlist = playlistData
 
-  const songs = songsData ?? []
+  const songs = useMemo(() => {
+    if (!songsData) return []
+    const order = new Map(songIds.map((songId, index) => [songId, index]))
+    return [...songsData].sort((a, b) => (order.get(a.id) ?? 0) - (order.get(b.id) ?? 0))
+  }, [songsData, songIds])
 
   const allSongIds = useMemo(() => songs.map((song) => song.id), [songs])
 
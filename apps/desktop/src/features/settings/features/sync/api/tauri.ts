@@ -1,30 +1,155 @@
 import { invoke } from "@tauri-apps/api/core"
 
-import { type TrackExportData } from "../types"
+/**
+ * Emitted by the backend whenever the sync server starts or stops, including from the tray.
+ * The payload is a {@link ServerStateEvent}.
+ */
+export const SERVER_STATE_EVENT = "sync-server-state"
+
+/**
+ * Emitted when the user picks "Show QR" from the tray menu.
+ */
+export const SHOW_QR_EVENT = "sync-show-qr"
+
+/**
+ * Emitted while an export bundle is being written.
+ * The payload is a {@link BundleTransferProgress}.
+ */
+export const BUNDLE_EXPORT_PROGRESS_EVENT = "bundle-export-progress"
+
+/**
+ * Emitted while a bundle is being copied to the cache and extracted.
+ * The payload is a {@link BundleTransferProgress}.
+ */
+export const BUNDLE_IMPORT_PROGRESS_EVENT = "bundle-import-progress"
 
 /**
- * Track export data structure sent to the Tauri backend.
- * Uses `snake_case` naming to match the corresponding Rust struct.
+ * Progress of a bundle export or import, reported at most every 100ms.
  */
-type TauriTrackExportData = {
-  dir_name: string
-  audio_file: string
-  thumbnails: string[]
-  metadata_json: string
+export type BundleTransferProgress = {
+  jobId: string
+  stage: "copying" | "verifying" | "extracting" | "writing"
+  bytesDone: number
+  bytesTotal: number
+  dirName: string | null
+  etaSecs: number | null
+  finished: boolean
+}
+
+/**
+ * Payload of {@link SERVER_STATE_EVENT}. `port` and `requestedPort` are `null` once the server
+ * has stopped.
+ */
+export type ServerStateEvent = {
+  running: boolean
+  port: number | null
+  requestedPort: number | null
 }
 
 /**
  * Represents the connection details of the running sync server.
+ *
+ * `port` is the port the server listens on. It only differs from `requestedPort`, the configured
+ * port, when `portMode` is `"preferred"` and the configured port was taken.
  */
 export type ServerInfo = {
   ip: string
+  bindAddress: string
   port: number
+  requestedPort: number
   url: string
   endpoints: string[]
+  token: string | null
+  certFingerprint: string | null
+  serviceName: string | null
+}
+
+/**
+ * Per-IP request quotas enforced by the sync server. A value of `0` disables the quota.
+ */
+export type RateLimits = {
+  requestsPerMinute: number
+  pingPerMinute: number
+  connectionPerMinute: number
+}
+
+/**
+ * A network interface address the sync server can bind to or advertise.
+ */
+export type NetworkInterface = {
+  name: string
+  address: string
+  isLoopback: boolean
+  isDefault: boolean
+}
+
+/**
+ * Controls which interface the sync server listens on, which address is advertised to phones
+ * and which client networks (CIDR notation) may connect. An empty allow-list admits everyone.
+ */
+export type NetworkOptions = {
+  bindInterface?: string | null
+  bindAddress?: string | null
+  advertisedAddress?: string | null
+  allowedNetworks?: string[]
+}
+
+/**
+ * Lists the network interfaces and addresses available for the sync server.
+ *
+ * @returns A Promise that resolves to the interfaces, with the default route first.
+ */
+export async function listNetworkInterfaces(): Promise<NetworkInterface[]> {
+  return await invoke<NetworkInterface[]>("list_network_interfaces")
+}
+
+/**
+ * Sync server settings persisted in the `.sync-server.json` store and read by `startServer`.
+ *
+ * With `portMode: "fixed"`, the default, the server fails to start if `port` is taken. With
+ * `"preferred"` it scans `portRange` ports starting at `port` and reports the port it picked. With `serveInBackground` the server starts on
+ * launch and closing the window hides the app to the tray instead of quitting. With `advertise`
+ * the server is published on the LAN as a `_tunno._tcp` service named after `deviceName`, or
+ * the machine's hostname when unset. Pushed uploads larger than `maxUploadMb` are rejected.
+ */
+export type ServerConfig = NetworkOptions & {
+  port: number
+  portMode: "fixed" | "preferred"
+  portRange: number
+  plainHttp: boolean
+  serveInBackground: boolean
+  advertise: boolean
+  deviceName: string | null
+  inactivityTimeoutSecs: number
+  statusIntervalSecs: number
+  maxUploadMb: number
+  rateLimits: RateLimits
+}
+
+/**
+ * Reads the persisted sync server settings, falling back to defaults.
+ *
+ * @returns A Promise that resolves to the current server settings.
+ */
+export async function getServerConfig(): Promise<ServerConfig> {
+  return await invoke<ServerConfig>("get_server_config")
 }
 
 /**
- * Starts the local sync server for mobile pairing.
+ * Validates and persists the sync server settings. They apply the next time the server starts.
+ *
+ * @param serverConfig - The settings to save.
+ * @returns A Promise that resolves to the saved settings.
+ */
+export async function setServerConfig(serverConfig: ServerConfig): Promise<ServerConfig> {
+  return await invoke<ServerConfig>("set_server_config", { serverConfig })
+}
+
+/**
+ * Starts the local sync server for mobile pairing using the persisted server settings.
+ *
+ * The server uses HTTPS with a persisted self-signed certificate unless `plainHttp` is set. The
+ * certificate's SHA-256 fingerprint is returned so the mobile app can pin it.
  *
  * @returns A Promise that resolves with the server connection details.
  */
@@ -67,12 +192,261 @@ export async function getQrData(): Promise<string | null> {
 }
 
 /**
- * Retrieves the current sync status from the running server.
+ * Phases of a sync session. `completed` only returns to `waiting` when the server restarts.
+ */
+export type SyncPhase = "waiting" | "connected" | "syncing" | "completed" | "cancelled" | "timedOut"
+
+/**
+ * Number of songs, albums, artists and playlists moved in one direction.
+ */
+export type EntityCounts = {
+  songs: number
+  albums: number
+  artists: number
+  playlists: number
+}
+
+/**
+ * Paired device that authenticated most recently in the current session.
+ */
+export type SyncDevice = {
+  id: string
+  name: string
+}
+
+/**
+ * Running totals for the current sync session.
+ */
+export type SyncProgress = {
+  bytesSent: number
+  sent: EntityCounts
+  received: EntityCounts
+  batchIndex: number | null
+  batchesSent: number
+}
+
+/**
+ * State of the current sync session, as returned by `getSyncStatus` and carried by
+ * {@link SYNC_STATE_EVENT} and {@link SYNC_PROGRESS_EVENT}.
+ */
+export type SyncSnapshot = {
+  status: SyncPhase
+  device: SyncDevice | null
+  progress: SyncProgress
+  idleSecs: number
+}
+
+/**
+ * Emitted with a {@link SyncSnapshot} whenever the sync phase or connected device changes.
+ */
+export const SYNC_STATE_EVENT = "sync-state"
+
+/**
+ * Emitted with a {@link SyncSnapshot} whenever entities or bytes are transferred.
+ */
+export const SYNC_PROGRESS_EVENT = "sync-progress"
+
+/**
+ * Retrieves the current sync session state from the running server.
+ *
+ * @returns A Promise that resolves to the current {@link SyncSnapshot}.
+ */
+export async function getSyncStatus(): Promise<SyncSnapshot> {
+  return await invoke<SyncSnapshot>("get_sync_status")
+}
+
+/**
+ * Represents a mobile device paired with the sync server.
+ */
+export type PairedDevice = {
+  id: string
+  name: string
+  createdAt: number
+  lastSeenAt: number | null
+  revokedAt: number | null
+}
+
+/**
+ * Lists every device that has been paired with this desktop, including revoked ones.
  *
- * @returns A Promise that resolves to the sync status string ("waiting" | "connected" | "syncing" | "completed").
+ * @returns A Promise that resolves to the paired devices, newest first.
  */
-export async function getSyncStatus(): Promise<string> {
-  return await invoke<string>("get_sync_status")
+export async function listPairedDevices(): Promise<PairedDevice[]> {
+  return await invoke<PairedDevice[]>("list_paired_devices")
+}
+
+/**
+ * Renames a paired device.
+ *
+ * @param deviceId - The ID of the device to rename.
+ * @param name - The new display name.
+ */
+export async function renamePairedDevice(deviceId: string, name: string): Promise<void> {
+  return await invoke<void>("rename_paired_device", { deviceId, name })
+}
+
+/**
+ * Revokes a paired device so its credential is no longer accepted by the sync server.
+ *
+ * @param deviceId - The ID of the device to revoke.
+ */
+export async function revokePairedDevice(deviceId: string): Promise<void> {
+  return await invoke<void>("revoke_paired_device", { deviceId })
+}
+
+/**
+ * A pending pairing request raised by a mobile device.
+ * The same payload is delivered through the `sync-pairing-request` event.
+ */
+export type PairingRequest = {
+  pairingId: string
+  deviceName: string
+  pin: string
+  expiresInSecs: number
+}
+
+/**
+ * Lists pairing requests that are still waiting for approval.
+ *
+ * @returns A Promise that resolves to the pending pairing requests.
+ */
+export async function listPairingRequests(): Promise<PairingRequest[]> {
+  return await invoke<PairingRequest[]>("list_pairing_requests")
+}
+
+/**
+ * Approves a pairing request after the user confirmed the PIN matches the phone.
+ *
+ * @param pairingId - The ID of the pairing request to approve.
+ */
+export async function approvePairing(pairingId: string): Promise<void> {
+  return await invoke<void>("approve_pairing", { pairingId })
+}
+
+/**
+ * Rejects a pairing request.
+ *
+ * @param pairingId - The ID of the pairing request to reject.
+ */
+export async function rejectPairing(pairingId: string): Promise<void> {
+  return await invoke<void>("reject_pairing", { pairingId })
+}
+
+/**
+ * A permission carried by an access token.
+ * `sync:read` grants the compare, delta, tombstone, batch and play history reads. `sync:write`
+ * grants updating and finishing a session and pushing entities and play history.
+ * `playlist:read` grants `GET /api/playlists/:fingerprint`, limited to the token's playlist
+ * allow-list when it has one.
+ */
+export type AccessScope =
+  | "sync:read"
+  | "sync:write"
+  | "files:audio"
+  | "files:thumbnail"
+  | "playlist:read"
+
+/**
+ * Parameters for minting an access token.
+ * Tokens with a playlist or album allow-list may only carry `files:*` and `playlist:read` scopes.
+ */
+export type AccessTokenRequest = {
+  label: string
+  scopes: AccessScope[]
+  playlists?: string[]
+  albums?: string[]
+  ttlSecs: number
+}
+
+/**
+ * A minted access token as stored on the desktop. The signed token itself is never stored.
+ */
+export type AccessTokenRecord = {
+  id: string
+  label: string
+  scopes: AccessScope[]
+  playlists: string[]
+  albums: string[]
+  createdAt: number
+  expiresAt: number
+  revokedAt: number | null
+}
+
+/**
+ * Mints a signed, expiring access token limited to the requested scopes.
+ *
+ * @param request - The label, scopes, optional playlist/album fingerprints and lifetime.
+ * @returns A Promise that resolves with the stored record and the bearer token to share.
+ */
+export async function mintAccessToken(
+  request: AccessTokenRequest
+): Promise<{ record: AccessTokenRecord; token: string }> {
+  return await invoke<{ record: AccessTokenRecord; token: string }>("mint_access_token", {
+    request
+  })
+}
+
+/**
+ * Lists all access tokens, including expired and revoked ones.
+ *
+ * @returns A Promise that resolves to the access tokens, newest first.
+ */
+export async function listAccessTokens(): Promise<AccessTokenRecord[]> {
+  return await invoke<AccessTokenRecord[]>("list_access_tokens")
+}
+
+/**
+ * Revokes an access token so it is rejected immediately.
+ *
+ * @param tokenId - The ID of the token to revoke.
+ */
+export async function revokeAccessToken(tokenId: string): Promise<void> {
+  return await invoke<void>("revoke_access_token", { tokenId })
+}
+
+/**
+ * A client that failed authentication, and its remaining lockout if any.
+ */
+export type BlockedClient = {
+  ip: string
+  failures: number
+  lockedForSecs: number
+  lastFailureSecsAgo: number | null
+}
+
+/**
+ * Lists clients with recent authentication failures, locked-out clients first.
+ *
+ * @returns A Promise that resolves to the clients tracked by the running server.
+ */
+export async function listBlockedClients(): Promise<BlockedClient[]> {
+  return await invoke<BlockedClient[]>("list_blocked_clients")
+}
+
+/**
+ * A Tunno sync server found on the LAN through its `_tunno._tcp` advertisement.
+ */
+export type DiscoveredServer = {
+  serviceName: string
+  deviceName: string
+  host: string
+  addresses: string[]
+  port: number
+  url: string | null
+  protocolVersion: string | null
+  certFingerprint: string | null
+}
+
+/**
+ * Browses the LAN for other Tunno desktops advertising a sync server.
+ *
+ * The running server on this machine is left out of the results.
+ *
+ * @param timeoutMs - How long to listen for answers, 3 seconds by default and at most 30.
+ * @returns A Promise that resolves to the servers that answered in time.
+ */
+export async function discoverSyncServers(timeoutMs?: number): Promise<DiscoveredServer[]> {
+  return await invoke<DiscoveredServer[]>("discover_sync_servers", { timeoutMs })
 }
 
 /**
@@ -85,28 +459,58 @@ export async function backfillFingerprints(): Promise<number> {
 }
 
 /**
- * Invokes the Tauri command to create an export bundle on the native filesystem.
+ * Selects which songs {@link exportLibraryBundle} puts in the bundle.
+ * Playlists, albums and artists are matched by fingerprint and `since` is a Unix timestamp in seconds.
+ */
+export type ExportSelection =
+  | { kind: "all" }
+  | { kind: "favorites" }
+  | { kind: "playlists"; fingerprints: string[] }
+  | { kind: "albums"; fingerprints: string[] }
+  | { kind: "artists"; fingerprints: string[] }
+  | { kind: "addedSince"; since: number }
+
+/**
+ * Describes the bundle written by {@link exportLibraryBundle}.
+ */
+export type LibraryExportResult = {
+  bundlePath: string
+  exportedTracks: number
+  skippedTracks: number
+  playlists: number
+}
+
+/**
+ * Builds an export bundle straight from the library database.
+ *
+ * The backend loads the selected songs with their albums, artists, playlists and thumbnails,
+ * so no track data crosses the IPC boundary. Songs without an album are skipped.
  *
  * @param outputDir - The absolute path to the directory where the bundle will be created.
- * @param manifestJson - A JSON string representing the manifest of all exported data.
- * @param tracksData - An array of track data objects to be included in the bundle.
- * @returns A Promise that resolves with the resulting bundle path.
+ * @param selection - Which songs to export.
+ * @param jobId - Optional id used to match progress events and to cancel the export.
+ * @returns A Promise that resolves to the bundle path and what it contains.
  */
-export async function createExportBundle(
+export async function exportLibraryBundle(
   outputDir: string,
-  manifestJson: string,
-  tracksData: TrackExportData[]
-): Promise<string> {
-  const tauriTracksData: TauriTrackExportData[] = tracksData.map((track) => ({
-    dir_name: track.dirName,
-    audio_file: track.audioFile,
-    thumbnails: track.thumbnails,
-    metadata_json: track.metadataJson
-  }))
-
-  return await invoke<string>("sync_create_bundle", {
+  selection: ExportSelection,
+  jobId?: string
+): Promise<LibraryExportResult> {
+  return await invoke<LibraryExportResult>("sync_export_library", {
     outputDir,
-    manifestJson,
-    tracksData: tauriTracksData
+    selection,
+    jobId
   })
 }
+
+/**
+ * Cancels a running bundle export or import.
+ *
+ * The partial bundle or cache directory is removed and the original call rejects.
+ *
+ * @param jobId - The id passed when the transfer was started.
+ * @returns A Promise that resolves to `true` if a running transfer was found.
+ */
+export async function cancelBundleTransfer(jobId: string): Promise<boolean> {
+  return await invoke<boolean>("cancel_bundle_transfer", { jobId })
+}
@@ -2,6 +2,8 @@ import { useState } from "react"
 
 import { useTranslation } from "@repo/i18n"
 
+import { formatFileSize } from "@repo/utils"
+
 import { useSyncServer } from "../hooks/useSyncServer"
 
 import { QRCodeSVG } from "qrcode.react"
@@ -13,7 +15,15 @@ import { SettingButton } from "@features/settings/components"
 const SyncSection = () => {
   const { t } = useTranslation()
 
-  const { isServerRunning, qrData, syncStatus, startSync, stopSync } = useSyncServer()
+  const {
+    isServerRunning,
+    qrData,
+    syncStatus,
+    syncDevice,
+    syncProgress,
+    startSync,
+    stopSync
+  } = useSyncServer()
 
   const [isStarting, setIsStarting] = useState(false)
   const [error, setError] = useState<string | null>(null)
@@ -87,6 +97,7 @@ const SyncSection = () => {
             <Icon name="CheckCircle" className="text-success" />
             <Typography affects={["small"]}>{t("settings.sync.mobile.deviceConnected")}</Typography>
           </div>
+          {syncDevice && <Typography affects={["small", "muted"]}>{syncDevice.name}</Typography>}
           <Button variant="outline" size="sm" className="w-fit" onClick={handleStopServer}>
             <Icon name="Square" />
             {t("settings.sync.mobile.stopServer")}
@@ -99,6 +110,13 @@ const SyncSection = () => {
             <Spinner />
             <Typography affects={["small"]}>{t("settings.sync.mobile.syncInProgress")}</Typography>
           </div>
+          {syncProgress && (
+            <Typography affects={["small", "muted"]}>
+              {[syncDevice?.name, formatFileSize(syncProgress.bytesSent)]
+                .filter(Boolean)
+                .join(" • ")}
+            </Typography>
+          )}
           <Button variant="outline" size="sm" className="w-fit" onClick={handleStopServer}>
             <Icon name="Square" />
             {t("settings.sync.mobile.stopServer")}
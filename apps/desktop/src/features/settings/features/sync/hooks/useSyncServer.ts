@@ -1,5 +1,7 @@
 import { useCallback, useEffect } from "react"
 
+import { listen } from "@tauri-apps/api/event"
+
 import { useSyncServerStore } from "../stores/useSyncServerStore"
 
 import {
@@ -8,8 +10,13 @@ import {
   getServerInfo,
   getSyncStatus,
   isServerRunning,
+  SERVER_STATE_EVENT,
+  type ServerStateEvent,
   startServer,
-  stopServer
+  stopServer,
+  SYNC_PROGRESS_EVENT,
+  SYNC_STATE_EVENT,
+  type SyncSnapshot
 } from "../api/tauri"
 
 /**
@@ -26,6 +33,8 @@ import {
  * - `serverUrl`: The local network URL of the running server.
  * - `qrData`: JSON string for QR code generation (contains host, port, token).
  * - `syncStatus`: Current sync lifecycle status.
+ * - `syncDevice`: The paired device taking part in the current session, if any.
+ * - `syncProgress`: Running transfer totals for the current session, if any.
  * - `startSync`: Function to backfill fingerprints and start the server.
  * - `stopSync`: Function to stop the server and reset state.
  */
@@ -34,8 +43,11 @@ export function useSyncServer() {
   const serverUrl = useSyncServerStore((state) => state.serverUrl)
   const qrData = useSyncServerStore((state) => state.qrData)
   const syncStatus = useSyncServerStore((state) => state.syncStatus)
+  const syncDevice = useSyncServerStore((state) => state.syncDevice)
+  const syncProgress = useSyncServerStore((state) => state.syncProgress)
   const setServerRunning = useSyncServerStore((state) => state.setServerRunning)
   const setSyncStatus = useSyncServerStore((state) => state.setSyncStatus)
+  const applySnapshot = useSyncServerStore((state) => state.applySnapshot)
   const reset = useSyncServerStore((state) => state.reset)
 
   useEffect(() => {
@@ -53,33 +65,72 @@ export function useSyncServer() {
     checkServerStatus()
   }, [setServerRunning])
 
-  // Poll sync status while server is running
+  // Follow starts and stops triggered outside this hook, e.g. from the tray menu
+  useEffect(() => {
+    const setupServerStateListener = async () => {
+      const unlisten = await listen<ServerStateEvent>(SERVER_STATE_EVENT, async (event) => {
+        if (!event.payload.running) {
+          reset()
+          return
+        }
+
+        const qr = await getQrData()
+        const info = await getServerInfo()
+
+        setServerRunning(true, info?.url ?? undefined, qr ?? undefined)
+      })
+
+      return unlisten
+    }
+
+    let cleanup: (() => void) | undefined
+
+    setupServerStateListener().then((cleanupFn) => {
+      cleanup = cleanupFn
+    })
+
+    return () => {
+      if (cleanup) cleanup()
+    }
+  }, [setServerRunning, reset])
+
+  // Follow sync state transitions and progress pushed by the server
   useEffect(() => {
     if (!isRunning) return
 
-    const interval = setInterval(async () => {
+    const setupSyncListeners = async () => {
+      const unlistenState = await listen<SyncSnapshot>(SYNC_STATE_EVENT, (event) => {
+        applySnapshot(event.payload)
+      })
+      const unlistenProgress = await listen<SyncSnapshot>(SYNC_PROGRESS_EVENT, (event) => {
+        applySnapshot(event.payload)
+      })
+
       try {
-        const status = await getSyncStatus()
-        const validStatuses = [
-          "waiting",
-          "connected",
-          "syncing",
-          "completed",
-          "cancelled",
-          "timedOut"
-        ] as const
-        type ValidStatus = (typeof validStatuses)[number]
-
-        if (validStatuses.includes(status as ValidStatus)) {
-          setSyncStatus(status as ValidStatus)
-        }
+        applySnapshot(await getSyncStatus())
       } catch {
         // Server may have stopped
       }
-    }, 2000)
 
-    return () => clearInterval(interval)
-  }, [isRunning, setSyncStatus])
+      return () => {
+        unlistenState()
+        unlistenProgress()
+      }
+    }
+
+    let cleanup: (() => void) | undefined
+    let cancelled = false
+
+    setupSyncListeners().then((cleanupFn) => {
+      if (cancelled) cleanupFn()
+      else cleanup = cleanupFn
+    })
+
+    return () => {
+      cancelled = true
+      if (cleanup) cleanup()
+    }
+  }, [isRunning, applySnapshot])
 
   const startSync = useCallback(async () => {
     try {
@@ -105,6 +156,8 @@ export function useSyncServer() {
     serverUrl,
     qrData,
     syncStatus,
+    syncDevice,
+    syncProgress,
     startSync,
     stopSync
   }
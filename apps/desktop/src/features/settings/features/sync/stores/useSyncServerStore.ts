@@ -1,5 +1,7 @@
 import { create } from "zustand"
 
+import { type SyncDevice, type SyncProgress, type SyncSnapshot } from "../api/tauri"
+
 /**
  * Represents the possible states of the sync server lifecycle.
  */
@@ -20,6 +22,8 @@ type SyncServerState = {
   serverUrl: string | null
   qrData: string | null
   syncStatus: SyncServerStatus
+  syncDevice: SyncDevice | null
+  syncProgress: SyncProgress | null
 }
 
 /**
@@ -28,6 +32,7 @@ type SyncServerState = {
 type SyncServerActions = {
   setServerRunning: (running: boolean, url?: string, qrData?: string) => void
   setSyncStatus: (status: SyncServerStatus) => void
+  applySnapshot: (snapshot: SyncSnapshot) => void
   reset: () => void
 }
 
@@ -41,30 +46,44 @@ type SyncServerStore = SyncServerState & SyncServerActions
  *
  * This store centralizes all information related to the local sync server,
  * tracking whether it's running, its URL, QR code data for mobile pairing,
- * and the current sync status throughout the connection lifecycle.
+ * and the current sync status, connected device and transfer progress throughout the
+ * connection lifecycle.
  */
 export const useSyncServerStore = create<SyncServerStore>()((set) => ({
   isServerRunning: false,
   serverUrl: null,
   qrData: null,
   syncStatus: "idle",
+  syncDevice: null,
+  syncProgress: null,
   setServerRunning: (running, url, qrData) => {
     set({
       isServerRunning: running,
       serverUrl: url ?? null,
       qrData: qrData ?? null,
-      syncStatus: running ? "waiting" : "idle"
+      syncStatus: running ? "waiting" : "idle",
+      syncDevice: null,
+      syncProgress: null
     })
   },
   setSyncStatus: (syncStatus) => {
     set({ syncStatus })
   },
+  applySnapshot: (snapshot) => {
+    set({
+      syncStatus: snapshot.status,
+      syncDevice: snapshot.device,
+      syncProgress: snapshot.progress
+    })
+  },
   reset: () => {
     set({
       isServerRunning: false,
       serverUrl: null,
       qrData: null,
-      syncStatus: "idle"
+      syncStatus: "idle",
+      syncDevice: null,
+      syncProgress: null
     })
   }
 }))
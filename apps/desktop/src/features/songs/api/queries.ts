@@ -219,7 +219,7 @@ export async function getSongsByIdsWithMainRelations(
 }
 
 /**
- * Retrieves a list of song IDs associated with specific playlist IDs.
+ * Retrieves a list of song IDs associated with specific playlist IDs, in playlist order.
  *
  * @param playlistIds - An array of playlist IDs for which to find associated song IDs.
  * @returns A Promise that resolves to an array of song IDs. Returns an empty array if `playlistIds` is empty.
@@ -231,6 +231,11 @@ export async function getSongIdsByPlaylistIds(playlistIds: number[]): Promise<nu
     .select({ songId: schema.playlistsToSongs.songId })
     .from(schema.playlistsToSongs)
     .where(inArray(schema.playlistsToSongs.playlistId, playlistIds))
+    .orderBy(
+      asc(schema.playlistsToSongs.playlistId),
+      asc(schema.playlistsToSongs.position),
+      asc(schema.playlistsToSongs.addedAt)
+    )
 
   return result.map((row) => row.songId)
 }
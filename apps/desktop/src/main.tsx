@@ -15,6 +15,8 @@ import { useZoom } from "@hooks/useZoom"
 
 import { cleanupAllFastUploadCache } from "@features/fastUpload/api/tauri"
 
+import { SHOW_QR_EVENT } from "@features/settings/features/sync/api/tauri"
+
 import { QueryClientProvider } from "@tanstack/react-query"
 import { ReactQueryDevtools } from "@tanstack/react-query-devtools"
 
@@ -69,6 +71,30 @@ const PlayerFavoriteListener = () => {
   return null
 }
 
+const SyncShowQrListener = () => {
+  useEffect(() => {
+    const setupShowQrListener = async () => {
+      const unlisten = await listen(SHOW_QR_EVENT, () => {
+        router.navigate({ to: "/settings/sync" })
+      })
+
+      return unlisten
+    }
+
+    let cleanup: (() => void) | undefined
+
+    setupShowQrListener().then((cleanupFn) => {
+      cleanup = cleanupFn
+    })
+
+    return () => {
+      if (cleanup) cleanup()
+    }
+  }, [])
+
+  return null
+}
+
 const ZoomManager = () => {
   useZoom()
   return null
@@ -97,6 +123,7 @@ const Main = () => {
       <ThemeProvider>
         <ZoomManager />
         <PlayerFavoriteListener />
+        <SyncShowQrListener />
         <RouterProvider router={router} />
         <Toaster />
       </ThemeProvider>
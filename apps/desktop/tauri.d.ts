@@ -13,16 +13,6 @@ declare module "@tauri-apps/api/core" {
     fast_upload_cleanup_cache_directory: { cachePath: string }
     fast_upload_check_cache_exists: { cachePath: string }
     fast_upload_cleanup_all_cache: void
-    sync_create_bundle: {
-      outputDir: string
-      manifestJson: string
-      tracksData: {
-        dir_name: string
-        audio_file: string
-        thumbnails: string[]
-        metadata_json: string
-      }[]
-    }
     backfill_fingerprints: void
     get_sync_status: void
   }
@@ -45,11 +35,24 @@ declare module "@tauri-apps/api/core" {
     get_qr_data: string | null
     get_audio_duration: number
     fast_upload_copy_bundle_to_cache: string
-    fast_upload_extract_manifest: string
+    fast_upload_extract_manifest: {
+      version: number
+      createdAt: string
+      source: { type: "cli" | "desktop"; version: string; os: string }
+      stats: { totalTracks: number }
+      tracks: {
+        dirName: string
+        title: string
+        artists: string[]
+        album: string
+        thumbnail: string | null
+        audioFile: string
+      }[]
+      playlists: { name: string; tracks: string[] }[]
+    }
     fast_upload_cleanup_cache_directory: void
     fast_upload_check_cache_exists: boolean
     fast_upload_cleanup_all_cache: void
-    sync_create_bundle: string
     backfill_fingerprints: number
     get_sync_status: string
   }
@@ -260,10 +260,23 @@ export function createSyncClient(connectionData: SyncConnectionData) {
 
   /**
    * Notifies the desktop server that the sync has completed successfully.
+   *
+   * The server answers `409 Conflict` when its session already ended, for example after it was
+   * cancelled on the desktop. The local sync still succeeded, so this is only logged.
    */
   async function complete(): Promise<void> {
     try {
-      await fetchWithTimeout(`${url}/api/sync/complete`, { method: "POST", headers }, API_TIMEOUT)
+      const response = await fetchWithTimeout(
+        `${url}/api/sync/complete`,
+        { method: "POST", headers },
+        API_TIMEOUT
+      )
+
+      if (response.status === 409) {
+        const body = (await response.json().catch(() => null)) as { status?: string } | null
+
+        console.warn("SyncClient: Desktop session already ended with status:", body?.status)
+      }
     } catch {
       // Non-critical — desktop will still show stale status but sync succeeded
     }
@@ -271,7 +284,8 @@ export function createSyncClient(connectionData: SyncConnectionData) {
 
   /**
    * Notifies the desktop server that the mobile user cancelled the sync.
-   * Fire-and-forget — errors are silently ignored since the server may already be down.
+   * Fire-and-forget — errors, including the `409 Conflict` returned when the desktop session
+   * already ended, are silently ignored since the server may already be down.
    */
   function abort(): void {
     fetch(`${url}/api/sync/abort`, { method: "POST", headers }).catch(() => {})
@@ -0,0 +1,5 @@
+ALTER TABLE `albums` ADD `thumbnail_sha256` text(64);--> statement-breakpoint
+ALTER TABLE `artists` ADD `thumbnail_sha256` text(64);--> statement-breakpoint
+ALTER TABLE `playlists` ADD `thumbnail_sha256` text(64);--> statement-breakpoint
+ALTER TABLE `songs` ADD `file_sha256` text(64);--> statement-breakpoint
+ALTER TABLE `songs` ADD `thumbnail_sha256` text(64);
\ No newline at end of file
@@ -0,0 +1,10 @@
+ALTER TABLE `playlist_songs` ADD `position` integer DEFAULT 0 NOT NULL;--> statement-breakpoint
+CREATE INDEX `playlist_songs_position_idx` ON `playlist_songs` (`playlist_id`,`position`);--> statement-breakpoint
+UPDATE `playlist_songs` SET `position` = `ranked`.`position` FROM (
+  SELECT `playlist_id`, `song_id`, ROW_NUMBER() OVER (
+    PARTITION BY `playlist_id` ORDER BY `added_at`, `song_id`
+  ) - 1 AS `position`
+  FROM `playlist_songs`
+) AS `ranked`
+WHERE `playlist_songs`.`playlist_id` = `ranked`.`playlist_id`
+  AND `playlist_songs`.`song_id` = `ranked`.`song_id`;
\ No newline at end of file
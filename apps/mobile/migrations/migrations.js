@@ -5,6 +5,8 @@ import m0000 from "./0000_dizzy_tomorrow_man.sql"
 import m0001 from "./0001_acoustic_blob.sql"
 import m0002 from "./0002_aberrant_gravity.sql"
 import m0003 from "./0003_ambiguous_obadiah_stane.sql"
+import m0004 from "./0004_gentle_file_hashes.sql"
+import m0005 from "./0005_ordered_playlist_songs.sql"
 
 export default {
   journal,
@@ -12,6 +14,8 @@ export default {
     m0000,
     m0001,
     m0002,
-    m0003
+    m0003,
+    m0004,
+    m0005
   }
 }
@@ -52,6 +52,8 @@ export const songs = sqliteTable(
       >()
       .default([]),
     fingerprint: text("fingerprint", { length: 64 }),
+    fileSha256: text("file_sha256", { length: 64 }),
+    thumbnailSha256: text("thumbnail_sha256", { length: 64 }),
     playCount: integer("play_count").notNull().default(0),
     lastPlayedAt: integer("last_played_at", { mode: "timestamp" }),
     createdAt: integer("created_at", { mode: "timestamp" })
@@ -138,6 +140,7 @@ export const artists = sqliteTable(
     name: text("name", { length: 100 }).unique().notNull(),
     thumbnail: text("thumbnail", { length: 50 }),
     fingerprint: text("fingerprint", { length: 64 }),
+    thumbnailSha256: text("thumbnail_sha256", { length: 64 }),
     playCount: integer("play_count").notNull().default(0),
     lastPlayedAt: integer("last_played_at", { mode: "timestamp" }),
     isFavorite: integer("is_favorite", { mode: "boolean" }).notNull().default(false),
@@ -212,6 +215,7 @@ export const albums = sqliteTable(
     isFavorite: integer("is_favorite", { mode: "boolean" }).notNull().default(false),
     albumType: text("album_type", { enum: ["single", "album", "compilation"] }).notNull(),
     fingerprint: text("fingerprint", { length: 64 }),
+    thumbnailSha256: text("thumbnail_sha256", { length: 64 }),
     totalTracks: integer("total_tracks").notNull().default(0),
     totalDuration: integer("total_duration").notNull().default(0),
     lastPlayedAt: integer("last_played_at", { mode: "timestamp" }),
@@ -299,6 +303,7 @@ export const playlists = sqliteTable(
     name: text("name", { length: 100 }).unique().notNull(),
     thumbnail: text("thumbnail", { length: 50 }),
     fingerprint: text("fingerprint", { length: 64 }),
+    thumbnailSha256: text("thumbnail_sha256", { length: 64 }),
     playCount: integer("play_count").notNull().default(0),
     lastPlayedAt: integer("last_played_at", { mode: "timestamp" }),
     isFavorite: integer("is_favorite", { mode: "boolean" }).notNull().default(false),
@@ -355,7 +360,8 @@ export const playlistStats = sqliteTable(
 
 /**
  * Schema definition for the `playlist_songs` join table.
- * Links playlists to their songs, supporting many-to-many relationship.
+ * Links playlists to their songs, supporting many-to-many relationship, and keeps
+ * the order of the songs within each playlist.
  */
 export const playlistsToSongs = sqliteTable(
   "playlist_songs",
@@ -366,13 +372,15 @@ export const playlistsToSongs = sqliteTable(
     songId: integer("song_id")
       .notNull()
       .references(() => songs.id, { onDelete: "cascade" }),
+    position: integer("position").notNull().default(0),
     addedAt: integer("added_at", { mode: "timestamp" })
       .notNull()
       .default(sql`(unixepoch())`)
   },
   (table) => [
     primaryKey({ columns: [table.playlistId, table.songId] }),
-    index("playlist_songs_song_idx").on(table.songId)
+    index("playlist_songs_song_idx").on(table.songId),
+    index("playlist_songs_position_idx").on(table.playlistId, table.position)
   ]
 )
 